@@ -5,6 +5,9 @@ use crate::settings;
 
 const DEF_IMAGE_SIZE: u32 = settings::MAPSIZE as u32;
 
+// Upper wall storeys, wall_map_2.png and up, are optional
+pub const MAX_STOREYS: usize = 8;
+
 pub struct Ass {
     pub tile_atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
     pub sprite_atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
@@ -13,11 +16,18 @@ pub struct Ass {
     pub sprite_image: ImageBuffer<Rgba<u8>, Vec<u8>>,
     pub wall_image_bot: ImageBuffer<Rgba<u8>, Vec<u8>>,
     pub wall_image_top: ImageBuffer<Rgba<u8>, Vec<u8>>,
-    pub font: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub wall_images_upper: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>,
+    pub entity_image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub hud_atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub special_image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub height_image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub edge_image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub hud_icons: ImageBuffer<Rgba<u8>, Vec<u8>>,
 }
 
 impl Ass {
-    pub fn load() -> Ass {
+    // Atlases are shared, the map images come from the level directory
+    pub fn load(level_dir: &str) -> Ass {
         let mut img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::new(DEF_IMAGE_SIZE, DEF_IMAGE_SIZE);
 
         for pixel in img.enumerate_pixels_mut() {
@@ -26,19 +36,24 @@ impl Ass {
 
         let mut images: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = Vec::new();
 
-        let paths: Vec<&str> = vec![
-            "assets/texture_atlas.png",
-            "assets/sprite_atlas.png",
-            "assets/floor_map.png",
-            "assets/ceil_map.png",
-            "assets/sprite_map.png",
-            "assets/wall_map_bot.png",
-            "assets/wall_map_top.png",
-            "assets/monospace.png",
+        let paths: Vec<String> = vec![
+            "assets/texture_atlas.png".to_string(),
+            "assets/sprite_atlas.png".to_string(),
+            format!("{}/floor_map.png", level_dir),
+            format!("{}/ceil_map.png", level_dir),
+            format!("{}/sprite_map.png", level_dir),
+            format!("{}/wall_map_bot.png", level_dir),
+            format!("{}/wall_map_top.png", level_dir),
+            format!("{}/entity_map.png", level_dir),
+            "assets/hud_atlas.png".to_string(),
+            format!("{}/special_map.png", level_dir),
+            format!("{}/height_map.png", level_dir),
+            format!("{}/edge_map.png", level_dir),
+            "assets/hud_icons.png".to_string(),
         ];
 
         for path in paths {
-            let image_result = image::open(Path::new(path));
+            let image_result = image::open(Path::new(&path));
             let image = match image_result {
                 Ok(image_result) => image_result.to_rgba8(),
                 Err(_image_result) => img.clone()
@@ -46,6 +61,14 @@ impl Ass {
             images.push(image)
         }
 
+        let mut wall_images_upper = Vec::new();
+        for storey in 2..MAX_STOREYS {
+            match image::open(Path::new(&format!("{}/wall_map_{}.png", level_dir, storey))) {
+                Ok(image) => wall_images_upper.push(image.to_rgba8()),
+                Err(_) => break,
+            }
+        }
+
         Ass {
             tile_atlas: images[0].clone(),
             sprite_atlas: images[1].clone(),
@@ -54,7 +77,13 @@ impl Ass {
             sprite_image: images[4].clone(),
             wall_image_bot: images[5].clone(),
             wall_image_top: images[6].clone(),
-            font: images[7].clone(),
+            wall_images_upper,
+            entity_image: images[7].clone(),
+            hud_atlas: images[8].clone(),
+            special_image: images[9].clone(),
+            height_image: images[10].clone(),
+            edge_image: images[11].clone(),
+            hud_icons: images[12].clone(),
         }
     }
 }
@@ -6,13 +6,20 @@ use miniquad::*;
 use crate::settings::FT_DESIRED;
 //rx.recv_timeout(std::time::Duration::from_secs_f64(FT_DESIRED))
 
-pub fn playback(rx: &Receiver<bool>) {
+pub enum AudioCommand {
+    Music(bool),
+    Track(String),
+    Sound(String),
+}
+
+type Track = (rodio::source::Buffered<rodio::Decoder<BufReader<std::fs::File>>>, f64);
+
+pub fn playback(rx: &Receiver<AudioCommand>) {
     let (_stream, handle) = rodio::OutputStream::try_default().unwrap();
-    let sink = rodio::Sink::try_new(&handle).unwrap();
+    let mut sink = rodio::Sink::try_new(&handle).unwrap();
 
-    let file = std::fs::File::open("assets/music.wav").unwrap();
-    let buffer = rodio::Decoder::new(BufReader::new(file)).unwrap().buffered();
-    let dur = buffer.total_duration().unwrap();
+    // nothing plays until the game picks a track
+    let mut track: Option<Track> = None;
 
     let mut play = true;
     let mut time_start = date::now();
@@ -20,19 +27,27 @@ pub fn playback(rx: &Receiver<bool>) {
     let mut time_paused = 0.0;
     //let mut iter: i32 = 0;
 
-    sink.append(buffer.clone());
-    let mut play_request = rx.try_recv();
-
     loop {
         //iter += 1;
-        match play_request {
-            Ok(play_result) => {
-                play = play_result;
-                //println!("Request sent at loop {}", iter)
-            },
-            Err(_) => {
-            },
-        };
+        while let Ok(command) = rx.try_recv() {
+            match command {
+                AudioCommand::Music(play_result) => {
+                    play = play_result;
+                    //println!("Request sent at loop {}", iter)
+                },
+                AudioCommand::Track(path) => {
+                    // a fresh sink drops whatever was still queued
+                    sink = rodio::Sink::try_new(&handle).unwrap();
+                    track = load_track(&path);
+                    if let Some((buffer, _)) = &track {
+                        sink.append(buffer.clone());
+                    }
+                    time_start = date::now();
+                    time_paused = 0.0;
+                },
+                AudioCommand::Sound(name) => play_sound(&handle, &name),
+            }
+        }
         if play {
             sink.play()
         } else {
@@ -41,13 +56,32 @@ pub fn playback(rx: &Receiver<bool>) {
         }
         std::thread::sleep(std::time::Duration::from_secs_f64(FT_DESIRED));
         time_curr = date::now();
-        if time_curr - time_start - time_paused > dur.as_secs_f64() {
-            sink.append(buffer.clone());
-            time_start = date::now();
-            time_paused = 0.0;
-        } else {
-            play_request = rx.try_recv();
+        if let Some((buffer, dur)) = &track {
+            if time_curr - time_start - time_paused > *dur {
+                sink.append(buffer.clone());
+                time_start = date::now();
+                time_paused = 0.0;
+            }
         }
     }
     
+}
+
+// Missing or unreadable music leaves the level silent
+fn load_track(path: &str) -> Option<Track> {
+    let file = std::fs::File::open(path).ok()?;
+    let buffer = rodio::Decoder::new(BufReader::new(file)).ok()?.buffered();
+    let dur = buffer.total_duration()?.as_secs_f64();
+    Some((buffer, dur))
+}
+
+// One-shot sound effect from assets/sounds, missing files are ignored
+fn play_sound(handle: &rodio::OutputStreamHandle, name: &str) {
+    let file = match std::fs::File::open(format!("assets/sounds/{}.wav", name)) {
+        Ok(file) => file,
+        Err(_) => return,
+    };
+    if let Ok(source) = rodio::Decoder::new(BufReader::new(file)) {
+        let _ = handle.play_raw(source.convert_samples());
+    }
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
 use std::cmp::Ordering;
+use std::collections::HashSet;
 
+use crate::assets;
 use crate::map;
+use crate::movers;
 use crate::player;
 use crate::settings;
 
@@ -16,6 +19,9 @@ use crate::settings;
 // 3 = (i+1,j+1)
 // 4 = (i+1.j)
 
+// Portals seen through portals are followed this many times at most
+const MAX_PORTAL_DEPTH: usize = 4;
+
 pub struct Ray {
     pub angle: f32,
     pub distance: f32,
@@ -40,27 +46,53 @@ impl Ray {
 
 #[derive(Debug, Clone)]
 pub struct FaceData {
-    pub top_right_x: usize,
-    pub top_right_y: usize,
-    pub bottom_right_x: usize,
-    pub bottom_right_y: usize,
-    pub top_left_x: usize,
-    pub top_left_y: usize,
-    pub bottom_left_x: usize,
-    pub bottom_left_y: usize,
+    pub top_right_x: f32,
+    pub top_right_y: f32,
+    pub bottom_right_x: f32,
+    pub bottom_right_y: f32,
+    pub top_left_x: f32,
+    pub top_left_y: f32,
+    pub bottom_left_x: f32,
+    pub bottom_left_y: f32,
+    pub z: f32,
+    pub bottom: f32,
+    pub top: f32,
+    pub floor_z: [f32; 4],
     pub is_wall: bool,
-    pub texture_bot: u8,
-    pub texture_top: u8,
+    // one texture per storey for walls, floor and ceiling for floors, only
+    // the first `storeys` are used
+    pub textures: [u8; assets::MAX_STOREYS],
+    pub storeys: usize,
     pub dist: f32,
 }
 
+impl FaceData {
+    pub fn transformed(&self, transform: &map::Transform) -> FaceData {
+        let (top_right_x, top_right_y) = transform.point(self.top_right_x, self.top_right_y);
+        let (bottom_right_x, bottom_right_y) = transform.point(self.bottom_right_x, self.bottom_right_y);
+        let (bottom_left_x, bottom_left_y) = transform.point(self.bottom_left_x, self.bottom_left_y);
+        let (top_left_x, top_left_y) = transform.point(self.top_left_x, self.top_left_y);
+        FaceData {
+            top_right_x,
+            top_right_y,
+            bottom_right_x,
+            bottom_right_y,
+            bottom_left_x,
+            bottom_left_y,
+            top_left_x,
+            top_left_y,
+            ..self.clone()
+        }
+    }
+}
+
 pub struct FaceBuffer {
     pub faces_dist: Vec<FaceData>,
     pub len: usize,
 }
 
 impl FaceBuffer {
-    pub fn generate(game_map: &map::GameMap, player: &player::Player, settings: &settings::Settings) -> FaceBuffer {
+    pub fn generate(game_map: &map::GameMap, movers: &movers::Movers, player: &player::Player, settings: &settings::Settings) -> FaceBuffer {
         let mut faces_dist: Vec<FaceData> = Vec::new();
         let mut len = 0;
         let xp = player.position.x;
@@ -75,99 +107,43 @@ impl FaceBuffer {
 
         for i in i1..i2 {
             for j in j1..j2 {
-                let xi = i as f32;
-                let yj = j as f32;
                 if game_map.wall_visible[i][j] {
-                
-                    let dist = (xi-xp).powi(2)+(yj+2.0/4.0-yp).powi(2);
-                    faces_dist.push(FaceData { // face 1
-                        top_right_x: i,
-                        top_right_y: j,
-                        bottom_right_x: i,
-                        bottom_right_y: j,
-                        bottom_left_x: i,
-                        bottom_left_y: j+1,
-                        top_left_x: i,
-                        top_left_y: j+1,
-                        is_wall: true,
-                        texture_bot: game_map.wall_bot_array[i][j],
-                        texture_top: game_map.wall_top_array[i][j],
-                        dist,
-                    });
-                    len += 1;
-
-                    let dist = (xi+2.0/4.0-xp).powi(2)+(yj+4.0/4.0-yp).powi(2);
-                    faces_dist.push(FaceData { // face 2
-                        top_right_x: i,
-                        top_right_y: j+1,
-                        bottom_right_x: i,
-                        bottom_right_y: j+1,
-                        bottom_left_x: i+1,
-                        bottom_left_y: j+1,
-                        top_left_x: i+1,
-                        top_left_y: j+1,
-                        is_wall: true,
-                        texture_bot: game_map.wall_bot_array[i][j],
-                        texture_top: game_map.wall_top_array[i][j],
-                        dist,
-                    });
-                    len += 1;
-
-                    let dist = (xi+4.0/4.0-xp).powi(2)+(yj+2.0/4.0-yp).powi(2);
-                    faces_dist.push(FaceData { // face 3
-                        top_right_x: i+1,
-                        top_right_y: j+1,
-                        bottom_right_x: i+1,
-                        bottom_right_y: j+1,
-                        bottom_left_x: i+1,
-                        bottom_left_y: j,
-                        top_left_x: i+1,
-                        top_left_y: j,
-                        is_wall: true,
-                        texture_bot: game_map.wall_bot_array[i][j],
-                        texture_top: game_map.wall_top_array[i][j],
-                        dist,
-                    });
-                    len += 1;
+                    for face in wall_faces(game_map, i, j, xp, yp) {
+                        faces_dist.push(face);
+                        len += 1;
+                    }
+                }
+                if game_map.floor_visible[i][j] {
+                    for face in floor_faces(game_map, i, j, xp, yp) {
+                        faces_dist.push(face);
+                        len += 1;
+                    }
+                }
+            }
+        }
 
-                    let dist = (xi+2.0/4.0-xp).powi(2)+(yj-yp).powi(2);
-                    faces_dist.push(FaceData { // face 4
-                        top_right_x: i+1,
-                        top_right_y: j,
-                        bottom_right_x: i+1,
-                        bottom_right_y: j,
-                        bottom_left_x: i,
-                        bottom_left_y: j,
-                        top_left_x: i,
-                        top_left_y: j,
-                        is_wall: true,
-                        texture_bot: game_map.wall_bot_array[i][j],
-                        texture_top: game_map.wall_top_array[i][j],
-                        dist,
-                    });
+        // what is seen through portals, moved in behind them
+        for view in game_map.portal_views.iter() {
+            let (xv, yv) = view.to_view.inverse().point(xp, yp);
+            for &(i, j) in view.walls.iter() {
+                for face in wall_faces(game_map, i, j, xv, yv) {
+                    faces_dist.push(face.transformed(&view.to_view));
                     len += 1;
-                } 
-                if game_map.floor_visible[i][j] {
-                    let dist = (xi+2.0/4.0-xp).powi(2)+(yj+2.0/4.0-yp).powi(2);
-                    faces_dist.push(FaceData {
-                        top_right_x: i+1,
-                        top_right_y: j+1,
-                        bottom_right_x: i+1,
-                        bottom_right_y: j,
-                        bottom_left_x: i,
-                        bottom_left_y: j,
-                        top_left_x: i,
-                        top_left_y: j+1,
-                        is_wall: false,
-                        texture_bot: game_map.floor_array[i][j],
-                        texture_top: game_map.ceil_array[i][j],
-                        dist,
-                    });
+                }
+            }
+            for &(i, j) in view.floors.iter() {
+                for face in floor_faces(game_map, i, j, xv, yv) {
+                    faces_dist.push(face.transformed(&view.to_view));
                     len += 1;
                 }
             }
         }
 
+        for face in movers.faces(game_map, player) {
+            faces_dist.push(face);
+            len += 1;
+        }
+
         //faces_dist.sort_by(cmp_dist);
 
         FaceBuffer {
@@ -177,11 +153,230 @@ impl FaceBuffer {
     }
 }
 
+// The sides of a wall block that can be seen: not those against another
+// wall block or the edge of the map, and not those that are portals
+fn wall_faces(game_map: &map::GameMap, i: usize, j: usize, xp: f32, yp: f32) -> Vec<FaceData> {
+    let mut faces = Vec::new();
+    let (textures, storeys) = game_map.wall_textures(i, j);
+    for ((di, dj), (x1, y1), (x2, y2)) in edges(i, j) {
+        let (ni, nj) = (i as i32 + di, j as i32 + dj);
+        if !check_ij(ni as usize, nj as usize)
+            || ni < 0
+            || nj < 0
+            || game_map.wall_bot_array[ni as usize][nj as usize] < 255
+            || game_map.portal(ni, nj, -di, -dj).is_some()
+        {
+            continue;
+        }
+        let dist = (0.5 * (x1 + x2) - xp).powi(2) + (0.5 * (y1 + y2) - yp).powi(2);
+        faces.push(FaceData {
+            top_right_x: x1,
+            top_right_y: y1,
+            bottom_right_x: x1,
+            bottom_right_y: y1,
+            bottom_left_x: x2,
+            bottom_left_y: y2,
+            top_left_x: x2,
+            top_left_y: y2,
+            z: 0.0,
+            bottom: 0.0,
+            top: game_map.height,
+            floor_z: [0.0; 4],
+            is_wall: true,
+            textures,
+            storeys,
+            dist,
+        });
+    }
+    faces
+}
+
+// Floor and ceiling of an open tile with the faces standing on its edges
+fn floor_faces(game_map: &map::GameMap, i: usize, j: usize, xp: f32, yp: f32) -> Vec<FaceData> {
+    let mut faces = Vec::new();
+    let xi = i as f32;
+    let yj = j as f32;
+    let corners = game_map.floor_corners(i as i32, j as i32);
+    let dist = (xi+2.0/4.0-xp).powi(2)+(yj+2.0/4.0-yp).powi(2);
+    let mut textures = [game_map.ceil_array[i][j]; assets::MAX_STOREYS];
+    textures[0] = game_map.floor_array[i][j];
+    faces.push(FaceData {
+        top_right_x: xi+1.0,
+        top_right_y: yj+1.0,
+        bottom_right_x: xi+1.0,
+        bottom_right_y: yj,
+        bottom_left_x: xi,
+        bottom_left_y: yj,
+        top_left_x: xi,
+        top_left_y: yj+1.0,
+        z: 0.0,
+        bottom: game_map.floor_height_array[i][j],
+        top: game_map.ceil_height_array[i][j],
+        floor_z: [corners[2], corners[1], corners[0], corners[3]],
+        is_wall: false,
+        textures,
+        storeys: 2,
+        dist,
+    });
+
+    faces.extend(lintels(game_map, i, j, xp, yp));
+    faces.extend(risers(game_map, i, j, xp, yp));
+
+    if let Some(wall) = game_map.thin_array[i][j] {
+        let dist = (0.5 * (wall.x1 + wall.x2) - xp).powi(2) + (0.5 * (wall.y1 + wall.y2) - yp).powi(2);
+        faces.push(FaceData {
+            top_right_x: wall.x1,
+            top_right_y: wall.y1,
+            bottom_right_x: wall.x1,
+            bottom_right_y: wall.y1,
+            bottom_left_x: wall.x2,
+            bottom_left_y: wall.y2,
+            top_left_x: wall.x2,
+            top_left_y: wall.y2,
+            z: 0.0,
+            bottom: wall.bottom,
+            top: wall.top,
+            floor_z: [0.0; 4],
+            is_wall: true,
+            textures: [wall.texture; assets::MAX_STOREYS],
+            storeys: storeys(wall.top),
+            dist,
+        });
+    }
+    faces
+}
+
+// Faces closing the gap between a low ceiling and the higher ceilings of
+// open neighbours, textured with the wall top texture of the low tile.
+fn lintels(game_map: &map::GameMap, i: usize, j: usize, xp: f32, yp: f32) -> Vec<FaceData> {
+    let mut faces = Vec::new();
+    let low = game_map.ceil_height_array[i][j];
+    if low >= game_map.height {
+        return faces;
+    }
+    let texture = if game_map.wall_top_array[i][j] < 255 {
+        game_map.wall_top_array[i][j]
+    } else {
+        game_map.ceil_array[i][j]
+    };
+    for ((di, dj), (x1, y1), (x2, y2)) in edges(i, j) {
+        let (ni, nj) = (i as i32 + di, j as i32 + dj);
+        if game_map.is_solid(ni, nj) {
+            continue;
+        }
+        let high = game_map.ceil_height(ni, nj);
+        if high <= low {
+            continue;
+        }
+        let dist = (0.5 * (x1 + x2) - xp).powi(2) + (0.5 * (y1 + y2) - yp).powi(2);
+        faces.push(FaceData {
+            top_right_x: x1,
+            top_right_y: y1,
+            bottom_right_x: x1,
+            bottom_right_y: y1,
+            bottom_left_x: x2,
+            bottom_left_y: y2,
+            top_left_x: x2,
+            top_left_y: y2,
+            z: 0.0,
+            bottom: low,
+            top: high,
+            floor_z: [0.0; 4],
+            is_wall: true,
+            textures: [texture; assets::MAX_STOREYS],
+            storeys: storeys(high),
+            dist,
+        });
+    }
+    faces
+}
+
+// Storeys a face up to `top` reaches into
+fn storeys(top: f32) -> usize {
+    (top.ceil().max(0.0) as usize).min(assets::MAX_STOREYS)
+}
+
+// Neighbour offset and the shared edge, in the order of the wall faces
+type Edge = ((i32, i32), (f32, f32), (f32, f32));
+
+fn edges(i: usize, j: usize) -> [Edge; 4] {
+    let xi = i as f32;
+    let yj = j as f32;
+    [
+        ((-1, 0), (xi, yj), (xi, yj + 1.0)),
+        ((0, 1), (xi, yj + 1.0), (xi + 1.0, yj + 1.0)),
+        ((1, 0), (xi + 1.0, yj + 1.0), (xi + 1.0, yj)),
+        ((0, -1), (xi + 1.0, yj), (xi, yj)),
+    ]
+}
+
+// Step faces from a floor down to the lower floors of open neighbours,
+// textured with the floor texture of the higher tile. Steps up to a
+// neighbour that is not visible itself are added here so they are not lost.
+// Where a ramp meets a lower floor along its side, the gap up to the lower
+// end of the ramp edge is closed.
+fn risers(game_map: &map::GameMap, i: usize, j: usize, xp: f32, yp: f32) -> Vec<FaceData> {
+    let mut faces = Vec::new();
+    let (ii, jj) = (i as i32, j as i32);
+    for ((di, dj), (x1, y1), (x2, y2)) in edges(i, j) {
+        let (ni, nj) = (ii + di, jj + dj);
+        if game_map.is_solid(ni, nj) {
+            continue;
+        }
+        let own = (game_map.floor_plane(ii, jj, x1, y1), game_map.floor_plane(ii, jj, x2, y2));
+        let other = (game_map.floor_plane(ni, nj, x1, y1), game_map.floor_plane(ni, nj, x2, y2));
+
+        let (bottom, top, start, end, texture) = if own.0.min(own.1) > other.0.max(other.1) {
+            (other.0.max(other.1), own.0.min(own.1), (x1, y1), (x2, y2), game_map.floor_array[i][j])
+        } else if other.0.min(other.1) > own.0.max(own.1) && !game_map.floor_visible[ni as usize][nj as usize] {
+            let texture = game_map.floor_array[ni as usize][nj as usize];
+            (own.0.max(own.1), other.0.min(other.1), (x2, y2), (x1, y1), texture)
+        } else {
+            continue;
+        };
+
+        let dist = (0.5 * (x1 + x2) - xp).powi(2) + (0.5 * (y1 + y2) - yp).powi(2);
+        faces.push(FaceData {
+            top_right_x: start.0,
+            top_right_y: start.1,
+            bottom_right_x: start.0,
+            bottom_right_y: start.1,
+            bottom_left_x: end.0,
+            bottom_left_y: end.1,
+            top_left_x: end.0,
+            top_left_y: end.1,
+            z: 0.0,
+            bottom,
+            top,
+            floor_z: [0.0; 4],
+            is_wall: true,
+            textures: [texture; assets::MAX_STOREYS],
+            storeys: storeys(top),
+            dist,
+        });
+    }
+    faces
+}
+
+// Distance along the ray to an opaque thin wall in tile (i,j)
+fn thin_wall_hit(game_map: &map::GameMap, i: usize, j: usize, x: f32, y: f32, cos_a: f32, sin_a: f32) -> Option<f32> {
+    game_map
+        .thin_wall(i as i32, j as i32)
+        .filter(|wall| wall.opaque)
+        .and_then(|wall| wall.ray_hit(x, y, cos_a, sin_a))
+}
+
 pub fn ray_cast(game_map: &mut map::GameMap, player: &player::Player, settings: &settings::Settings) -> Vec<Ray> {
     let mut rays: Vec<Ray> = Vec::new();
 
     game_map.wall_visible = vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE];
     game_map.floor_visible = vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE];
+    // views are kept from frame to frame with their sets emptied, so a
+    // chain of portals allocates them only once
+    for view in game_map.portal_views.iter_mut() {
+        view.floors.clear();
+        view.walls.clear();
+    }
 
     let screen_dist: f32 = settings.screen_width_f / 2.0 / settings.fov_xy.tan();
     let scale = settings.screen_width_f / (settings.draw_rays_num as f32);
@@ -233,7 +428,15 @@ pub fn ray_cast(game_map: &mut map::GameMap, player: &player::Player, settings:
             (i_hor, j_hor) = (x_hor.floor() as usize, y_hor.floor() as usize);
 
             if check_ij(i_hor, j_hor) {
-                if game_map.wall_bot_array[i_hor][j_hor] < 255 {
+                let thin_hit = thin_wall_hit(game_map, i_hor, j_hor, xp, yp, cos_a, sin_a);
+                if game_map.wall_bot_array[i_hor][j_hor] < 255
+                    || game_map.opaque_array[i_hor][j_hor]
+                    || game_map.is_closed(i_hor as i32, j_hor as i32)
+                {
+                    check = true;
+                } else if let Some(hit) = thin_hit {
+                    game_map.floor_visible[i_hor][j_hor] = true;
+                    dist_hor = hit;
                     check = true;
                 } else {
                     game_map.floor_visible[i_hor][j_hor] = true;
@@ -269,7 +472,15 @@ pub fn ray_cast(game_map: &mut map::GameMap, player: &player::Player, settings:
             (i_vert, j_vert) = (x_vert.floor() as usize, y_vert.floor() as usize);
 
             if check_ij(i_vert, j_vert) {
-                if game_map.wall_bot_array[i_vert][j_vert] < 255 {
+                let thin_hit = thin_wall_hit(game_map, i_vert, j_vert, xp, yp, cos_a, sin_a);
+                if game_map.wall_bot_array[i_vert][j_vert] < 255
+                    || game_map.opaque_array[i_vert][j_vert]
+                    || game_map.is_closed(i_vert as i32, j_vert as i32)
+                {
+                    check = true;
+                } else if let Some(hit) = thin_hit {
+                    game_map.floor_visible[i_vert][j_vert] = true;
+                    dist_vert = hit;
                     check = true;
                 } else {
                     game_map.floor_visible[i_vert][j_vert] = true;
@@ -293,6 +504,10 @@ pub fn ray_cast(game_map: &mut map::GameMap, player: &player::Player, settings:
             ray.distance = dist_hor;
             if check_ij(i_hor, j_hor) && game_map.wall_bot_array[i_hor][j_hor] < 255 {
                 game_map.wall_visible[i_hor][j_hor] = true;
+            } else if check_ij(i_hor, j_hor)
+                && (game_map.opaque_array[i_hor][j_hor] || game_map.is_closed(i_hor as i32, j_hor as i32))
+            {
+                game_map.floor_visible[i_hor][j_hor] = true;
             }
             ray.i = i_hor;
             ray.j = j_hor;
@@ -303,6 +518,10 @@ pub fn ray_cast(game_map: &mut map::GameMap, player: &player::Player, settings:
             ray.distance = dist_vert;
             if check_ij(i_vert, j_vert) && game_map.wall_bot_array[i_vert][j_vert] < 255 {
                 game_map.wall_visible[i_vert][j_vert] = true;
+            } else if check_ij(i_vert, j_vert)
+                && (game_map.opaque_array[i_vert][j_vert] || game_map.is_closed(i_vert as i32, j_vert as i32))
+            {
+                game_map.floor_visible[i_vert][j_vert] = true;
             }
             ray.i = i_vert;
             ray.j = j_vert;
@@ -310,6 +529,21 @@ pub fn ray_cast(game_map: &mut map::GameMap, player: &player::Player, settings:
             ray.y = yp + (dist_vert+1.0)*sin_a;
         };
 
+        // rays ending on a portal go on from its far side
+        let (hit_i, hit_j) = (ray.i as i32, ray.j as i32);
+        let (di, dj) = if dist_vert > dist_hor {
+            (0, if sin_a > 0.0 { 1 } else { -1 })
+        } else {
+            (if cos_a > 0.0 { 1 } else { -1 }, 0)
+        };
+        if ray.distance < settings.draw_max_dist && game_map.is_solid(hit_i, hit_j) {
+            if let Some(k) = game_map.portal(hit_i - di, hit_j - dj, di, dj) {
+                let (x, y) = (xp + ray.distance * cos_a, yp + ray.distance * sin_a);
+                let remaining = settings.draw_max_dist - ray.distance;
+                follow_portal(game_map, vec![k], map::Transform::identity(), (x, y), (cos_a, sin_a), remaining);
+            }
+        }
+
         rays.push(ray);
 
         let q = scale / screen_dist;
@@ -330,6 +564,134 @@ pub fn ray_cast(game_map: &mut map::GameMap, player: &player::Player, settings:
     
 }
 
+// Carries on a ray that reached the last portal of `chain` at (x,y), both
+// in the space the ray has been travelling in. Tiles on the far side go
+// into the view of that chain of portals.
+fn follow_portal(
+    game_map: &mut map::GameMap,
+    chain: Vec<usize>,
+    to_view: map::Transform,
+    (x, y): (f32, f32),
+    (cos_a, sin_a): (f32, f32),
+    max_dist: f32,
+) {
+    let portal = game_map.portals[chain[chain.len() - 1]];
+    let (x, y) = portal.transform.point(x, y);
+    let (cos_a, sin_a) = portal.transform.vector(cos_a, sin_a);
+    let to_view = portal.transform.inverse().then(&to_view);
+
+    let view = match game_map.portal_views.iter().position(|v| v.chain == chain) {
+        Some(view) => view,
+        None => {
+            game_map.portal_views.push(map::PortalView {
+                chain: chain.clone(),
+                to_view,
+                floors: HashSet::new(),
+                walls: HashSet::new(),
+            });
+            game_map.portal_views.len() - 1
+        }
+    };
+
+    let mut i = portal.to_i as i32;
+    let mut j = portal.to_j as i32;
+    let step_i = if cos_a > 0.0 { 1 } else { -1 };
+    let step_j = if sin_a > 0.0 { 1 } else { -1 };
+    let d_dist_x = if cos_a.abs() > settings::TOLERANCE { (1.0 / cos_a).abs() } else { f32::INFINITY };
+    let d_dist_y = if sin_a.abs() > settings::TOLERANCE { (1.0 / sin_a).abs() } else { f32::INFINITY };
+    let mut dist_x = if cos_a > 0.0 { (i as f32 + 1.0 - x) * d_dist_x } else { (x - i as f32) * d_dist_x }.max(0.0);
+    let mut dist_y = if sin_a > 0.0 { (j as f32 + 1.0 - y) * d_dist_y } else { (y - j as f32) * d_dist_y }.max(0.0);
+
+    loop {
+        if !check_ij(i as usize, j as usize) || i < 0 || j < 0 {
+            return;
+        }
+        let (iu, ju) = (i as usize, j as usize);
+        game_map.portal_views[view].floors.insert((iu, ju));
+        if game_map.opaque_array[iu][ju]
+            || game_map.is_closed(i, j)
+            || thin_wall_hit(game_map, iu, ju, x, y, cos_a, sin_a).is_some()
+        {
+            return;
+        }
+
+        let (dist, di, dj) = if dist_x < dist_y {
+            dist_x += d_dist_x;
+            (dist_x - d_dist_x, step_i, 0)
+        } else {
+            dist_y += d_dist_y;
+            (dist_y - d_dist_y, 0, step_j)
+        };
+        if dist > max_dist {
+            return;
+        }
+        i += di;
+        j += dj;
+
+        if game_map.is_solid(i, j) {
+            if check_ij(i as usize, j as usize) && i >= 0 && j >= 0 {
+                game_map.portal_views[view].walls.insert((i as usize, j as usize));
+            }
+            if chain.len() < MAX_PORTAL_DEPTH {
+                if let Some(k) = game_map.portal(i - di, j - dj, di, dj) {
+                    let mut next = chain.clone();
+                    next.push(k);
+                    let hit = (x + dist * cos_a, y + dist * sin_a);
+                    follow_portal(game_map, next, to_view, hit, (cos_a, sin_a), max_dist - dist);
+                }
+            }
+            return;
+        }
+    }
+}
+
+// Distance along (cos_a, sin_a) to the first solid tile, capped at max_dist
+pub fn ray_distance(game_map: &map::GameMap, x: f32, y: f32, cos_a: f32, sin_a: f32, max_dist: f32) -> f32 {
+    let mut i = x.floor() as i32;
+    let mut j = y.floor() as i32;
+
+    let step_i = if cos_a > 0.0 { 1 } else { -1 };
+    let step_j = if sin_a > 0.0 { 1 } else { -1 };
+
+    let d_dist_x = if cos_a.abs() > settings::TOLERANCE { (1.0 / cos_a).abs() } else { f32::INFINITY };
+    let d_dist_y = if sin_a.abs() > settings::TOLERANCE { (1.0 / sin_a).abs() } else { f32::INFINITY };
+
+    let mut dist_x = if cos_a > 0.0 {
+        (i as f32 + 1.0 - x) * d_dist_x
+    } else {
+        (x - i as f32) * d_dist_x
+    };
+    let mut dist_y = if sin_a > 0.0 {
+        (j as f32 + 1.0 - y) * d_dist_y
+    } else {
+        (y - j as f32) * d_dist_y
+    };
+
+    loop {
+        // thin walls of the tile the ray is in, the first one included
+        if i >= 0 && j >= 0 {
+            if let Some(hit) = thin_wall_hit(game_map, i as usize, j as usize, x, y, cos_a, sin_a) {
+                return hit.min(max_dist);
+            }
+        }
+        let dist = if dist_x < dist_y {
+            i += step_i;
+            dist_x += d_dist_x;
+            dist_x - d_dist_x
+        } else {
+            j += step_j;
+            dist_y += d_dist_y;
+            dist_y - d_dist_y
+        };
+        if dist > max_dist {
+            return max_dist;
+        }
+        if game_map.is_solid(i, j) {
+            return dist;
+        }
+    }
+}
+
 fn _cmp_dist(a: &FaceData, b: &FaceData) -> Ordering {
     if a.dist < b.dist {
         return Ordering::Greater;
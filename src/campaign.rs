@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+
+pub const CAMPAIGN_PATH: &str = "assets/campaign.txt";
+
+#[derive(Debug, Clone)]
+pub struct LevelInfo {
+    pub dir: String,
+    pub music: String,
+    pub title: String,
+}
+
+pub struct Campaign {
+    pub levels: Vec<LevelInfo>,
+    pub current: usize,
+}
+
+impl Campaign {
+    // One `level <directory> <music> <title>` line per level, in order, with
+    // paths relative to the campaign file. Without a usable campaign the
+    // game plays the first level directory on its own.
+    pub fn load(path: &str) -> Campaign {
+        let text = fs::read_to_string(path).unwrap_or_default();
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        let relative = |name: &str| base.join(name).to_string_lossy().into_owned();
+
+        let mut levels = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() < 3 || words[0] != "level" {
+                continue;
+            }
+            levels.push(LevelInfo {
+                dir: relative(words[1]),
+                music: relative(words[2]),
+                title: words[3..].join(" "),
+            });
+        }
+
+        if levels.is_empty() {
+            levels.push(LevelInfo {
+                dir: relative("levels/1"),
+                music: relative("music.wav"),
+                title: String::new(),
+            });
+        }
+
+        Campaign { levels, current: 0 }
+    }
+
+    pub fn level(&self) -> &LevelInfo {
+        &self.levels[self.current]
+    }
+
+    // A level by its number counting from 1, its directory or its title
+    pub fn find(&self, name: &str) -> Option<usize> {
+        if let Ok(number) = name.parse::<usize>() {
+            if (1..=self.levels.len()).contains(&number) {
+                return Some(number - 1);
+            }
+        }
+        self.levels.iter().position(|level| Path::new(&level.dir).ends_with(name) || level.title.eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.current + 1 >= self.levels.len()
+    }
+}
+
+// Shown on the intermission screen
+pub struct Stats {
+    pub time: f32,
+    pub kills: u32,
+    pub kills_total: u32,
+    pub secrets: u32,
+    pub secrets_total: u32,
+}
+
+impl Stats {
+    pub fn new(kills_total: u32, secrets_total: u32) -> Stats {
+        Stats {
+            time: 0.0,
+            kills: 0,
+            kills_total,
+            secrets: 0,
+            secrets_total,
+        }
+    }
+
+    pub fn time_text(&self) -> String {
+        let seconds = self.time as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -0,0 +1,342 @@
+use miniquad::KeyCode;
+
+use crate::{richtext, settings, text};
+
+// Lines kept in the scrollback and commands kept in the history
+const LOG_MAX: usize = 500;
+const HISTORY_MAX: usize = 50;
+// Scrollback lines shown above the input line
+pub const VISIBLE_LINES: usize = 14;
+pub const BACKGROUND: (f32, f32, f32, f32) = (0.06, 0.05, 0.08, 0.85);
+
+// Commands besides the cvars, for help and completion
+const COMMANDS: [(&str, &str); 10] = [
+    ("help", "lists the commands"),
+    ("cvars", "[prefix]  lists the cvars and their values"),
+    ("clear", "empties the console"),
+    ("tp", "<x> <y>  moves the player"),
+    ("noclip", "walks through walls"),
+    ("god", "takes no damage"),
+    ("map", "<number|directory|title>  plays a level of the campaign"),
+    ("screenshot", "saves the screen to a png"),
+    ("quit", "leaves the game"),
+    ("<cvar>", "[value]  shows or sets a setting"),
+];
+
+// What the console leaves to the stage to carry out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Teleport(f32, f32),
+    Noclip,
+    God,
+    Map(String),
+    Screenshot,
+    Quit,
+    // a cvar was set, some take effect only through the stage
+    Changed(String),
+}
+
+// Drop-down console with a scrollback log, command history and tab
+// completion of commands and cvars
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub log: Vec<String>,
+    pub history: Vec<String>,
+    // position while going through the history with up and down
+    browse: Option<usize>,
+    // lines scrolled back from the end of the log
+    pub scroll: usize,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        let mut console =
+            Console { open: false, input: String::new(), log: Vec::new(), history: Vec::new(), browse: None, scroll: 0 };
+        console.print("Type help for the commands, ` closes the console.");
+        console
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.scroll = 0;
+    }
+
+    pub fn print(&mut self, text: &str) {
+        self.log.extend(text.lines().map(|line| line.to_string()));
+        if self.log.len() > LOG_MAX {
+            self.log.drain(..self.log.len() - LOG_MAX);
+        }
+        self.scroll = 0;
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        if !c.is_control() && c != '`' {
+            self.input.push(c);
+            self.browse = None;
+        }
+    }
+
+    // Editing keys, Enter runs the line
+    pub fn key(&mut self, keycode: KeyCode, settings: &mut settings::Settings) -> Option<Command> {
+        match keycode {
+            KeyCode::Enter | KeyCode::KpEnter => return self.submit(settings),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Tab => self.complete(),
+            KeyCode::Up => self.recall(-1),
+            KeyCode::Down => self.recall(1),
+            KeyCode::PageUp => self.scroll = (self.scroll + VISIBLE_LINES / 2).min(self.log.len().saturating_sub(1)),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(VISIBLE_LINES / 2),
+            KeyCode::Escape => self.open = false,
+            _ => {}
+        }
+        None
+    }
+
+    fn recall(&mut self, step: i32) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() as i32 - 1;
+        let pos = match self.browse {
+            Some(pos) => pos as i32 + step,
+            None if step < 0 => last,
+            None => return,
+        };
+        if pos > last {
+            self.browse = None;
+            self.input.clear();
+        } else {
+            let pos = pos.max(0) as usize;
+            self.browse = Some(pos);
+            self.input = self.history[pos].clone();
+        }
+    }
+
+    fn submit(&mut self, settings: &mut settings::Settings) -> Option<Command> {
+        let line = std::mem::take(&mut self.input);
+        self.browse = None;
+        if line.trim().is_empty() {
+            return None;
+        }
+        self.print(&format!("> {}", line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > HISTORY_MAX {
+                self.history.remove(0);
+            }
+        }
+        self.run(&line, settings)
+    }
+
+    // Console commands run right here, the ones touching the game are
+    // returned
+    pub fn run(&mut self, line: &str, settings: &mut settings::Settings) -> Option<Command> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => None,
+            ["help"] => {
+                for (name, about) in COMMANDS {
+                    self.print(&format!("{} {}", name, about));
+                }
+                None
+            }
+            ["cvars", rest @ ..] => {
+                let prefix = rest.first().copied().unwrap_or("");
+                for name in settings::CVARS.iter().filter(|name| name.starts_with(prefix)) {
+                    self.print(&format!("{} = {}", name, settings.cvar(name).unwrap_or_default()));
+                }
+                None
+            }
+            ["clear"] => {
+                self.log.clear();
+                None
+            }
+            ["tp", x, y] if x.parse::<f32>().is_ok() && y.parse::<f32>().is_ok() => {
+                Some(Command::Teleport(x.parse().ok()?, y.parse().ok()?))
+            }
+            ["tp", ..] => {
+                self.print("Usage: tp <x> <y>");
+                None
+            }
+            ["noclip"] => Some(Command::Noclip),
+            ["god"] => Some(Command::God),
+            ["map", name @ ..] if !name.is_empty() => Some(Command::Map(name.join(" "))),
+            ["map"] => {
+                self.print("Usage: map <number|directory|title>");
+                None
+            }
+            ["screenshot"] => Some(Command::Screenshot),
+            ["quit"] => Some(Command::Quit),
+            [name] if settings.cvar(name).is_some() => {
+                self.print(&format!("{} = {}", name, settings.cvar(name)?));
+                None
+            }
+            [name, value] if settings.cvar(name).is_some() => match settings.set_cvar(name, value) {
+                Ok(()) => {
+                    self.print(&format!("{} = {}", name, settings.cvar(name)?));
+                    Some(Command::Changed(name.to_string()))
+                }
+                Err(error) => {
+                    self.print(&error);
+                    None
+                }
+            },
+            [name, ..] => {
+                self.print(&format!("Unknown command {}", name));
+                None
+            }
+        }
+    }
+
+    // Completes the first word to the longest start all matching commands
+    // and cvars share, several matches are listed
+    fn complete(&mut self) {
+        if self.input.contains(' ') {
+            return;
+        }
+        let commands = COMMANDS.iter().map(|(name, _)| *name).filter(|name| !name.starts_with('<'));
+        let matches: Vec<&str> = commands
+            .chain(settings::CVARS.iter().copied())
+            .filter(|name| name.starts_with(self.input.as_str()))
+            .collect();
+        match matches.as_slice() {
+            [] => {}
+            [name] => self.input = format!("{} ", name),
+            _ => {
+                let mut common = matches[0].to_string();
+                for name in &matches[1..] {
+                    while !name.starts_with(common.as_str()) {
+                        common.pop();
+                    }
+                }
+                if common.len() > self.input.len() {
+                    self.input = common;
+                } else {
+                    self.print(&matches.join("  "));
+                }
+            }
+        }
+    }
+
+    // The scrollback and the input line as overlay text at the top of the
+    // screen, long lines wrap
+    pub fn overlay(&self, fonts: &text::Fonts, settings: &settings::Settings) -> text::Overlay {
+        let end = self.log.len() - self.scroll.min(self.log.len());
+        let start = end.saturating_sub(VISIBLE_LINES);
+        let mut lines: Vec<String> = self.log[start..end].iter().map(|line| richtext::escape(line)).collect();
+        lines.push(format!("] {}_", richtext::escape(&self.input)));
+
+        let mut overlay = text::Overlay::new_from(fonts, Vec::new());
+        let max_width = settings.screen_width_f - 2.0 * overlay.x0;
+        // the newest lines are kept at the bottom, above the input line
+        let mut y = self.height(fonts);
+        for line in lines.iter().rev() {
+            let text_box = richtext::TextBox::new(fonts, line, overlay.font, Some(max_width), richtext::Align::Left);
+            y -= text_box.height;
+            if y < 0.0 {
+                break;
+            }
+            overlay.push(&text_box, overlay.x0, y);
+        }
+        overlay
+    }
+
+    // Bottom of the console in pixels
+    pub fn height(&self, fonts: &text::Fonts) -> f32 {
+        (VISIBLE_LINES + 1) as f32 * fonts.line_height(text::MONO) + 10.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_commands_and_checks_cvars() {
+        let mut console = Console::new();
+        let mut settings = settings::Settings::init();
+        assert_eq!(console.run("tp 10 12.5", &mut settings), Some(Command::Teleport(10.0, 12.5)));
+        assert_eq!(console.run("tp here", &mut settings), None);
+        assert_eq!(console.log.last().unwrap(), "Usage: tp <x> <y>");
+        assert_eq!(console.run("map 2", &mut settings), Some(Command::Map("2".to_string())));
+
+        assert_eq!(console.run("hud_scale 2", &mut settings), Some(Command::Changed("hud_scale".to_string())));
+        assert_eq!(settings.hud_scale, 2.0);
+        assert_eq!(console.log.last().unwrap(), "hud_scale = 2");
+
+        // out of range or not a number, the value is kept
+        let rays = settings.draw_rays_num;
+        assert_eq!(console.run("draw_rays_num 100000", &mut settings), None);
+        assert_eq!(settings.draw_rays_num, rays);
+        assert!(console.log.last().unwrap().starts_with("draw_rays_num must be between"));
+        for value in ["0", "-1", "NaN", "inf"] {
+            assert_eq!(console.run(&format!("player_radius {}", value), &mut settings), None);
+            assert_eq!(settings.player_radius, 0.5);
+        }
+        assert_eq!(console.run("draw_max_dist 0", &mut settings), None);
+        assert_eq!(console.run("music_playing maybe", &mut settings), None);
+        assert_eq!(console.log.last().unwrap(), "maybe is not a valid bool");
+
+        // derived and per-frame values are not cvars, the ones they follow
+        // keep them in step
+        assert_eq!(console.run("delta_time 1", &mut settings), None);
+        assert_eq!(console.log.last().unwrap(), "Unknown command delta_time");
+        assert_eq!(console.run("fov_xy 0", &mut settings), None);
+        assert_eq!(settings.fov_xy, settings::PI / 4.0);
+        assert!(console.run("fov_xy 1", &mut settings).is_some());
+        assert_eq!(settings.fov_z, 1.0 / settings.screen_aspect);
+
+        assert_eq!(console.run("frobnicate", &mut settings), None);
+        assert_eq!(console.log.last().unwrap(), "Unknown command frobnicate");
+    }
+
+    #[test]
+    fn completes_commands_and_cvars() {
+        let mut console = Console::new();
+        console.input = "noc".to_string();
+        console.complete();
+        assert_eq!(console.input, "noclip ");
+
+        // several matches complete as far as they agree, then are listed
+        console.input = "dra".to_string();
+        console.complete();
+        assert_eq!(console.input, "draw_");
+        console.complete();
+        assert_eq!(console.input, "draw_");
+        assert!(console.log.last().unwrap().contains("draw_rays_num"));
+
+        console.input = "zzz".to_string();
+        console.complete();
+        assert_eq!(console.input, "zzz");
+    }
+
+    #[test]
+    fn recalls_the_history() {
+        let mut console = Console::new();
+        let mut settings = settings::Settings::init();
+        for line in ["help", "god", "god", "clear"] {
+            console.input = line.to_string();
+            console.submit(&mut settings);
+        }
+        // repeated lines are kept once
+        assert_eq!(console.history, vec!["help", "god", "clear"]);
+
+        console.recall(1);
+        assert_eq!(console.input, "");
+        console.recall(-1);
+        assert_eq!(console.input, "clear");
+        console.recall(-1);
+        console.recall(-1);
+        console.recall(-1);
+        assert_eq!(console.input, "help");
+        console.recall(1);
+        assert_eq!(console.input, "god");
+        console.recall(1);
+        console.recall(1);
+        assert_eq!(console.input, "");
+        assert_eq!(console.browse, None);
+    }
+}
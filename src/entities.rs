@@ -0,0 +1,687 @@
+use crate::inventory;
+use crate::map;
+use crate::pathfinding;
+use crate::player;
+use crate::settings;
+
+// Entity codes in the red channel of the entity map, 255 = empty
+pub const MAP_BARREL: u8 = 0;
+pub const MAP_ENEMY: u8 = 16;
+pub const MAP_HEALTH: u8 = 32;
+pub const MAP_ARMOUR: u8 = 48;
+pub const MAP_AMMO: u8 = 64;
+pub const MAP_KEY_RED: u8 = 80;
+pub const MAP_KEY_BLUE: u8 = 96;
+pub const MAP_KEY_YELLOW: u8 = 112;
+pub const MAP_COLLECTIBLE: u8 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntityKind {
+    Barrel,
+    Enemy,
+    Corpse,
+    Puff,
+    Pickup(inventory::Item),
+}
+
+impl EntityKind {
+    pub fn from_map(value: u8) -> Option<EntityKind> {
+        match value {
+            MAP_BARREL => Some(EntityKind::Barrel),
+            MAP_ENEMY => Some(EntityKind::Enemy),
+            MAP_HEALTH => Some(EntityKind::Pickup(inventory::Item::Health)),
+            MAP_ARMOUR => Some(EntityKind::Pickup(inventory::Item::Armour)),
+            MAP_AMMO => Some(EntityKind::Pickup(inventory::Item::Ammo)),
+            MAP_KEY_RED => Some(EntityKind::Pickup(inventory::Item::Key(inventory::KeyColor::Red))),
+            MAP_KEY_BLUE => Some(EntityKind::Pickup(inventory::Item::Key(inventory::KeyColor::Blue))),
+            MAP_KEY_YELLOW => Some(EntityKind::Pickup(inventory::Item::Key(inventory::KeyColor::Yellow))),
+            MAP_COLLECTIBLE => Some(EntityKind::Pickup(inventory::Item::Collectible)),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EntityKind> {
+        match name {
+            "barrel" => Some(EntityKind::Barrel),
+            "enemy" => Some(EntityKind::Enemy),
+            "health" => Some(EntityKind::Pickup(inventory::Item::Health)),
+            "armour" => Some(EntityKind::Pickup(inventory::Item::Armour)),
+            "ammo" => Some(EntityKind::Pickup(inventory::Item::Ammo)),
+            "gem" => Some(EntityKind::Pickup(inventory::Item::Collectible)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub a: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Velocity {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteInfo {
+    pub texture: u8,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Collider {
+    pub radius: f32,
+    pub solid: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Health {
+    pub current: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AiState {
+    Idle,
+    Patrol,
+    Chase,
+    Search,
+    Return,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ai {
+    pub state: AiState,
+    pub speed: f32,
+    pub sight_range: f32,
+    pub fov: f32,
+    pub give_up_time: f32,
+    pub patrol: Vec<(f32, f32)>,
+    pub patrol_index: usize,
+    pub path: Vec<(usize, usize)>,
+    pub target: (f32, f32),
+    pub lost_time: f32,
+    pub repath_time: f32,
+    pub anim_time: f32,
+    pub attack_range: f32,
+    pub attack_damage: f32,
+    pub attack_cooldown: f32,
+    pub attack_time: f32,
+}
+
+impl Ai {
+    pub fn new(speed: f32) -> Ai {
+        Ai {
+            state: AiState::Idle,
+            speed,
+            sight_range: 24.0,
+            fov: settings::PI * 2.0 / 3.0,
+            give_up_time: 5.0,
+            patrol: Vec::new(),
+            patrol_index: 0,
+            path: Vec::new(),
+            target: (0.0, 0.0),
+            lost_time: 0.0,
+            repath_time: 0.0,
+            anim_time: 0.0,
+            attack_range: 1.2,
+            attack_damage: 10.0,
+            attack_cooldown: 0.0,
+            attack_time: 0.0,
+        }
+    }
+
+    // Patrol back and forth between the spawn point and the far end of
+    // the longest free corridor leading away from it.
+    fn plan_patrol(&mut self, game_map: &map::GameMap, x: f32, y: f32) {
+        let i = x.floor() as i32;
+        let j = y.floor() as i32;
+        let mut best = (0, 0, 0);
+        for (di, dj) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let mut n = 0;
+            while n < 8 && !game_map.is_solid(i + di * (n + 1), j + dj * (n + 1)) {
+                n += 1;
+            }
+            if n > best.2 {
+                best = (di, dj, n);
+            }
+        }
+        self.patrol = vec![(x, y)];
+        if best.2 > 1 {
+            let n = best.2 - 1;
+            self.patrol.push((
+                (i + best.0 * n) as f32 + 0.5,
+                (j + best.1 * n) as f32 + 0.5,
+            ));
+        }
+        self.patrol_index = 0;
+        self.state = AiState::Patrol;
+    }
+
+    fn sees(&self, game_map: &map::GameMap, position: &Position, player: &player::Player) -> bool {
+        if !player.status.alive {
+            return false;
+        }
+        let dx = player.position.x - position.x;
+        let dy = player.position.y - position.y;
+        let dist = (dx * dx + dy * dy).sqrt();
+        if dist > self.sight_range {
+            return false;
+        }
+        if self.state != AiState::Chase && self.state != AiState::Search {
+            let mut da = dy.atan2(dx) - position.a;
+            while da > settings::PI {
+                da -= 2.0 * settings::PI;
+            }
+            while da < -settings::PI {
+                da += 2.0 * settings::PI;
+            }
+            if da.abs() > 0.5 * self.fov {
+                return false;
+            }
+        }
+        // opaque thin walls block the view where they cross it
+        let (cos_a, sin_a) = (dx / dist.max(settings::TOLERANCE), dy / dist.max(settings::TOLERANCE));
+        pathfinding::line_of_sight(position.x, position.y, player.position.x, player.position.y, |i, j| {
+            game_map.is_solid(i, j)
+                || game_map
+                    .thin_wall(i, j)
+                    .filter(|wall| wall.opaque)
+                    .and_then(|wall| wall.ray_hit(position.x, position.y, cos_a, sin_a))
+                    .is_some_and(|t| t <= dist)
+        })
+    }
+
+    fn repath(&mut self, game_map: &map::GameMap, position: &Position, goal: (f32, f32)) {
+        let start = (position.x.floor() as usize, position.y.floor() as usize);
+        let goal = (goal.0.floor() as usize, goal.1.floor() as usize);
+        self.path = pathfinding::find_path(
+            start,
+            goal,
+            settings::MAPSIZE,
+            4096,
+            |i, j| !game_map.is_solid(i as i32, j as i32),
+            |(i, j), (ni, nj)| {
+                let reach = game_map.floor_height_array[i][j] + player::STEP_HEIGHT;
+                !game_map.thin_wall_between(i as i32, j as i32, ni as i32, nj as i32, reach)
+            },
+        )
+        .unwrap_or_default();
+    }
+
+    // Returns the desired velocity towards the next path node, or towards
+    // `goal` directly when it is in the current tile.
+    fn follow(&mut self, position: &Position, goal: (f32, f32)) -> (f32, f32) {
+        let (tx, ty) = loop {
+            match self.path.first() {
+                Some(&(i, j)) => {
+                    let (tx, ty) = (i as f32 + 0.5, j as f32 + 0.5);
+                    if (tx - position.x).powi(2) + (ty - position.y).powi(2) < 0.01 {
+                        self.path.remove(0);
+                    } else {
+                        break (tx, ty);
+                    }
+                }
+                None => break goal,
+            }
+        };
+        let dx = tx - position.x;
+        let dy = ty - position.y;
+        let d = (dx * dx + dy * dy).sqrt();
+        if d < 0.05 {
+            (0.0, 0.0)
+        } else {
+            let v = self.speed.min(d * 10.0);
+            (v * dx / d, v * dy / d)
+        }
+    }
+
+    // Melee attack, returns the damage dealt to the player this tick
+    fn attack(&mut self, position: &Position, player: &player::Player, dt: f32) -> f32 {
+        self.attack_cooldown = (self.attack_cooldown - dt).max(0.0);
+        self.attack_time = (self.attack_time - dt).max(0.0);
+        if self.state != AiState::Chase || !player.status.alive || self.attack_cooldown > 0.0 {
+            return 0.0;
+        }
+        let dist = (player.position.x - position.x).powi(2) + (player.position.y - position.y).powi(2);
+        if dist > self.attack_range.powi(2) {
+            return 0.0;
+        }
+        self.attack_cooldown = 1.0;
+        self.attack_time = 0.3;
+        self.attack_damage
+    }
+
+    fn think(&mut self, game_map: &map::GameMap, position: &Position, player: &player::Player, dt: f32) -> (f32, f32) {
+        if self.state == AiState::Idle {
+            self.plan_patrol(game_map, position.x, position.y);
+        }
+
+        let player_pos = (player.position.x, player.position.y);
+
+        if self.sees(game_map, position, player) {
+            if self.state != AiState::Chase {
+                self.repath_time = 0.0;
+            }
+            self.state = AiState::Chase;
+            self.target = player_pos;
+            self.lost_time = 0.0;
+        } else if self.state == AiState::Chase {
+            self.state = AiState::Search;
+            self.repath_time = 0.0;
+        }
+
+        self.repath_time -= dt;
+
+        match self.state {
+            AiState::Chase => {
+                if self.repath_time <= 0.0 {
+                    self.repath(game_map, position, self.target);
+                    self.repath_time = 0.5;
+                }
+                let dist = (self.target.0 - position.x).powi(2) + (self.target.1 - position.y).powi(2);
+                if dist < 1.0 {
+                    (0.0, 0.0)
+                } else {
+                    self.follow(position, self.target)
+                }
+            }
+            AiState::Search => {
+                self.lost_time += dt;
+                if self.repath_time <= 0.0 {
+                    self.repath(game_map, position, self.target);
+                    self.repath_time = 1.0;
+                }
+                if self.lost_time > self.give_up_time {
+                    self.state = AiState::Return;
+                    self.repath_time = 0.0;
+                }
+                self.follow(position, self.target)
+            }
+            AiState::Return => {
+                let home = self.patrol[0];
+                if self.repath_time <= 0.0 {
+                    self.repath(game_map, position, home);
+                    self.repath_time = 2.0;
+                }
+                if (home.0 - position.x).powi(2) + (home.1 - position.y).powi(2) < 0.01 {
+                    self.state = AiState::Patrol;
+                    self.patrol_index = 0;
+                }
+                self.follow(position, home)
+            }
+            AiState::Patrol | AiState::Idle => {
+                let goal = self.patrol[self.patrol_index];
+                if (goal.0 - position.x).powi(2) + (goal.1 - position.y).powi(2) < 0.01 {
+                    self.patrol_index = (self.patrol_index + 1) % self.patrol.len();
+                    self.path.clear();
+                }
+                let goal = self.patrol[self.patrol_index];
+                let (vx, vy) = self.follow(position, goal);
+                (0.5 * vx, 0.5 * vy)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub id: EntityId,
+    pub kind: EntityKind,
+    pub position: Position,
+    pub velocity: Option<Velocity>,
+    pub sprite: Option<SpriteInfo>,
+    pub collider: Option<Collider>,
+    pub health: Option<Health>,
+    pub ai: Option<Ai>,
+    pub lifetime: Option<f32>,
+    pub hurt_time: f32,
+    pub alive: bool,
+}
+
+impl Entity {
+    pub fn new(id: EntityId, kind: EntityKind, x: f32, y: f32) -> Entity {
+        let mut entity = Entity {
+            id,
+            kind,
+            position: Position { x, y, z: 0.0, a: 0.0 },
+            velocity: None,
+            sprite: None,
+            collider: None,
+            health: None,
+            ai: None,
+            lifetime: None,
+            hurt_time: 0.0,
+            alive: true,
+        };
+
+        match kind {
+            EntityKind::Barrel => {
+                entity.sprite = Some(SpriteInfo { texture: 1, width: 0.6, height: 0.9 });
+                entity.collider = Some(Collider { radius: 0.4, solid: true });
+                entity.health = Some(Health { current: 20.0 });
+            }
+            EntityKind::Enemy => {
+                entity.velocity = Some(Velocity { x: 0.0, y: 0.0 });
+                entity.sprite = Some(SpriteInfo { texture: 2, width: 0.8, height: 1.0 });
+                entity.collider = Some(Collider { radius: 0.35, solid: true });
+                entity.health = Some(Health { current: 50.0 });
+                entity.ai = Some(Ai::new(3.0));
+            }
+            EntityKind::Corpse => {
+                entity.sprite = Some(SpriteInfo { texture: 6, width: 1.0, height: 1.0 });
+            }
+            EntityKind::Puff => {
+                entity.sprite = Some(SpriteInfo { texture: 7, width: 0.3, height: 0.3 });
+                entity.lifetime = Some(0.25);
+            }
+            EntityKind::Pickup(item) => {
+                let texture = match item {
+                    inventory::Item::Health => 16,
+                    inventory::Item::Ammo => 17,
+                    inventory::Item::Key(inventory::KeyColor::Red) => 18,
+                    inventory::Item::Key(inventory::KeyColor::Blue) => 19,
+                    inventory::Item::Key(inventory::KeyColor::Yellow) => 20,
+                    inventory::Item::Collectible => 21,
+                    inventory::Item::Armour => 22,
+                };
+                entity.sprite = Some(SpriteInfo { texture, width: 0.4, height: 0.4 });
+            }
+        }
+
+        entity
+    }
+
+    pub fn damage(&mut self, amount: f32, player: &player::Player) {
+        if let Some(health) = self.health.as_mut() {
+            health.current -= amount;
+        }
+        self.hurt_time = 0.15;
+        if let Some(ai) = self.ai.as_mut() {
+            ai.state = AiState::Chase;
+            ai.target = (player.position.x, player.position.y);
+            ai.lost_time = 0.0;
+            ai.repath_time = 0.0;
+        }
+    }
+
+    // `solids` are the other entities that block movement, as position and
+    // radius
+    fn step(
+        &mut self,
+        game_map: &map::GameMap,
+        player: &mut player::Player,
+        settings: &settings::Settings,
+        solids: &[(EntityId, f32, f32, f32)],
+    ) {
+        if let Some(health) = self.health {
+            if health.current <= 0.0 {
+                self.alive = false;
+                return;
+            }
+        }
+
+        if let Some(lifetime) = self.lifetime.as_mut() {
+            *lifetime -= settings.delta_time;
+            if *lifetime <= 0.0 {
+                self.alive = false;
+                return;
+            }
+        }
+
+        self.hurt_time = (self.hurt_time - settings.delta_time).max(0.0);
+
+        if let Some(ai) = self.ai.as_mut() {
+            let (vx, vy) = ai.think(game_map, &self.position, player, settings.delta_time);
+            let damage = ai.attack(&self.position, player, settings.delta_time);
+            player.damage(damage, player::DamageSource::Enemy);
+            if let Some(velocity) = self.velocity.as_mut() {
+                velocity.x = vx;
+                velocity.y = vy;
+            }
+            if vx.abs() + vy.abs() > settings::TOLERANCE {
+                ai.anim_time += settings.delta_time;
+            }
+            if let Some(sprite) = self.sprite.as_mut() {
+                sprite.texture = if self.hurt_time > 0.0 {
+                    5
+                } else if ai.attack_time > 0.0 {
+                    4
+                } else {
+                    2 + ((2.0 * ai.anim_time) as u8) % 2
+                };
+            }
+        }
+
+        let (vx, vy) = match self.velocity {
+            Some(v) => (v.x, v.y),
+            None => return,
+        };
+        let radius = match self.collider {
+            Some(c) => c.radius,
+            None => 0.0,
+        };
+
+        // enemies take steps but not ledges or fences
+        let floor = game_map.floor_z(self.position.x, self.position.y);
+        let climbs = |x: f32, y: f32| {
+            game_map.floor_z(x, y) > floor + player::STEP_HEIGHT
+                || game_map.thin_wall_dist(x, y, floor + player::STEP_HEIGHT) < radius
+        };
+        // moving apart is always allowed, so entities that overlap can
+        // get free of each other
+        let (x0, y0, id) = (self.position.x, self.position.y, self.id);
+        let bumps = |x: f32, y: f32| {
+            solids.iter().any(|&(other, ox, oy, r)| {
+                let d = (x - ox).powi(2) + (y - oy).powi(2);
+                other != id && d < (radius + r).powi(2) && d < (x0 - ox).powi(2) + (y0 - oy).powi(2)
+            })
+        };
+        let x = self.position.x + vx * settings.delta_time;
+        if !blocked(game_map, x, self.position.y, radius) && !climbs(x, self.position.y) && !bumps(x, self.position.y) {
+            self.position.x = x;
+        }
+        let y = self.position.y + vy * settings.delta_time;
+        if !blocked(game_map, self.position.x, y, radius) && !climbs(self.position.x, y) && !bumps(self.position.x, y) {
+            self.position.y = y;
+        }
+        if vx.abs() + vy.abs() > settings::TOLERANCE {
+            self.position.a = vy.atan2(vx);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EntityList {
+    pub entities: Vec<Entity>,
+    pub removed: Vec<EntityId>,
+    pub kills: u32,
+    // enemies spawned so far, from the map or later, the most there are to kill
+    pub enemies: u32,
+    map_count: u32,
+    next_id: u32,
+}
+
+impl EntityList {
+    pub fn new() -> EntityList {
+        EntityList {
+            entities: Vec::new(),
+            removed: Vec::new(),
+            kills: 0,
+            enemies: 0,
+            map_count: 0,
+            next_id: 0,
+        }
+    }
+
+    pub fn spawn_from_map(game_map: &map::GameMap) -> EntityList {
+        let mut list = EntityList::new();
+        for i in 0..settings::MAPSIZE {
+            for j in 0..settings::MAPSIZE {
+                if let Some(kind) = EntityKind::from_map(game_map.entity_array[i][j]) {
+                    list.spawn(kind, i as f32 + 0.5, j as f32 + 0.5);
+                }
+            }
+        }
+        list.map_count = list.next_id;
+        list
+    }
+
+    // Nothing is spawned outside the map
+    pub fn spawn(&mut self, kind: EntityKind, x: f32, y: f32) -> Option<EntityId> {
+        let inside = 0.0..settings::MAPSIZE as f32;
+        if !inside.contains(&x) || !inside.contains(&y) {
+            return None;
+        }
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        if kind == EntityKind::Enemy {
+            self.enemies += 1;
+        }
+        self.entities.push(Entity::new(id, kind, x, y));
+        Some(id)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.iter().find(|e| e.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities.iter_mut().find(|e| e.id == id)
+    }
+
+    // Map entities are remembered as removed right away, so a save made in
+    // the same frame already leaves them out
+    pub fn despawn(&mut self, id: EntityId) {
+        if let Some(entity) = self.get_mut(id) {
+            entity.alive = false;
+            self.forget(id);
+        }
+    }
+
+    // Keeps a map entity that is gone for good out of save games
+    fn forget(&mut self, id: EntityId) {
+        if id.0 < self.map_count && !self.removed.contains(&id) {
+            self.removed.push(id);
+        }
+    }
+
+    pub fn count(&self, kind: EntityKind) -> usize {
+        self.entities.iter().filter(|e| e.kind == kind).count()
+    }
+
+    pub fn pickups_in_reach(&self, x: f32, y: f32, reach: f32) -> Vec<(EntityId, inventory::Item)> {
+        self.entities
+            .iter()
+            .filter(|e| e.alive)
+            .filter(|e| (e.position.x - x).powi(2) + (e.position.y - y).powi(2) < reach * reach)
+            .filter_map(|e| match e.kind {
+                EntityKind::Pickup(item) => Some((e.id, item)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn update(&mut self, game_map: &map::GameMap, player: &mut player::Player, settings: &settings::Settings) {
+        let solids: Vec<(EntityId, f32, f32, f32)> = self
+            .entities
+            .iter()
+            .filter_map(|e| match e.collider {
+                Some(c) if c.solid && e.alive => Some((e.id, e.position.x, e.position.y, c.radius)),
+                _ => None,
+            })
+            .collect();
+        for entity in self.entities.iter_mut() {
+            entity.step(game_map, player, settings, &solids);
+            entity.position.z = game_map.floor_z(entity.position.x, entity.position.y);
+        }
+
+        let corpses: Vec<(f32, f32)> = self
+            .entities
+            .iter()
+            .filter(|e| !e.alive && e.kind == EntityKind::Enemy)
+            .map(|e| (e.position.x, e.position.y))
+            .collect();
+
+        let dead: Vec<EntityId> = self.entities.iter().filter(|e| !e.alive).map(|e| e.id).collect();
+        for id in dead {
+            self.forget(id);
+        }
+
+        self.entities.retain(|e| e.alive);
+
+        self.kills += corpses.len() as u32;
+        for (x, y) in corpses {
+            self.spawn(EntityKind::Corpse, x, y);
+        }
+    }
+}
+
+pub fn blocked(game_map: &map::GameMap, x: f32, y: f32, radius: f32) -> bool {
+    let corners = [
+        (x - radius, y - radius),
+        (x + radius, y - radius),
+        (x - radius, y + radius),
+        (x + radius, y + radius),
+    ];
+    corners
+        .iter()
+        .any(|&(cx, cy)| game_map.is_solid(cx.floor() as i32, cy.floor() as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_with(entities: &[(usize, usize, u8)]) -> map::GameMap {
+        let mut game_map = map::GameMap::empty();
+        for &(i, j, code) in entities {
+            game_map.entity_array[i][j] = code;
+        }
+        game_map
+    }
+
+    #[test]
+    fn opaque_thin_walls_block_sight() {
+        let settings = settings::Settings::init();
+        let mut player = player::Player::new(&settings);
+        player.place(14.5, 10.5, 0.0, &settings);
+        let position = Position { x: 10.5, y: 10.5, z: 0.0, a: 0.0 };
+        let ai = Ai::new(1.0);
+        let mut game_map = map::GameMap::empty();
+        assert!(ai.sees(&game_map, &position, &player));
+        game_map.thin_array[12][10] = map::ThinWall::new(12, 10, map::EDGE_DIAGONAL, 1, 255, 0.0, map::CEILING);
+        assert!(!ai.sees(&game_map, &position, &player));
+        // bars can be seen through
+        game_map.thin_array[12][10] = map::ThinWall::new(12, 10, map::EDGE_WEST, map::SEE_THROUGH[0], 255, 0.0, map::CEILING);
+        assert!(ai.sees(&game_map, &position, &player));
+        // a wall beyond the player is not in the way
+        game_map.thin_array[15][10] = map::ThinWall::new(15, 10, map::EDGE_DIAGONAL, 1, 255, 0.0, map::CEILING);
+        assert!(ai.sees(&game_map, &position, &player));
+    }
+
+    #[test]
+    fn despawned_map_entities_are_removed_at_once() {
+        let game_map = map_with(&[(10, 10, MAP_HEALTH), (12, 10, MAP_AMMO)]);
+        let mut list = EntityList::spawn_from_map(&game_map);
+        let spawned = list.spawn(EntityKind::Barrel, 20.5, 20.5).unwrap();
+        assert_eq!(list.spawn(EntityKind::Barrel, -0.5, 20.5), None);
+        list.despawn(EntityId(1));
+        list.despawn(spawned);
+        assert_eq!(list.removed, vec![EntityId(1)]);
+
+        let settings = settings::Settings::init();
+        let mut player = player::Player::new(&settings);
+        list.update(&game_map, &mut player, &settings);
+        assert_eq!(list.removed, vec![EntityId(1)]);
+        assert_eq!(list.entities.len(), 1);
+    }
+}
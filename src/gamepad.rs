@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use crate::input::Nav;
+
+// Linux joystick interface, one 8 byte event per button press or axis move
+const DEVICE: &str = "/dev/input/js0";
+const EVENT_BUTTON: u8 = 0x01;
+const EVENT_AXIS: u8 = 0x02;
+const EVENT_INIT: u8 = 0x80;
+
+// Buttons and axes as numbered for Xbox style pads
+const BUTTON_A: u8 = 0;
+const BUTTON_B: u8 = 1;
+const BUTTON_START: u8 = 7;
+const STICK_X: u8 = 0;
+const STICK_Y: u8 = 1;
+const DPAD_X: u8 = 6;
+const DPAD_Y: u8 = 7;
+const DEAD_ZONE: i16 = 16000;
+
+// Reads the first gamepad on a thread of its own and sends its D-pad, left
+// stick and buttons on as menu navigation. Without a pad it looks again
+// every second, so one plugged in later is picked up.
+pub fn listen(tx: Sender<Nav>) {
+    thread::spawn(move || loop {
+        if let Ok(mut device) = File::open(DEVICE) {
+            read_events(&mut device, &tx);
+        }
+        thread::sleep(Duration::from_secs(1));
+    });
+}
+
+// Returns when the pad is unplugged or nobody listens any more
+fn read_events(device: &mut File, tx: &Sender<Nav>) -> Option<()> {
+    let mut event = [0u8; 8];
+    // the way each axis points, so that holding it sends a single step
+    let mut axes = [0; 256];
+    loop {
+        device.read_exact(&mut event).ok()?;
+        let value = i16::from_le_bytes([event[4], event[5]]);
+        let kind = event[6];
+        let number = event[7];
+        if kind & EVENT_INIT != 0 {
+            continue;
+        }
+
+        let nav = if kind == EVENT_BUTTON && value == 1 {
+            match number {
+                BUTTON_A => Some(Nav::Activate),
+                BUTTON_B | BUTTON_START => Some(Nav::Back),
+                _ => None,
+            }
+        } else if kind == EVENT_AXIS {
+            let way = if value > DEAD_ZONE { 1 } else if value < -DEAD_ZONE { -1 } else { 0 };
+            let was = axes[number as usize];
+            axes[number as usize] = way;
+            match (number, way) {
+                _ if way == was => None,
+                (STICK_X | DPAD_X, -1) => Some(Nav::Left),
+                (STICK_X | DPAD_X, 1) => Some(Nav::Right),
+                (STICK_Y | DPAD_Y, -1) => Some(Nav::Up),
+                (STICK_Y | DPAD_Y, 1) => Some(Nav::Down),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        if let Some(nav) = nav {
+            tx.send(nav).ok()?;
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::inventory;
+use crate::lang;
+use crate::mesh::TextureUV;
+use crate::player;
+use crate::richtext;
+use crate::settings;
+use crate::text;
+use crate::weapon;
+
+// assets/hud_icons.png, square icons in 32 pixel cells
+pub const ICONS_SIZE: f32 = 128.0;
+const CELL: f32 = 32.0;
+// Pixels between the HUD and the edges of the screen
+const MARGIN: f32 = 20.0;
+
+// Degrees of heading the compass shows either side of the centre
+const COMPASS_SPAN: f32 = 60.0;
+const COMPASS_POINTS: [&str; 8] =
+    ["compass.n", "compass.ne", "compass.e", "compass.se", "compass.s", "compass.sw", "compass.w", "compass.nw"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Icon {
+    Health,
+    Armour,
+    Ammo,
+    Key,
+    Cross,
+    Dot,
+    Circle,
+    Marker,
+    // plain white, for bars and ticks
+    Solid,
+}
+
+impl Icon {
+    pub fn uv(self) -> TextureUV {
+        let (i, j) = match self {
+            Icon::Health => (0.0, 0.0),
+            Icon::Armour => (1.0, 0.0),
+            Icon::Ammo => (2.0, 0.0),
+            Icon::Key => (3.0, 0.0),
+            Icon::Cross => (0.0, 1.0),
+            Icon::Dot => (1.0, 1.0),
+            Icon::Circle => (2.0, 1.0),
+            Icon::Marker => (3.0, 1.0),
+            Icon::Solid => (0.0, 2.0),
+        };
+        // the solid patch is sampled well inside so its edges never blur in
+        let inset = if self == Icon::Solid { 8.0 } else { 0.0 };
+        let mut tex_uv = TextureUV {
+            u1: i * CELL + inset,
+            u2: (i + 1.0) * CELL - inset,
+            v1: j * CELL + inset,
+            v2: (j + 1.0) * CELL - inset,
+        };
+        tex_uv.normalize(ICONS_SIZE, ICONS_SIZE);
+        tex_uv
+    }
+}
+
+// Where on the screen an element is kept, so that it follows resizes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    Top,
+    Center,
+    BottomRight,
+}
+
+impl Anchor {
+    // Top left corner of a `w` by `h` box that sits `dx`, `dy` pixels in
+    // from the anchor, centred ones are moved right and down
+    pub fn place(self, dx: f32, dy: f32, w: f32, h: f32, settings: &settings::Settings) -> (f32, f32) {
+        let (sw, sh) = (settings.screen_width_f, settings.screen_height_f);
+        let (x, y) = match self {
+            Anchor::Top => ((sw - w) * 0.5 + dx, dy),
+            Anchor::Center => ((sw - w) * 0.5 + dx, (sh - h) * 0.5 + dy),
+            Anchor::BottomRight => (sw - w - dx, sh - h - dy),
+        };
+        (x.round(), y.round())
+    }
+}
+
+// The gameplay HUD: crosshair, counters, keys and compass, laid out anew
+// every frame. Icons come from the icon atlas, numbers and letters from
+// the fonts.
+pub struct Hud {
+    pub icons: Vec<Icon>,
+    // in pixels
+    pub icon_rect: Vec<(f32, f32, f32, f32)>,
+    pub icon_tint: Vec<Option<richtext::Color>>,
+    pub text: text::Overlay,
+}
+
+impl Hud {
+    pub fn new(
+        fonts: &text::Fonts,
+        strings: &lang::Strings,
+        settings: &settings::Settings,
+        player: &player::Player,
+        weapon: &weapon::Weapon,
+        inventory: &inventory::Inventory,
+        fired: bool,
+    ) -> Hud {
+        let mut hud = Hud {
+            icons: Vec::new(),
+            icon_rect: Vec::new(),
+            icon_tint: Vec::new(),
+            text: text::Overlay::new_from(fonts, Vec::new()),
+        };
+        hud.text.scale = settings.hud_scale;
+        if !settings.hud_show {
+            return hud;
+        }
+        if player.status.alive {
+            hud.crosshair(settings, fired);
+        }
+        let top = hud.counters(fonts, settings, player, weapon);
+        hud.keys(settings, inventory, top);
+        if settings.hud_compass {
+            hud.compass(fonts, strings, settings, player.position.a);
+        }
+        hud
+    }
+
+    fn icon(&mut self, icon: Icon, rect: (f32, f32, f32, f32), tint: Option<richtext::Color>) {
+        self.icons.push(icon);
+        self.icon_rect.push(rect);
+        self.icon_tint.push(tint);
+    }
+
+    fn crosshair(&mut self, settings: &settings::Settings, fired: bool) {
+        let icon = match settings.crosshair {
+            1 => Icon::Cross,
+            2 => Icon::Dot,
+            3 => Icon::Circle,
+            _ => return,
+        };
+        let size = CELL * settings.hud_scale;
+        let (x, y) = Anchor::Center.place(0.0, 0.0, size, size, settings);
+        let tint = if fired { richtext::color("red") } else { None };
+        self.icon(icon, (x, y, x + size, y + size), tint);
+    }
+
+    // Health, armour and ammo in the bottom right corner, each number
+    // turns red when it runs low. Returns how far up from the bottom they
+    // reach.
+    fn counters(
+        &mut self,
+        fonts: &text::Fonts,
+        settings: &settings::Settings,
+        player: &player::Player,
+        weapon: &weapon::Weapon,
+    ) -> f32 {
+        let scale = settings.hud_scale;
+        let font = text::SANS_LARGE;
+        let rows = [
+            (Icon::Ammo, weapon.ammo as f32, weapon.ammo <= 0),
+            (Icon::Armour, player.status.armour.ceil(), false),
+            (Icon::Health, player.status.health.ceil(), player.status.health <= 0.25 * player::MAX_HEALTH),
+        ];
+        let digits = fonts.width(font, "000");
+        let width = (CELL + 8.0 + digits) * scale;
+        let height = CELL.max(fonts.line_height(font)) * scale;
+        let mut dy = MARGIN;
+        for (icon, value, low) in rows {
+            let (x, y) = Anchor::BottomRight.place(MARGIN, dy, width, height, settings);
+            let size = CELL * scale;
+            let top = y + (height - size) * 0.5;
+            self.icon(icon, (x, top, x + size, top + size), None);
+
+            let number = format!("{}", value.max(0.0));
+            let text_box = richtext::TextBox::new(fonts, &number, font, None, richtext::Align::Left);
+            let first = self.text.lines.len();
+            let tx = x + width - text_box.width * scale;
+            let ty = y + (height - text_box.height * scale) * 0.5;
+            self.text.push(&text_box, tx.round(), ty.round());
+            if low {
+                self.text.tint(first, richtext::color("red"), 1.0);
+            }
+            dy += height + 4.0 * scale;
+        }
+        dy
+    }
+
+    // One key per colour held, right to left above the counters
+    fn keys(&mut self, settings: &settings::Settings, inventory: &inventory::Inventory, dy: f32) {
+        let scale = settings.hud_scale;
+        let size = CELL * scale;
+        let dy = dy + 4.0 * scale;
+        for (k, key) in inventory.keys.iter().enumerate() {
+            let dx = MARGIN + k as f32 * (size + 4.0 * scale);
+            let (x, y) = Anchor::BottomRight.place(dx, dy, size, size, settings);
+            self.icon(Icon::Key, (x, y, x + size, y + size), richtext::color(key.name()));
+        }
+    }
+
+    // Strip at the top of the screen with the heading in the middle,
+    // bearings go clockwise from north (+y) like on a map
+    fn compass(&mut self, fonts: &text::Fonts, strings: &lang::Strings, settings: &settings::Settings, a: f32) {
+        let scale = settings.hud_scale;
+        let width = 320.0 * scale;
+        let height = 30.0 * scale;
+        let (x, y) = Anchor::Top.place(0.0, MARGIN, width, height, settings);
+        self.icon(Icon::Solid, (x, y, x + width, y + height), Some((0.0, 0.0, 0.0, 0.35)));
+
+        let heading = 90.0 - a.to_degrees();
+        let center = x + 0.5 * width;
+        let per_degree = 0.5 * width / COMPASS_SPAN;
+        for step in 0..24 {
+            let bearing = step as f32 * 15.0;
+            let d = (bearing - heading + 180.0).rem_euclid(360.0) - 180.0;
+            if d.abs() > COMPASS_SPAN {
+                continue;
+            }
+            // marks fade towards the ends of the strip
+            let alpha = 1.0 - (d / COMPASS_SPAN).powi(2);
+            let tx = center + d * per_degree;
+            let tick = if step % 3 == 0 { 8.0 } else { 4.0 } * scale;
+            let tint = Some((1.0, 1.0, 1.0, 0.8 * alpha));
+            self.icon(Icon::Solid, (tx - scale, y + height - tick, tx + scale, y + height), tint);
+
+            if step % 3 == 0 {
+                let point = strings.get(COMPASS_POINTS[step / 3]);
+                let text_box = richtext::TextBox::new(fonts, point, text::SANS, None, richtext::Align::Left);
+                let first = self.text.lines.len();
+                self.text.push(&text_box, (tx - 0.5 * text_box.width * scale).round(), y + scale);
+                let color = if step == 0 { richtext::color("red") } else { None };
+                self.text.tint(first, color, alpha);
+            }
+        }
+
+        let size = 16.0 * scale;
+        self.icon(Icon::Marker, (center - 0.5 * size, y - 0.5 * size, center + 0.5 * size, y + 0.5 * size), None);
+    }
+}
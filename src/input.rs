@@ -38,6 +38,31 @@ impl TimeState {
     }
 }
 
+// Moves through menus, from the keyboard or a gamepad
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Nav {
+    Up,
+    Down,
+    Left,
+    Right,
+    Activate,
+    Back,
+}
+
+impl Nav {
+    pub fn from_key(keycode: KeyCode) -> Option<Nav> {
+        match keycode {
+            KeyCode::Up | KeyCode::W => Some(Nav::Up),
+            KeyCode::Down | KeyCode::S => Some(Nav::Down),
+            KeyCode::Left | KeyCode::A => Some(Nav::Left),
+            KeyCode::Right | KeyCode::D => Some(Nav::Right),
+            KeyCode::Enter | KeyCode::KpEnter | KeyCode::Space => Some(Nav::Activate),
+            KeyCode::Escape | KeyCode::Backspace => Some(Nav::Back),
+            _ => None,
+        }
+    }
+}
+
 pub struct KeysState {
     pub w: bool,
     pub a: bool,
@@ -46,16 +71,20 @@ pub struct KeysState {
     pub q: bool,
     pub e: bool,
     pub k: bool,
+    pub h: bool,
     pub l: bool,
     pub f: bool,
     pub m: bool,
-    pub esc: bool,
     pub left: bool,
     pub right: bool,
     pub up: bool,
     pub down: bool,
     pub space: bool,
-    pub enter: bool,
+    pub c: bool,
+    pub shift: bool,
+    pub f5: bool,
+    pub f9: bool,
+    pub f3: bool,
 }
 
 impl KeysState {
@@ -70,14 +99,18 @@ impl KeysState {
             KeyCode::Up => self.up = state,
             KeyCode::Down => self.down = state,
             KeyCode::Space => self.space = state,
-            KeyCode::Escape => self.esc = state,
-            KeyCode::Enter => self.enter = state,
+            KeyCode::C | KeyCode::LeftControl => self.c = state,
+            KeyCode::LeftShift => self.shift = state,
             KeyCode::K => self.k = state,
+            KeyCode::H => self.h = state,
             KeyCode::L => self.l = state,
             KeyCode::Q => self.q = state,
             KeyCode::E => self.e = state,
             KeyCode::F => self.f = state,
             KeyCode::M => self.m = state,
+            KeyCode::F5 => self.f5 = state,
+            KeyCode::F9 => self.f9 = state,
+            KeyCode::F3 => self.f3 = state,
             _ => {},
         }
     }
@@ -96,6 +129,7 @@ pub struct MouseState {
 pub struct InputState {
     pub keys: KeysState,
     pub mouse: MouseState,
+    pub nav: Vec<Nav>,
     pub apply_change: bool,
 }
 
@@ -110,6 +144,7 @@ impl InputState {
                 q: false,
                 e: false,
                 k: false,
+                h: false,
                 l: false,
                 f: false,
                 m: false,
@@ -118,8 +153,11 @@ impl InputState {
                 up: false,
                 down: false,
                 space: false,
-                enter: false,
-                esc: false,
+                c: false,
+                shift: false,
+                f5: false,
+                f9: false,
+                f3: false,
             },
             mouse: MouseState {
                 left: false,
@@ -130,6 +168,7 @@ impl InputState {
                 dx: 0.5 * settings::TOLERANCE,
                 dy: 0.5 * settings::TOLERANCE,
             },
+            nav: Vec::new(),
             apply_change: false,
         }
     }
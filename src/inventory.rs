@@ -0,0 +1,109 @@
+use crate::lang;
+use crate::player;
+use crate::weapon;
+
+pub const HEALTH_PICKUP: f32 = 25.0;
+pub const ARMOUR_PICKUP: f32 = 50.0;
+pub const AMMO_PICKUP: i32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyColor {
+    Red,
+    Blue,
+    Yellow,
+}
+
+impl KeyColor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyColor::Red => "red",
+            KeyColor::Blue => "blue",
+            KeyColor::Yellow => "yellow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyColor> {
+        match name {
+            "red" => Some(KeyColor::Red),
+            "blue" => Some(KeyColor::Blue),
+            "yellow" => Some(KeyColor::Yellow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Item {
+    Health,
+    Armour,
+    Ammo,
+    Key(KeyColor),
+    Collectible,
+}
+
+pub struct Inventory {
+    pub keys: Vec<KeyColor>,
+    pub collectibles: u32,
+    pub collectibles_total: u32,
+}
+
+impl Inventory {
+    pub fn new(collectibles_total: u32) -> Inventory {
+        Inventory {
+            keys: Vec::new(),
+            collectibles: 0,
+            collectibles_total,
+        }
+    }
+
+    pub fn has_key(&self, color: KeyColor) -> bool {
+        self.keys.contains(&color)
+    }
+
+    // Applies the item and returns the pickup message, or None when the
+    // item is of no use right now and should stay in the level.
+    pub fn collect(&mut self, item: Item, player: &mut player::Player, weapon: &mut weapon::Weapon) -> Option<lang::Text> {
+        match item {
+            Item::Health => {
+                if player.status.health >= player::MAX_HEALTH {
+                    return None;
+                }
+                player.status.health = (player.status.health + HEALTH_PICKUP).min(player::MAX_HEALTH);
+                Some(lang::Text::new("pickup.health", &[&HEALTH_PICKUP]))
+            }
+            Item::Armour => {
+                if player.status.armour >= player::MAX_ARMOUR {
+                    return None;
+                }
+                player.status.armour = (player.status.armour + ARMOUR_PICKUP).min(player::MAX_ARMOUR);
+                Some(lang::Text::new("pickup.armour", &[&ARMOUR_PICKUP]))
+            }
+            Item::Ammo => {
+                if weapon.ammo >= weapon.max_ammo {
+                    return None;
+                }
+                weapon.ammo = (weapon.ammo + AMMO_PICKUP).min(weapon.max_ammo);
+                Some(lang::Text::new("pickup.ammo", &[&AMMO_PICKUP]))
+            }
+            Item::Key(color) => {
+                if !self.has_key(color) {
+                    self.keys.push(color);
+                }
+                Some(lang::Text::new(&format!("pickup.key.{}", color.name()), &[]))
+            }
+            Item::Collectible => {
+                self.collectibles += 1;
+                Some(lang::Text::new("pickup.gem", &[&self.collectibles, &self.collectibles_total]))
+            }
+        }
+    }
+
+    pub fn keys_text(&self, strings: &lang::Strings) -> String {
+        let names: Vec<String> = self.keys.iter().map(|k| strings.get(&format!("key.{}", k.name())).to_string()).collect();
+        if names.is_empty() {
+            strings.get("key.none").to_string()
+        } else {
+            names.join(" ")
+        }
+    }
+}
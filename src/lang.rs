@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+
+use crate::{richtext, text};
+
+// One `<code>.txt` per language, every key missing from one is taken from
+// the English file
+pub const LANG_DIR: &str = "assets/lang";
+pub const FALLBACK: &str = "en";
+// Fonts the strings are drawn with, each needs a glyph for every character
+pub const FONTS: [usize; 5] = [text::SANS, text::SANS_LARGE, text::SANS_BOLD, text::SANS_ITALIC, text::MONO];
+
+// A string of the table with its arguments, for code that has no table at
+// hand. It is looked up when it is shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub key: String,
+    pub args: Vec<String>,
+}
+
+impl Text {
+    pub fn new(key: &str, args: &[&dyn Display]) -> Text {
+        Text { key: key.to_string(), args: args.iter().map(|arg| arg.to_string()).collect() }
+    }
+}
+
+// The user-facing strings of one language by key. Strings are markup and
+// {0}, {1}, ... in them are replaced by the arguments in that order.
+pub struct Strings {
+    pub language: String,
+    pub table: HashMap<String, String>,
+    pub fallback: HashMap<String, String>,
+}
+
+impl Strings {
+    // An unknown language leaves everything in English
+    pub fn load(language: &str) -> Strings {
+        let read = |code: &str| parse(&fs::read_to_string(format!("{}/{}.txt", LANG_DIR, code)).unwrap_or_default());
+        let table = if language == FALLBACK { HashMap::new() } else { read(language) };
+        Strings { language: language.to_string(), table, fallback: read(FALLBACK) }
+    }
+
+    // The key itself when no file has it, so a missing string shows up as
+    // what is missing
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.table.get(key).or_else(|| self.fallback.get(key)).map(|s| s.as_str()).unwrap_or(key)
+    }
+
+    pub fn format(&self, key: &str, args: &[&dyn Display]) -> String {
+        fill(self.get(key), args.iter().map(|arg| arg.to_string()))
+    }
+
+    pub fn text(&self, text: &Text) -> String {
+        fill(self.get(&text.key), text.args.iter().cloned())
+    }
+
+    // Characters of the strings some of `FONTS` have no glyph for
+    pub fn missing_glyphs(&self, fonts: &text::Fonts) -> Vec<char> {
+        let mut missing: Vec<char> = Vec::new();
+        for value in self.table.values().chain(self.fallback.values()) {
+            for c in fonts.missing(&FONTS, &richtext::plain(value)) {
+                if !missing.contains(&c) {
+                    missing.push(c);
+                }
+            }
+        }
+        missing.sort();
+        missing
+    }
+}
+
+// The languages there are files for, as code and the name the file gives
+// itself, English first
+pub fn available() -> Vec<(String, String)> {
+    let mut languages: Vec<(String, String)> = fs::read_dir(LANG_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let code = path.file_stem()?.to_str()?.to_string();
+            if path.extension()? != "txt" {
+                return None;
+            }
+            let name = parse(&fs::read_to_string(&path).ok()?).remove("language").unwrap_or(code.clone());
+            Some((code, name))
+        })
+        .collect();
+    languages.sort_by_key(|(code, _)| (code != FALLBACK, code.clone()));
+    if languages.is_empty() {
+        languages.push((FALLBACK.to_string(), "English".to_string()));
+    }
+    languages
+}
+
+// `key = text` lines, `#` at the start of a line makes it a comment and
+// \n in a text breaks the line
+pub fn parse(source: &str) -> HashMap<String, String> {
+    let mut table = HashMap::new();
+    for line in source.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            table.insert(key.trim().to_string(), value.trim().replace("\\n", "\n"));
+        }
+    }
+    table
+}
+
+fn fill(template: &str, args: impl Iterator<Item = String>) -> String {
+    let mut text = template.to_string();
+    for (k, arg) in args.enumerate() {
+        text = text.replace(&format!("{{{}}}", k), &arg);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_english_and_formats() {
+        let strings = Strings {
+            language: "de".to_string(),
+            table: parse("# comment\ngreeting = Hallo {0}, {1}!\n"),
+            fallback: parse("greeting = Hello {0}, {1}!\nbye = Bye\nlines = a\\nb"),
+        };
+        assert_eq!(strings.format("greeting", &[&"Welt", &2]), "Hallo Welt, 2!");
+        assert_eq!(strings.get("bye"), "Bye");
+        assert_eq!(strings.get("lines"), "a\nb");
+        assert_eq!(strings.get("missing.key"), "missing.key");
+        assert_eq!(strings.text(&Text::new("greeting", &[&1])), "Hallo 1, {1}!");
+    }
+
+    // The shipped languages only use keys English has and the fonts draw
+    // all of their characters
+    #[test]
+    fn languages_are_complete() {
+        let fonts = text::Fonts::load();
+        let english = Strings::load(FALLBACK);
+        assert!(!english.fallback.is_empty());
+        for (code, _) in available() {
+            let strings = Strings::load(&code);
+            for key in strings.table.keys() {
+                assert!(english.fallback.contains_key(key), "{}: unknown key {}", code, key);
+            }
+            assert_eq!(strings.missing_glyphs(&fonts), Vec::<char>::new(), "{}", code);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::fs;
+use std::path::Path;
+
+use crate::entities;
+use crate::inventory;
+use crate::map;
+use crate::movers;
+use crate::settings;
+use crate::triggers;
+
+pub const LEVEL_FILE: &str = "level.txt";
+
+pub struct Level {
+    pub movers: movers::Movers,
+    pub triggers: triggers::Triggers,
+    pub script: Option<String>,
+    pub start: Option<(f32, f32, f32)>,
+    // lines that could not be read, with their number
+    pub errors: Vec<String>,
+}
+
+impl Level {
+    // Reads the level description next to the map images. A missing file
+    // gives an empty level, bad lines are skipped and reported.
+    pub fn load(path: &str, game_map: &mut map::GameMap) -> Level {
+        let text = fs::read_to_string(path).unwrap_or_default();
+        let mut level = Level {
+            movers: movers::Movers::new(),
+            triggers: triggers::Triggers::new(),
+            script: None,
+            start: None,
+            errors: Vec::new(),
+        };
+
+        for (number, text_line) in text.lines().enumerate() {
+            let line = text_line.split('#').next().unwrap_or("");
+            let mut parts = line.split(';');
+            let words: Vec<&str> = parts.next().unwrap_or("").split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let parsed = if words[0] == "trigger" {
+                level.parse_trigger(&words, parts)
+            } else if words[0] == "script" && words.len() == 2 {
+                // script files live next to the level file
+                let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+                level.script = Some(dir.join(words[1]).to_string_lossy().into_owned());
+                Some(())
+            } else {
+                level.parse(&words, game_map)
+            };
+            if parsed.is_none() {
+                level.errors.push(format!("{} line {}: {}", path, number + 1, text_line.trim()));
+            }
+        }
+
+        level.movers.attach(game_map);
+        level
+    }
+
+    fn parse(&mut self, words: &[&str], game_map: &mut map::GameMap) -> Option<()> {
+        let tile = |k: usize| -> Option<usize> {
+            let v: usize = words.get(k)?.parse().ok()?;
+            if v < settings::MAPSIZE { Some(v) } else { None }
+        };
+        let number = |k: usize| -> Option<u32> { words.get(k)?.parse().ok() };
+        // tag 0 stands for no tag
+        let tag = |k: usize| -> Option<u32> { number(k).filter(|&t| t > 0) };
+        let float = |k: usize| -> Option<f32> { words.get(k)?.parse().ok() };
+        // a point inside the map
+        let coordinate = |k: usize| -> Option<f32> { float(k).filter(|v| (0.0..settings::MAPSIZE as f32).contains(v)) };
+        let direction = |k: usize| -> Option<(i32, i32)> {
+            match *words.get(k)? {
+                "east" => Some((1, 0)),
+                "north" => Some((0, 1)),
+                "west" => Some((-1, 0)),
+                "south" => Some((0, -1)),
+                _ => None,
+            }
+        };
+
+        match words[0] {
+            "start" => {
+                self.start = Some((coordinate(1)?, coordinate(2)?, float(3).unwrap_or(0.0)));
+            }
+            "door" => {
+                let style = match *words.get(3)? {
+                    "slide" => movers::DoorStyle::Slide,
+                    "swing" => movers::DoorStyle::Swing,
+                    _ => return None,
+                };
+                let mut door = movers::Mover::new(movers::MoverKind::Door(style), tile(1)?, tile(2)?, number(4)? as u8);
+                if let Some(key) = words.get(5) {
+                    door.key = Some(inventory::KeyColor::from_name(key)?);
+                }
+                self.movers.movers.push(door);
+            }
+            "pushwall" => {
+                let wall = movers::Mover::new(movers::MoverKind::PushWall, tile(1)?, tile(2)?, 0);
+                self.movers.movers.push(wall);
+            }
+            "gate" => {
+                let mut gate = movers::Mover::new(movers::MoverKind::Gate, tile(1)?, tile(2)?, number(3)? as u8);
+                gate.tag = tag(4)?;
+                self.movers.movers.push(gate);
+            }
+            "switch" => {
+                self.movers.switches.push(movers::Switch {
+                    i: tile(1)?,
+                    j: tile(2)?,
+                    tag: tag(3)?,
+                    on: false,
+                });
+            }
+            "teleporter" => {
+                let (i, j) = (tile(1)?, tile(2)?);
+                let (x, y) = (coordinate(3)?, coordinate(4)?);
+                if game_map.is_solid(x.floor() as i32, y.floor() as i32) {
+                    return None;
+                }
+                let mut trigger = triggers::Trigger::new(i, j, i, j, triggers::Condition::Enter);
+                trigger.repeat = true;
+                trigger.actions.push(triggers::Action::Teleport(x, y, float(5)));
+                self.triggers.triggers.push(trigger);
+            }
+            "portal" => {
+                let portal = map::Portal::new(tile(1)?, tile(2)?, direction(3)?, tile(4)?, tile(5)?, direction(6)?);
+                game_map.portals.push(portal);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    // trigger <i1> <j1> <i2> <j2> <enter|use|timer <seconds>> [repeat] ; <action> ; ...
+    fn parse_trigger<'a>(&mut self, words: &[&str], actions: impl Iterator<Item = &'a str>) -> Option<()> {
+        let tile = |k: usize| -> Option<usize> {
+            let v: usize = words.get(k)?.parse().ok()?;
+            if v < settings::MAPSIZE { Some(v) } else { None }
+        };
+        let (condition, next) = match *words.get(5)? {
+            "enter" => (triggers::Condition::Enter, 6),
+            "use" => (triggers::Condition::Use, 6),
+            "timer" => (triggers::Condition::Timer(words.get(6)?.parse().ok()?), 7),
+            _ => return None,
+        };
+        let mut trigger = triggers::Trigger::new(tile(1)?, tile(2)?, tile(3)?, tile(4)?, condition);
+        trigger.repeat = words.get(next) == Some(&"repeat");
+
+        for action in actions {
+            let words: Vec<&str> = action.split_whitespace().collect();
+            trigger.actions.push(parse_action(&words)?);
+        }
+        self.triggers.triggers.push(trigger);
+        Some(())
+    }
+}
+
+fn parse_action(words: &[&str]) -> Option<triggers::Action> {
+    let float = |k: usize| -> Option<f32> { words.get(k)?.parse().ok() };
+    let index = |k: usize| -> Option<usize> { words.get(k)?.parse().ok() };
+    // a point inside the map
+    let coordinate = |k: usize| -> Option<f32> { float(k).filter(|v| (0.0..settings::MAPSIZE as f32).contains(v)) };
+    let action = match *words.first()? {
+        "message" => triggers::Action::Message(words[1..].join(" ")),
+        "announce" => triggers::Action::Announce(words[1..].join(" ")),
+        "open" => triggers::Action::Open(index(1)?, index(2)?),
+        "sound" => triggers::Action::Sound(words.get(1)?.to_string()),
+        "teleport" => triggers::Action::Teleport(coordinate(1)?, coordinate(2)?, float(3)),
+        "spawn" => {
+            triggers::Action::Spawn(entities::EntityKind::from_name(words.get(1)?)?, coordinate(2)?, coordinate(3)?)
+        }
+        "script" => triggers::Action::Script(words.get(1)?.to_string()),
+        "end" => triggers::Action::EndLevel,
+        _ => return None,
+    };
+    Some(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets;
+
+    #[test]
+    fn reports_bad_lines() {
+        let path = std::env::temp_dir().join("level_reports_bad_lines.txt");
+        let text = "start 10 10\ntrigger 1 1 2 2 enter ; spawn enemy 300 5\ntrigger 1 1 2 2 enter ; spawn enemy 3 5\nwobble\n\
+                    start 10 -1\ntrigger 1 1 2 2 enter ; teleport 300 300\n";
+        fs::write(&path, text).unwrap();
+        let mut game_map = map::GameMap::empty();
+        let level = Level::load(&path.to_string_lossy(), &mut game_map);
+        fs::remove_file(&path).ok();
+
+        assert_eq!(level.triggers.triggers.len(), 1);
+        assert_eq!(level.start, Some((10.0, 10.0, 0.0)));
+        assert_eq!(level.errors.len(), 4);
+        assert!(level.errors[0].ends_with("line 2: trigger 1 1 2 2 enter ; spawn enemy 300 5"));
+        assert!(level.errors[1].ends_with("line 4: wobble"));
+        assert!(level.errors[2].ends_with("line 5: start 10 -1"));
+        assert!(level.errors[3].ends_with("line 6: trigger 1 1 2 2 enter ; teleport 300 300"));
+    }
+
+    #[test]
+    fn teleporters_lead_onto_open_floor() {
+        let path = std::env::temp_dir().join("level_teleporters_lead_onto_open_floor.txt");
+        let text = "teleporter 5 5 20.5 20.5\nteleporter 5 6 300 20.5\nteleporter 5 7 30.5 30.5 1.57\n";
+        fs::write(&path, text).unwrap();
+        let mut game_map = map::GameMap::empty();
+        game_map.wall_bot_array[20][20] = 0;
+        let level = Level::load(&path.to_string_lossy(), &mut game_map);
+        fs::remove_file(&path).ok();
+
+        assert_eq!(level.triggers.triggers.len(), 1);
+        assert_eq!(level.triggers.triggers[0].actions, vec![triggers::Action::Teleport(30.5, 30.5, Some(1.57))]);
+        assert_eq!(level.errors.len(), 2);
+        assert!(level.errors[0].ends_with("line 1: teleporter 5 5 20.5 20.5"));
+        assert!(level.errors[1].ends_with("line 2: teleporter 5 6 300 20.5"));
+    }
+
+    #[test]
+    fn shipped_levels_have_no_bad_lines() {
+        for dir in ["assets/levels/1", "assets/levels/2"] {
+            let mut game_map = map::GameMap::new(&assets::Ass::load(dir));
+            let level = Level::load(&format!("{}/{}", dir, LEVEL_FILE), &mut game_map);
+            assert_eq!(level.errors, Vec::<String>::new());
+        }
+    }
+}
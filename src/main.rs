@@ -14,9 +14,28 @@ mod settings;
 mod shaders;
 mod stage;
 mod text;
+mod richtext;
+mod widgets;
+mod menus;
 mod input;
+mod gamepad;
+mod console;
+mod messages;
+mod hud;
+mod profiler;
+mod lang;
 mod sprites;
 mod audio;
+mod entities;
+mod pathfinding;
+mod weapon;
+mod inventory;
+mod save;
+mod level;
+mod movers;
+mod triggers;
+mod scripting;
+mod campaign;
 
 fn window_conf() -> Conf {
     let mut conf = Conf {
@@ -31,7 +50,7 @@ fn window_conf() -> Conf {
 }
 
 fn main() {
-    let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
+    let (tx, rx): (Sender<audio::AudioCommand>, Receiver<audio::AudioCommand>) = mpsc::channel();
     thread::spawn(move || {audio::playback(&rx)});
     miniquad::start(window_conf(), move || {Box::new(stage::Stage::new(&tx))});
     
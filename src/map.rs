@@ -1,13 +1,202 @@
 use crate::assets;
 use crate::settings;
 use image::{self, Pixel};
+use std::collections::HashSet;
+
+// Tile codes in the red channel of the special map, 255 = none
+pub const SPECIAL_HAZARD: u8 = 0;
+pub const SPECIAL_CHECKPOINT: u8 = 16;
+pub const SPECIAL_EXIT: u8 = 32;
+
+// Ceiling height of open tiles in a map with two wall storeys, each upper
+// storey raises it by one. The green channel of the height map gives lower
+// ceilings in hundredths of a tile, 255 = default.
+pub const CEILING: f32 = 2.0;
+
+// Floor heights come from the red channel of the height map in hundredths
+// of a tile, 255 = 0. The blue channel turns a tile into a ramp rising from
+// its own floor to the floor of the next tile in the given direction.
+pub const RAMP_UP_I: u8 = 0;
+pub const RAMP_UP_J: u8 = 16;
+pub const RAMP_DOWN_I: u8 = 32;
+pub const RAMP_DOWN_J: u8 = 48;
+
+// Thin walls from the edge map, one per tile: red = texture, green = shape,
+// blue = height above the floor in hundredths of a tile, 255 = up to the
+// ceiling. Diagonals run corner to corner across the tile.
+pub const EDGE_WEST: u8 = 0;
+pub const EDGE_SOUTH: u8 = 16;
+pub const EDGE_DIAGONAL: u8 = 32;
+pub const EDGE_ANTIDIAGONAL: u8 = 48;
+
+// Cut-out textures (bars, fence, window) that can be seen and shot through
+pub const SEE_THROUGH: [u8; 3] = [28, 96, 100];
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThinWall {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+    pub bottom: f32,
+    pub top: f32,
+    pub texture: u8,
+    pub opaque: bool,
+}
+
+impl ThinWall {
+    pub fn new(i: usize, j: usize, shape: u8, texture: u8, height: u8, floor: f32, ceiling: f32) -> Option<ThinWall> {
+        let (xi, yj) = (i as f32, j as f32);
+        let ((x1, y1), (x2, y2)) = match shape {
+            EDGE_WEST => ((xi, yj), (xi, yj + 1.0)),
+            EDGE_SOUTH => ((xi, yj), (xi + 1.0, yj)),
+            EDGE_DIAGONAL => ((xi, yj), (xi + 1.0, yj + 1.0)),
+            EDGE_ANTIDIAGONAL => ((xi + 1.0, yj), (xi, yj + 1.0)),
+            _ => return None,
+        };
+        let top = if height < 255 { floor + height as f32 / 100.0 } else { ceiling };
+        Some(ThinWall {
+            x1,
+            y1,
+            x2,
+            y2,
+            bottom: floor,
+            top,
+            texture,
+            opaque: height == 255 && !SEE_THROUGH.contains(&texture),
+        })
+    }
+
+    // Distance along the ray from (x,y) in direction (cos_a, sin_a) to the
+    // wall, if the ray hits it
+    pub fn ray_hit(&self, x: f32, y: f32, cos_a: f32, sin_a: f32) -> Option<f32> {
+        let (ex, ey) = (self.x2 - self.x1, self.y2 - self.y1);
+        let denom = cos_a * ey - sin_a * ex;
+        if denom.abs() < settings::TOLERANCE {
+            return None;
+        }
+        let (dx, dy) = (self.x1 - x, self.y1 - y);
+        let t = (dx * ey - dy * ex) / denom;
+        let s = (dx * sin_a - dy * cos_a) / denom;
+        if t >= 0.0 && (0.0..=1.0).contains(&s) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        let (ex, ey) = (self.x2 - self.x1, self.y2 - self.y1);
+        let s = (((x - self.x1) * ex + (y - self.y1) * ey) / (ex * ex + ey * ey)).clamp(0.0, 1.0);
+        ((self.x1 + s * ex - x).powi(2) + (self.y1 + s * ey - y).powi(2)).sqrt()
+    }
+}
+
+// Rigid motion of the plane by quarter turns, p' = R p + d
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub cos: f32,
+    pub sin: f32,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform { cos: 1.0, sin: 0.0, dx: 0.0, dy: 0.0 }
+    }
+
+    pub fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        let (x, y) = self.vector(x, y);
+        (x + self.dx, y + self.dy)
+    }
+
+    pub fn vector(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.cos * x - self.sin * y, self.sin * x + self.cos * y)
+    }
+
+    pub fn angle(&self) -> f32 {
+        self.sin.atan2(self.cos)
+    }
+
+    // `self` first, then `other`
+    pub fn then(&self, other: &Transform) -> Transform {
+        let (cos, sin) = other.vector(self.cos, self.sin);
+        let (dx, dy) = other.point(self.dx, self.dy);
+        Transform { cos, sin, dx, dy }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        let (cos, sin) = (self.cos, -self.sin);
+        let dx = -(cos * self.dx - sin * self.dy);
+        let dy = -(sin * self.dx + cos * self.dy);
+        Transform { cos, sin, dx, dy }
+    }
+}
+
+// Leaving tile (i,j) across its edge in direction (di,dj) leads into tile
+// (to_i,to_j). Points and directions are carried over by `transform`.
+#[derive(Debug, Clone, Copy)]
+pub struct Portal {
+    pub i: usize,
+    pub j: usize,
+    pub di: i32,
+    pub dj: i32,
+    pub to_i: usize,
+    pub to_j: usize,
+    pub transform: Transform,
+}
+
+impl Portal {
+    // (di,dj) is the way out of the first tile, (to_di,to_dj) the way the
+    // player then walks in the second one
+    pub fn new(i: usize, j: usize, (di, dj): (i32, i32), to_i: usize, to_j: usize, (to_di, to_dj): (i32, i32)) -> Portal {
+        let cos = (di * to_di + dj * to_dj) as f32;
+        let sin = (di * to_dj - dj * to_di) as f32;
+        let rotation = Transform { cos, sin, dx: 0.0, dy: 0.0 };
+        // the middle of the exit edge goes to the middle of the entry edge
+        let exit = (i as f32 + 0.5 + 0.5 * di as f32, j as f32 + 0.5 + 0.5 * dj as f32);
+        let entry = (to_i as f32 + 0.5 - 0.5 * to_di as f32, to_j as f32 + 0.5 - 0.5 * to_dj as f32);
+        let (rx, ry) = rotation.vector(exit.0, exit.1);
+        Portal {
+            i,
+            j,
+            di,
+            dj,
+            to_i,
+            to_j,
+            transform: Transform { cos, sin, dx: entry.0 - rx, dy: entry.1 - ry },
+        }
+    }
+}
+
+// Tiles seen through a chain of portals, `to_view` places them in front of
+// the viewer
+pub struct PortalView {
+    pub chain: Vec<usize>,
+    pub to_view: Transform,
+    pub floors: HashSet<(usize, usize)>,
+    pub walls: HashSet<(usize, usize)>,
+}
 
 pub struct GameMap {
     pub wall_top_array: Vec<Vec<u8>>,
     pub wall_bot_array: Vec<Vec<u8>>,
+    pub wall_layers: Vec<Vec<Vec<u8>>>,
+    pub height: f32,
     pub floor_array: Vec<Vec<u8>>,
     pub ceil_array: Vec<Vec<u8>>,
     pub sprite_array: Vec<Vec<u8>>,
+    pub entity_array: Vec<Vec<u8>>,
+    pub special_array: Vec<Vec<u8>>,
+    pub ceil_height_array: Vec<Vec<f32>>,
+    pub floor_height_array: Vec<Vec<f32>>,
+    pub ramp_array: Vec<Vec<u8>>,
+    pub thin_array: Vec<Vec<Option<ThinWall>>>,
+    pub portals: Vec<Portal>,
+    pub portal_views: Vec<PortalView>,
+    pub blocked_array: Vec<Vec<bool>>,
+    pub opaque_array: Vec<Vec<bool>>,
     pub wall_visible: Vec<Vec<bool>>,
     pub floor_visible: Vec<Vec<bool>>,
 }
@@ -19,6 +208,14 @@ impl GameMap {
         let mut floor_array = vec![vec![0; settings::MAPSIZE]; settings::MAPSIZE];
         let mut ceil_array = vec![vec![0; settings::MAPSIZE]; settings::MAPSIZE];
         let mut sprite_array = vec![vec![0; settings::MAPSIZE]; settings::MAPSIZE];
+        let mut entity_array = vec![vec![0; settings::MAPSIZE]; settings::MAPSIZE];
+        let mut special_array = vec![vec![0; settings::MAPSIZE]; settings::MAPSIZE];
+        // storeys above the top layer
+        let mut wall_layers = vec![vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE]; ass.wall_images_upper.len()];
+        let height = CEILING + wall_layers.len() as f32;
+        let mut ceil_height_array = vec![vec![height; settings::MAPSIZE]; settings::MAPSIZE];
+        let mut floor_height_array = vec![vec![0.0; settings::MAPSIZE]; settings::MAPSIZE];
+        let mut ramp_array = vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE];
 
         for i in 0..settings::MAPSIZE {
             for j in 0..settings::MAPSIZE {
@@ -37,20 +234,325 @@ impl GameMap {
                 let pixel1 =
                     image::ImageBuffer::get_pixel(&ass.sprite_image, i as u32, j as u32).to_rgba();
                     sprite_array[i][settings::MAPSIZE - j - 1] = pixel1[2];
+                let pixel1 =
+                    image::ImageBuffer::get_pixel(&ass.entity_image, i as u32, j as u32).to_rgba();
+                entity_array[i][settings::MAPSIZE - j - 1] = pixel1[0];
+                let pixel1 =
+                    image::ImageBuffer::get_pixel(&ass.special_image, i as u32, j as u32).to_rgba();
+                special_array[i][settings::MAPSIZE - j - 1] = pixel1[0];
+                let pixel1 =
+                    image::ImageBuffer::get_pixel(&ass.height_image, i as u32, j as u32).to_rgba();
+                if pixel1[1] < 255 {
+                    ceil_height_array[i][settings::MAPSIZE - j - 1] = pixel1[1] as f32 / 100.0;
+                }
+                if pixel1[0] < 255 {
+                    floor_height_array[i][settings::MAPSIZE - j - 1] = pixel1[0] as f32 / 100.0;
+                }
+                ramp_array[i][settings::MAPSIZE - j - 1] = pixel1[2];
+                for (layer, upper) in wall_layers.iter_mut().zip(ass.wall_images_upper.iter()) {
+                    layer[i][settings::MAPSIZE - j - 1] = image::ImageBuffer::get_pixel(upper, i as u32, j as u32)[0];
+                }
+            }
+        }
+
+        // thin walls stand on the floor, so they are set up once heights are known
+        let mut thin_array = vec![vec![None; settings::MAPSIZE]; settings::MAPSIZE];
+        for i in 0..settings::MAPSIZE {
+            for j in 0..settings::MAPSIZE {
+                let pixel1 =
+                    image::ImageBuffer::get_pixel(&ass.edge_image, i as u32, j as u32).to_rgba();
+                let (i, j) = (i, settings::MAPSIZE - j - 1);
+                thin_array[i][j] =
+                    ThinWall::new(i, j, pixel1[1], pixel1[0], pixel1[2], floor_height_array[i][j], ceil_height_array[i][j]);
             }
         }
 
+        let blocked_array = vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE];
+        let opaque_array = vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE];
         let wall_visible = vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE];
         let floor_visible = vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE];
 
         GameMap {
             wall_top_array,
             wall_bot_array,
+            wall_layers,
+            height,
             floor_array,
             ceil_array,
             sprite_array,
+            entity_array,
+            special_array,
+            ceil_height_array,
+            floor_height_array,
+            ramp_array,
+            thin_array,
+            portals: Vec::new(),
+            portal_views: Vec::new(),
+            blocked_array,
+            opaque_array,
             wall_visible,
             floor_visible,
         }
     }
+
+    // Open map without walls or level data, for use without assets
+    pub fn empty() -> GameMap {
+        GameMap {
+            wall_top_array: vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE],
+            wall_bot_array: vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE],
+            wall_layers: Vec::new(),
+            height: CEILING,
+            floor_array: vec![vec![32; settings::MAPSIZE]; settings::MAPSIZE],
+            ceil_array: vec![vec![36; settings::MAPSIZE]; settings::MAPSIZE],
+            sprite_array: vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE],
+            entity_array: vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE],
+            special_array: vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE],
+            ceil_height_array: vec![vec![CEILING; settings::MAPSIZE]; settings::MAPSIZE],
+            floor_height_array: vec![vec![0.0; settings::MAPSIZE]; settings::MAPSIZE],
+            ramp_array: vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE],
+            thin_array: vec![vec![None; settings::MAPSIZE]; settings::MAPSIZE],
+            portals: Vec::new(),
+            portal_views: Vec::new(),
+            blocked_array: vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE],
+            opaque_array: vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE],
+            wall_visible: vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE],
+            floor_visible: vec![vec![false; settings::MAPSIZE]; settings::MAPSIZE],
+        }
+    }
+
+    pub fn special(&self, x: f32, y: f32) -> u8 {
+        let i = x.floor() as i32;
+        let j = y.floor() as i32;
+        if i < 0 || j < 0 || i >= settings::MAPSIZE as i32 || j >= settings::MAPSIZE as i32 {
+            return 255;
+        }
+        self.special_array[i as usize][j as usize]
+    }
+
+    pub fn ceil_height(&self, i: i32, j: i32) -> f32 {
+        if i < 0 || j < 0 || i >= settings::MAPSIZE as i32 || j >= settings::MAPSIZE as i32 {
+            return 0.0;
+        }
+        self.ceil_height_array[i as usize][j as usize]
+    }
+
+    // Texture of every storey of the wall at (i,j), bottom first, and the
+    // number of storeys
+    pub fn wall_textures(&self, i: usize, j: usize) -> ([u8; assets::MAX_STOREYS], usize) {
+        self.storey_textures(i, j, self.wall_bot_array[i][j], self.wall_top_array[i][j])
+    }
+
+    // The two lower storeys textured with `bottom` and `top`, then the upper
+    // storeys of the wall map at (i,j). Upper storeys without a texture of
+    // their own continue the one below.
+    pub fn storey_textures(&self, i: usize, j: usize, bottom: u8, top: u8) -> ([u8; assets::MAX_STOREYS], usize) {
+        let mut textures = [top; assets::MAX_STOREYS];
+        textures[0] = bottom;
+        let mut storeys = 2;
+        for layer in self.wall_layers.iter().take(assets::MAX_STOREYS - 2) {
+            textures[storeys] = if layer[i][j] < 255 { layer[i][j] } else { textures[storeys - 1] };
+            storeys += 1;
+        }
+        (textures, storeys)
+    }
+
+    pub fn floor_height(&self, i: i32, j: i32) -> f32 {
+        if i < 0 || j < 0 || i >= settings::MAPSIZE as i32 || j >= settings::MAPSIZE as i32 {
+            return 0.0;
+        }
+        self.floor_height_array[i as usize][j as usize]
+    }
+
+    // Floor heights at the corners (i,j), (i+1,j), (i+1,j+1) and (i,j+1)
+    pub fn floor_corners(&self, i: i32, j: i32) -> [f32; 4] {
+        let base = self.floor_height(i, j);
+        if i < 0 || j < 0 || i >= settings::MAPSIZE as i32 || j >= settings::MAPSIZE as i32 {
+            return [base; 4];
+        }
+        let (di, dj) = match self.ramp_array[i as usize][j as usize] {
+            RAMP_UP_I => (1, 0),
+            RAMP_UP_J => (0, 1),
+            RAMP_DOWN_I => (-1, 0),
+            RAMP_DOWN_J => (0, -1),
+            _ => return [base; 4],
+        };
+        if self.is_solid(i + di, j + dj) {
+            return [base; 4];
+        }
+        let high = self.floor_height(i + di, j + dj);
+        match (di, dj) {
+            (1, 0) => [base, high, high, base],
+            (0, 1) => [base, base, high, high],
+            (-1, 0) => [high, base, base, high],
+            _ => [high, high, base, base],
+        }
+    }
+
+    // Floor height of tile (i,j) at the point (x,y), extended past the tile
+    // edges for ramps
+    pub fn floor_plane(&self, i: i32, j: i32, x: f32, y: f32) -> f32 {
+        let c = self.floor_corners(i, j);
+        let u = x - i as f32;
+        let v = y - j as f32;
+        c[0] + (c[1] - c[0]) * u + (c[3] - c[0]) * v
+    }
+
+    pub fn floor_z(&self, x: f32, y: f32) -> f32 {
+        self.floor_plane(x.floor() as i32, y.floor() as i32, x, y)
+    }
+
+    pub fn thin_wall(&self, i: i32, j: i32) -> Option<&ThinWall> {
+        if i < 0 || j < 0 || i >= settings::MAPSIZE as i32 || j >= settings::MAPSIZE as i32 {
+            return None;
+        }
+        self.thin_array[i as usize][j as usize].as_ref()
+    }
+
+    // Distance from (x,y) to the nearest thin wall in the surrounding tiles
+    // whose top is above `reach`, lower ones can be stepped over
+    pub fn thin_wall_dist(&self, x: f32, y: f32, reach: f32) -> f32 {
+        let (i, j) = (x.floor() as i32, y.floor() as i32);
+        let mut dist = f32::INFINITY;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                if let Some(wall) = self.thin_wall(i + di, j + dj) {
+                    if wall.top > reach {
+                        dist = dist.min(wall.distance(x, y));
+                    }
+                }
+            }
+        }
+        dist
+    }
+
+    // Whether a thin wall with its top above `reach` stands between the
+    // centres of the neighbouring tiles (i,j) and (ni,nj)
+    pub fn thin_wall_between(&self, i: i32, j: i32, ni: i32, nj: i32, reach: f32) -> bool {
+        let (x, y) = (i as f32 + 0.5, j as f32 + 0.5);
+        let (dx, dy) = ((ni - i) as f32, (nj - j) as f32);
+        let length = (dx * dx + dy * dy).sqrt();
+        [(i, j), (ni, nj)]
+            .iter()
+            .filter_map(|&(a, b)| self.thin_wall(a, b))
+            .any(|wall| wall.top > reach && wall.ray_hit(x, y, dx / length, dy / length).is_some_and(|t| t <= length))
+    }
+
+    // Index of the portal leaving tile (i,j) in direction (di,dj)
+    pub fn portal(&self, i: i32, j: i32, di: i32, dj: i32) -> Option<usize> {
+        if di.abs() + dj.abs() != 1 {
+            return None;
+        }
+        self.portals
+            .iter()
+            .position(|p| p.i as i32 == i && p.j as i32 == j && p.di == di && p.dj == dj)
+    }
+
+    // Open tiles raised up to their ceiling hide whatever is behind them
+    pub fn is_closed(&self, i: i32, j: i32) -> bool {
+        self.floor_height(i, j) >= self.ceil_height(i, j)
+    }
+
+    // Why the player can't be put at (x,y), None if it can
+    pub fn unplaceable(&self, x: f32, y: f32) -> Option<&'static str> {
+        let on_map = |v: f32| (0.0..settings::MAPSIZE as f32).contains(&v);
+        if !on_map(x) || !on_map(y) {
+            Some("outside the map")
+        } else if self.is_solid(x.floor() as i32, y.floor() as i32) {
+            Some("inside a wall")
+        } else {
+            None
+        }
+    }
+
+    pub fn is_solid(&self, i: i32, j: i32) -> bool {
+        if i < 0 || j < 0 || i >= settings::MAPSIZE as i32 || j >= settings::MAPSIZE as i32 {
+            return true;
+        }
+        self.wall_bot_array[i as usize][j as usize] < 255 || self.blocked_array[i as usize][j as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    fn same(a: Transform, b: Transform) -> bool {
+        close(a.cos, b.cos) && close(a.sin, b.sin) && close(a.dx, b.dx) && close(a.dy, b.dy)
+    }
+
+    #[test]
+    fn transforms_undo_their_inverse() {
+        for (cos, sin) in [(1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)] {
+            let t = Transform { cos, sin, dx: 3.0, dy: -7.5 };
+            assert!(same(t.then(&t.inverse()), Transform::identity()));
+            assert!(same(t.inverse().then(&t), Transform::identity()));
+        }
+        // a quarter turn, then a shift
+        let turn = Transform { cos: 0.0, sin: 1.0, dx: 0.0, dy: 0.0 };
+        let shift = Transform { cos: 1.0, sin: 0.0, dx: 2.0, dy: 0.0 };
+        let (x, y) = turn.then(&shift).point(1.0, 0.0);
+        assert!(close(x, 2.0) && close(y, 1.0));
+    }
+
+    #[test]
+    fn portals_carry_the_exit_edge_to_the_entry_edge() {
+        // straight through, leaving west and walking on west
+        let portal = Portal::new(120, 6, (-1, 0), 144, 30, (-1, 0));
+        let (x, y) = portal.transform.point(120.0, 6.5);
+        assert!(close(x, 145.0) && close(y, 30.5));
+        let (cos_a, sin_a) = portal.transform.vector(-1.0, 0.0);
+        assert!(close(cos_a, -1.0) && close(sin_a, 0.0));
+
+        // leaving east and walking on north turns by a quarter
+        let portal = Portal::new(0, 0, (1, 0), 5, 5, (0, 1));
+        let (x, y) = portal.transform.point(1.0, 0.5);
+        assert!(close(x, 5.5) && close(y, 5.0));
+        let (cos_a, sin_a) = portal.transform.vector(1.0, 0.0);
+        assert!(close(cos_a, 0.0) && close(sin_a, 1.0));
+        // going back the way the player came is the inverse
+        let back = Portal::new(5, 5, (0, -1), 0, 0, (-1, 0));
+        assert!(same(back.transform, portal.transform.inverse()));
+    }
+
+    #[test]
+    fn rays_hit_and_miss_thin_walls() {
+        // west edge of tile (2,0), from (2,0) to (2,1)
+        let wall = ThinWall::new(2, 0, EDGE_WEST, 1, 255, 0.0, CEILING).unwrap();
+        assert!(close(wall.ray_hit(0.5, 0.5, 1.0, 0.0).unwrap(), 1.5));
+        // facing away or passing beside the wall
+        assert!(wall.ray_hit(0.5, 0.5, -1.0, 0.0).is_none());
+        assert!(wall.ray_hit(0.5, 1.5, 1.0, 0.0).is_none());
+        // grazing: through an end point, and along the wall itself
+        assert!(close(wall.ray_hit(1.0, 0.0, 1.0, 0.0).unwrap(), 1.0));
+        assert!(wall.ray_hit(2.0, -1.0, 0.0, 1.0).is_none());
+
+        let diagonal = ThinWall::new(0, 0, EDGE_DIAGONAL, 1, 255, 0.0, CEILING).unwrap();
+        assert!(close(diagonal.ray_hit(1.0, 0.0, -1.0, 0.0).unwrap(), 1.0));
+    }
+
+    #[test]
+    fn distance_to_thin_walls() {
+        let wall = ThinWall::new(2, 0, EDGE_WEST, 1, 255, 0.0, CEILING).unwrap();
+        assert!(close(wall.distance(1.5, 0.5), 0.5));
+        assert!(close(wall.distance(2.0, 0.25), 0.0));
+        // beyond the ends the distance is to the nearest end point
+        assert!(close(wall.distance(2.0, 3.0), 2.0));
+        assert!(close(wall.distance(1.0, -1.0), 2.0_f32.sqrt()));
+    }
+
+    #[test]
+    fn low_thin_walls_can_be_stepped_over() {
+        let mut game_map = GameMap::empty();
+        game_map.thin_array[2][0] = ThinWall::new(2, 0, EDGE_WEST, 1, 255, 0.0, CEILING);
+        game_map.thin_array[2][1] = ThinWall::new(2, 1, EDGE_WEST, 1, 20, 0.0, CEILING);
+        assert!(game_map.thin_wall_between(1, 0, 2, 0, 0.5));
+        assert!(game_map.thin_wall_between(2, 0, 1, 0, 0.5));
+        assert!(!game_map.thin_wall_between(1, 1, 2, 1, 0.5));
+        assert!(game_map.thin_wall_between(1, 1, 2, 1, 0.1));
+        assert!(!game_map.thin_wall_between(2, 0, 2, 1, 0.5));
+    }
 }
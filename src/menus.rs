@@ -0,0 +1,189 @@
+use crate::lang::{self, Strings};
+use crate::settings;
+use crate::widgets::{Menu, Widget};
+
+// Rays cast per frame for each detail level of the video options
+const DETAIL_RAYS: [usize; 3] = [480, 960, 1920];
+const DETAILS: [&str; 3] = ["menu.detail.low", "menu.detail.medium", "menu.detail.high"];
+// Crosshair styles in the order of `settings.crosshair`
+const CROSSHAIRS: [&str; 4] = ["menu.crosshair.none", "menu.crosshair.cross", "menu.crosshair.dot", "menu.crosshair.circle"];
+
+// Screens at the bottom of the stack, Esc does not leave them
+pub fn is_root(menu: &Menu) -> bool {
+    matches!(menu.name, "title" | "death" | "end")
+}
+
+// The menus that are built the same every time by their name, for
+// building the open ones again in another language
+pub fn page(name: &str, strings: &Strings) -> Option<Menu> {
+    match name {
+        "title" => Some(title(strings)),
+        "pause" => Some(pause(strings)),
+        "options" => Some(options(strings)),
+        "video" => Some(video(strings)),
+        "audio" => Some(audio(strings)),
+        "hud" => Some(hud(strings)),
+        "controls" => Some(controls(strings)),
+        _ => None,
+    }
+}
+
+fn toggle(
+    strings: &Strings,
+    id: &'static str,
+    key: &str,
+    get: impl Fn(&settings::Settings) -> bool + 'static,
+    set: impl Fn(&mut settings::Settings, bool) + 'static,
+) -> Widget {
+    Widget::toggle(id, strings.get(key), [strings.get("menu.off"), strings.get("menu.on")], get, set)
+}
+
+pub fn title(strings: &Strings) -> Menu {
+    Menu::new("title", vec![
+        Widget::heading(strings.get("menu.heading")),
+        Widget::Separator,
+        Widget::button("new", strings.get("menu.new")),
+        Widget::button("load", strings.get("menu.load")),
+        Widget::button("options", strings.get("menu.options")),
+        Widget::Separator,
+        Widget::button("quit", strings.get("menu.quit")),
+    ])
+}
+
+pub fn pause(strings: &Strings) -> Menu {
+    Menu::new("pause", vec![
+        Widget::button("continue", strings.get("menu.continue")),
+        Widget::button("load", strings.get("menu.load")),
+        Widget::button("options", strings.get("menu.options")),
+        Widget::Separator,
+        Widget::button("title", strings.get("menu.quit_title")),
+        Widget::button("quit", strings.get("menu.quit")),
+    ])
+}
+
+// Languages are listed by the names they give themselves
+pub fn options(strings: &Strings) -> Menu {
+    let (codes, names): (Vec<String>, Vec<String>) = lang::available().into_iter().unzip();
+    let get_codes = codes.clone();
+    Menu::new("options", vec![
+        Widget::heading(strings.get("menu.options")),
+        Widget::Separator,
+        Widget::button("video", strings.get("menu.video")),
+        Widget::button("audio", strings.get("menu.audio")),
+        Widget::button("hud", strings.get("menu.hud")),
+        Widget::button("controls", strings.get("menu.controls")),
+        Widget::dropdown(
+            "language",
+            strings.get("menu.language"),
+            names.iter().map(|name| name.as_str()).collect(),
+            move |s| get_codes.iter().position(|code| *code == s.language).unwrap_or(0),
+            move |s, k| s.language = codes[k].clone(),
+        ),
+        Widget::Separator,
+        Widget::button("back", strings.get("menu.back")),
+    ])
+}
+
+pub fn video(strings: &Strings) -> Menu {
+    Menu::new("video", vec![
+        Widget::heading(strings.get("menu.video")),
+        Widget::Separator,
+        toggle(strings, "fullscreen", "menu.fullscreen", |s| s.full_screen, |s, on| {
+            miniquad::window::set_fullscreen(on);
+            let screen = miniquad::window::screen_size();
+            s.full_screen = on;
+            s.screen_change(screen.0, screen.1);
+        }),
+        Widget::slider("light", strings.get("menu.light"), (1.0, 15.0, 0.5), |s| s.light_dist, |s, v| s.light_dist = v),
+        Widget::dropdown(
+            "detail",
+            strings.get("menu.detail"),
+            DETAILS.iter().map(|key| strings.get(key)).collect(),
+            |s| DETAIL_RAYS.iter().position(|&n| n >= s.draw_rays_num).unwrap_or(DETAIL_RAYS.len() - 1),
+            |s, k| s.draw_rays_num = DETAIL_RAYS[k],
+        ),
+        Widget::Separator,
+        Widget::button("back", strings.get("menu.back")),
+    ])
+}
+
+pub fn audio(strings: &Strings) -> Menu {
+    Menu::new("audio", vec![
+        Widget::heading(strings.get("menu.audio")),
+        Widget::Separator,
+        toggle(strings, "music", "menu.music", |s| s.music_playing, |s, on| s.music_playing = on),
+        Widget::Separator,
+        Widget::button("back", strings.get("menu.back")),
+    ])
+}
+
+pub fn hud(strings: &Strings) -> Menu {
+    Menu::new("hud", vec![
+        Widget::heading(strings.get("menu.hud")),
+        Widget::Separator,
+        toggle(strings, "show", "menu.hud_show", |s| s.hud_show, |s, on| s.hud_show = on),
+        Widget::slider("scale", strings.get("menu.hud_scale"), (0.5, 2.0, 0.25), |s| s.hud_scale, |s, v| s.hud_scale = v),
+        Widget::dropdown(
+            "crosshair",
+            strings.get("menu.crosshair"),
+            CROSSHAIRS.iter().map(|key| strings.get(key)).collect(),
+            |s| s.crosshair.min(CROSSHAIRS.len() - 1),
+            |s, k| s.crosshair = k,
+        ),
+        toggle(strings, "compass", "menu.compass", |s| s.hud_compass, |s, on| s.hud_compass = on),
+        Widget::Separator,
+        Widget::button("back", strings.get("menu.back")),
+    ])
+}
+
+pub fn controls(strings: &Strings) -> Menu {
+    Menu::new("controls", vec![
+        Widget::heading(strings.get("menu.controls")),
+        Widget::Separator,
+        Widget::slider("mouse", strings.get("menu.mouse"), (1.0, 30.0, 1.0), |s| s.mouse_sensitivity, |s, v| s.mouse_sensitivity = v),
+        Widget::label(strings.get("controls.move")),
+        Widget::label(strings.get("controls.jump")),
+        Widget::label(strings.get("controls.use")),
+        Widget::label(strings.get("controls.messages")),
+        Widget::label(strings.get("controls.screenshot")),
+        Widget::Separator,
+        Widget::button("back", strings.get("menu.back")),
+    ])
+}
+
+// Yes or no, `name` tells what is being confirmed
+pub fn confirm(strings: &Strings, name: &'static str, question: &str) -> Menu {
+    Menu::new(name, vec![
+        Widget::label(question),
+        Widget::Separator,
+        Widget::Row(vec![
+            Widget::button("yes", strings.get("menu.yes")),
+            Widget::button("no", strings.get("menu.no")),
+        ]),
+    ])
+}
+
+pub fn death(strings: &Strings, cause: &str) -> Menu {
+    Menu::new("death", vec![
+        Widget::heading(strings.get("death.heading")),
+        Widget::label(cause),
+        Widget::Separator,
+        Widget::Row(vec![
+            Widget::button("respawn", strings.get("menu.respawn")),
+            Widget::button("quit", strings.get("menu.quit")),
+        ]),
+    ])
+}
+
+// Intermission between levels, `stats` are the lines under the title
+pub fn end(strings: &Strings, title: &str, stats: Vec<String>, last: bool) -> Menu {
+    let mut widgets = vec![Widget::heading(title), Widget::Separator];
+    widgets.extend(stats.iter().map(|line| Widget::label(line)));
+    widgets.push(Widget::Separator);
+    widgets.push(if last {
+        Widget::button("quit", strings.get("menu.quit"))
+    } else {
+        Widget::button("next", strings.get("menu.next"))
+    });
+    Menu::new("end", widgets)
+}
@@ -1,8 +1,12 @@
 use crate::camera;
+use crate::console;
+use crate::hud;
 use crate::player;
+use crate::richtext;
 use crate::settings;
 use crate::sprites;
 use crate::text;
+use crate::weapon;
 
 #[repr(C)]
 struct Vec2 {
@@ -16,13 +20,16 @@ struct Vec3 {
     z: f32,
 }
 
-// #[repr(C)]
-// struct Vec4 {
-//     x: f32,
-//     y: f32,
-//     z: f32,
-//     w: f32,
-// }
+#[repr(C)]
+struct Vec4 {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32,
+}
+
+// Vertices that are not text keep the colour of their pipeline
+const NO_TINT: Vec4 = Vec4 { x: 0.0, y: 0.0, z: 0.0, w: 0.0 };
 
 #[derive(Debug, Clone, Copy)]
 pub struct TextureUV {
@@ -46,6 +53,7 @@ pub struct Vertex {
     pos: Vec3,
     uv: Vec2,
     act: f32,
+    tint: Vec4,
 }
 
 pub struct Mesh {
@@ -69,138 +77,89 @@ impl Mesh {
 
         for l in 0..face_buffer.len {
             if face_buffer.faces_dist[l].is_wall {
-                let texture_u =
-                    1.0 + face_buffer.faces_dist[l].texture_top.overflowing_rem(32).0 as f32 / 4.0;
-                let texture_v =
-                    1.0 + face_buffer.faces_dist[l].texture_top.overflowing_div(32).0 as f32;
-
-                let tex_uv = TextureUV {
-                    u1: texture_u * du - 1.5 * uw,
-                    u2: texture_u * du - 0.5 * uw,
-                    v1: texture_v * du - 1.5 * uw,
-                    v2: texture_v * du - 0.5 * uw,
-                };
-
-                let x = face_buffer.faces_dist[l].top_right_x as f32;
-                let y = face_buffer.faces_dist[l].top_right_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 2.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v1,
-                    },
-                    act: 0.0,
-                }); // top right
-                let x = face_buffer.faces_dist[l].bottom_right_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_right_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 1.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v2,
-                    },
-                    act: 0.0,
-                }); // bottom right
-                let x = face_buffer.faces_dist[l].bottom_left_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_left_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 1.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v2,
-                    },
-                    act: 0.0,
-                }); // bottom left
-                let x = face_buffer.faces_dist[l].top_left_x as f32;
-                let y = face_buffer.faces_dist[l].top_left_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 2.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v1,
-                    },
-                    act: 0.0,
-                }); // top left
-
-                indices.push(4 * idx + 0);
-                indices.push(4 * idx + 1);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 3);
-                indices.push(4 * idx + 0);
-
-                idx = idx + 1;
-
-                let texture_u =
-                    1.0 + face_buffer.faces_dist[l].texture_bot.overflowing_rem(32).0 as f32 / 4.0;
-                let texture_v =
-                    1.0 + face_buffer.faces_dist[l].texture_bot.overflowing_div(32).0 as f32;
-
-                let tex_uv = TextureUV {
-                    u1: texture_u * du - 1.5 * uw,
-                    u2: texture_u * du - 0.5 * uw,
-                    v1: texture_v * du - 1.5 * uw,
-                    v2: texture_v * du - 0.5 * uw,
-                };
-
-                let x = face_buffer.faces_dist[l].top_right_x as f32;
-                let y = face_buffer.faces_dist[l].top_right_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 1.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v1,
-                    },
-                    act: 0.0,
-                }); // top right
-                let x = face_buffer.faces_dist[l].bottom_right_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_right_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v2,
-                    },
-                    act: 0.0,
-                }); // bottom right
-                let x = face_buffer.faces_dist[l].bottom_left_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_left_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v2,
-                    },
-                    act: 0.0,
-                }); // bottom left
-                let x = face_buffer.faces_dist[l].top_left_x as f32;
-                let y = face_buffer.faces_dist[l].top_left_y as f32;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 1.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v1,
-                    },
-                    act: 0.0,
-                }); // top left
-
-                indices.push(4 * idx + 0);
-                indices.push(4 * idx + 1);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 3);
-                indices.push(4 * idx + 0);
-
-                idx = idx + 1;
+                let z0 = face_buffer.faces_dist[l].z;
+                // one quad per storey, only the part of the face between
+                // bottom and top is drawn
+                let storeys = face_buffer.faces_dist[l].storeys;
+                for (k, texture) in face_buffer.faces_dist[l].textures[..storeys].iter().enumerate() {
+                    let texture_u = 1.0 + texture.overflowing_rem(32).0 as f32 / 4.0;
+                    let texture_v = 1.0 + texture.overflowing_div(32).0 as f32;
+
+                    let storey = k as f32;
+                    let (z_lo, z_hi) = (face_buffer.faces_dist[l].bottom.max(storey), face_buffer.faces_dist[l].top.min(storey + 1.0));
+                    let tex_uv = TextureUV {
+                        u1: texture_u * du - 1.5 * uw,
+                        u2: texture_u * du - 0.5 * uw,
+                        v1: texture_v * du - 1.5 * uw + (storey + 1.0 - z_hi) * uw,
+                        v2: texture_v * du - 1.5 * uw + (storey + 1.0 - z_lo) * uw,
+                    };
+
+                    if z_hi > z_lo {
+                        let x = face_buffer.faces_dist[l].top_right_x;
+                        let y = face_buffer.faces_dist[l].top_right_y;
+                        vertices.push(Vertex {
+                            pos: Vec3 { x, y, z: z_hi + z0 },
+                            uv: Vec2 {
+                                x: tex_uv.u2,
+                                y: tex_uv.v1,
+                            },
+                            act: 0.0,
+                            tint: NO_TINT,
+                        }); // top right
+                        let x = face_buffer.faces_dist[l].bottom_right_x;
+                        let y = face_buffer.faces_dist[l].bottom_right_y;
+                        vertices.push(Vertex {
+                            pos: Vec3 { x, y, z: z_lo + z0 },
+                            uv: Vec2 {
+                                x: tex_uv.u2,
+                                y: tex_uv.v2,
+                            },
+                            act: 0.0,
+                            tint: NO_TINT,
+                        }); // bottom right
+                        let x = face_buffer.faces_dist[l].bottom_left_x;
+                        let y = face_buffer.faces_dist[l].bottom_left_y;
+                        vertices.push(Vertex {
+                            pos: Vec3 { x, y, z: z_lo + z0 },
+                            uv: Vec2 {
+                                x: tex_uv.u1,
+                                y: tex_uv.v2,
+                            },
+                            act: 0.0,
+                            tint: NO_TINT,
+                        }); // bottom left
+                        let x = face_buffer.faces_dist[l].top_left_x;
+                        let y = face_buffer.faces_dist[l].top_left_y;
+                        vertices.push(Vertex {
+                            pos: Vec3 { x, y, z: z_hi + z0 },
+                            uv: Vec2 {
+                                x: tex_uv.u1,
+                                y: tex_uv.v1,
+                            },
+                            act: 0.0,
+                            tint: NO_TINT,
+                        }); // top left
+
+                        indices.push(4 * idx + 0);
+                        indices.push(4 * idx + 1);
+                        indices.push(4 * idx + 2);
+                        indices.push(4 * idx + 2);
+                        indices.push(4 * idx + 3);
+                        indices.push(4 * idx + 0);
+
+                        idx = idx + 1;
+                    }
+                }
             } else {
                 //ceiling and floor
-                let z1: f32 = 2.0;
-                let z2: f32 = 0.0;
+                let z1: f32 = face_buffer.faces_dist[l].top;
+                // floor corners in vertex order, ramps are sloped
+                let z2 = face_buffer.faces_dist[l].floor_z;
 
                 let texture1_u =
-                    1.0 + face_buffer.faces_dist[l].texture_top.overflowing_rem(32).0 as f32 / 4.0;
+                    1.0 + face_buffer.faces_dist[l].textures[1].overflowing_rem(32).0 as f32 / 4.0;
                 let texture1_v =
-                    1.0 + face_buffer.faces_dist[l].texture_top.overflowing_div(32).0 as f32;
+                    1.0 + face_buffer.faces_dist[l].textures[1].overflowing_div(32).0 as f32;
 
                 let tex_uv_1 = TextureUV {
                     u1: texture1_u * du - 1.5 * uw,
@@ -210,9 +169,9 @@ impl Mesh {
                 };
 
                 let texture2_u =
-                    1.0 + face_buffer.faces_dist[l].texture_bot.overflowing_rem(32).0 as f32 / 4.0;
+                    1.0 + face_buffer.faces_dist[l].textures[0].overflowing_rem(32).0 as f32 / 4.0;
                 let texture2_v =
-                    1.0 + face_buffer.faces_dist[l].texture_bot.overflowing_div(32).0 as f32;
+                    1.0 + face_buffer.faces_dist[l].textures[0].overflowing_div(32).0 as f32;
 
                 let tex_uv_2 = TextureUV {
                     u1: texture2_u * du - 1.5 * uw,
@@ -221,8 +180,8 @@ impl Mesh {
                     v2: texture2_v * du - 0.5 * uw,
                 };
 
-                let x = face_buffer.faces_dist[l].top_right_x as f32;
-                let y = face_buffer.faces_dist[l].top_right_y as f32;
+                let x = face_buffer.faces_dist[l].top_right_x;
+                let y = face_buffer.faces_dist[l].top_right_y;
                 vertices.push(Vertex {
                     pos: Vec3 { x, y, z: z1 },
                     uv: Vec2 {
@@ -230,9 +189,10 @@ impl Mesh {
                         y: tex_uv_1.v1,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // top right
-                let x = face_buffer.faces_dist[l].bottom_right_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_right_y as f32;
+                let x = face_buffer.faces_dist[l].bottom_right_x;
+                let y = face_buffer.faces_dist[l].bottom_right_y;
                 vertices.push(Vertex {
                     pos: Vec3 { x, y, z: z1 },
                     uv: Vec2 {
@@ -240,9 +200,10 @@ impl Mesh {
                         y: tex_uv_1.v2,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // bottom right
-                let x = face_buffer.faces_dist[l].bottom_left_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_left_y as f32;
+                let x = face_buffer.faces_dist[l].bottom_left_x;
+                let y = face_buffer.faces_dist[l].bottom_left_y;
                 vertices.push(Vertex {
                     pos: Vec3 { x, y, z: z1 },
                     uv: Vec2 {
@@ -250,9 +211,10 @@ impl Mesh {
                         y: tex_uv_1.v2,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // bottom left
-                let x = face_buffer.faces_dist[l].top_left_x as f32;
-                let y = face_buffer.faces_dist[l].top_left_y as f32;
+                let x = face_buffer.faces_dist[l].top_left_x;
+                let y = face_buffer.faces_dist[l].top_left_y;
                 vertices.push(Vertex {
                     pos: Vec3 { x, y, z: z1 },
                     uv: Vec2 {
@@ -260,6 +222,7 @@ impl Mesh {
                         y: tex_uv_1.v1,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // top left
 
                 indices.push(4 * idx + 0);
@@ -271,45 +234,49 @@ impl Mesh {
 
                 idx = idx + 1;
 
-                let x = face_buffer.faces_dist[l].top_right_x as f32;
-                let y = face_buffer.faces_dist[l].top_right_y as f32;
+                let x = face_buffer.faces_dist[l].top_right_x;
+                let y = face_buffer.faces_dist[l].top_right_y;
                 vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: z2 },
+                    pos: Vec3 { x, y, z: z2[0] },
                     uv: Vec2 {
                         x: tex_uv_2.u2,
                         y: tex_uv_2.v1,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // top right
-                let x = face_buffer.faces_dist[l].bottom_right_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_right_y as f32;
+                let x = face_buffer.faces_dist[l].bottom_right_x;
+                let y = face_buffer.faces_dist[l].bottom_right_y;
                 vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: z2 },
+                    pos: Vec3 { x, y, z: z2[1] },
                     uv: Vec2 {
                         x: tex_uv_2.u2,
                         y: tex_uv_2.v2,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // bottom right
-                let x = face_buffer.faces_dist[l].bottom_left_x as f32;
-                let y = face_buffer.faces_dist[l].bottom_left_y as f32;
+                let x = face_buffer.faces_dist[l].bottom_left_x;
+                let y = face_buffer.faces_dist[l].bottom_left_y;
                 vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: z2 },
+                    pos: Vec3 { x, y, z: z2[2] },
                     uv: Vec2 {
                         x: tex_uv_2.u1,
                         y: tex_uv_2.v2,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // bottom left
-                let x = face_buffer.faces_dist[l].top_left_x as f32;
-                let y = face_buffer.faces_dist[l].top_left_y as f32;
+                let x = face_buffer.faces_dist[l].top_left_x;
+                let y = face_buffer.faces_dist[l].top_left_y;
                 vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: z2 },
+                    pos: Vec3 { x, y, z: z2[3] },
                     uv: Vec2 {
                         x: tex_uv_2.u1,
                         y: tex_uv_2.v1,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // top left
 
                 indices.push(4 * idx + 0);
@@ -350,6 +317,7 @@ impl Mesh {
                         y: tex_uv.v1,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // top right
                 let x = sprite_buffer.sprites_dist[l].bottom_right_x;
                 let y = sprite_buffer.sprites_dist[l].bottom_right_y;
@@ -361,6 +329,7 @@ impl Mesh {
                         y: tex_uv.v2,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // bottom right
                 let x = sprite_buffer.sprites_dist[l].bottom_left_x;
                 let y = sprite_buffer.sprites_dist[l].bottom_left_y;
@@ -372,6 +341,7 @@ impl Mesh {
                         y: tex_uv.v2,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // bottom left
                 let x = sprite_buffer.sprites_dist[l].top_left_x;
                 let y = sprite_buffer.sprites_dist[l].top_left_y;
@@ -383,6 +353,7 @@ impl Mesh {
                         y: tex_uv.v1,
                     },
                     act: 0.0,
+                    tint: NO_TINT,
                 }); // top left
 
                 indices.push(4 * idx + 0);
@@ -403,126 +374,49 @@ impl Mesh {
         }
     }
 
-    pub fn new_overlay(overlay: &text::Overlay, scalex: f32, scaley: f32, fired: bool) -> Mesh {
+    pub fn new_overlay(overlay: &text::Overlay, fonts: &text::Fonts, scalex: f32, scaley: f32) -> Mesh {
         let mut vertices: Vec<Vertex> = Vec::new();
         let mut indices: Vec<i16> = Vec::new();
         let mut idx = 0;
 
-        let mut tex_uv;
-
-        if fired {
-            tex_uv = text::string_to_uv("#")[0]
-        } else {
-            tex_uv = text::string_to_uv("&")[0]
-        }
-
-        let x = 0.5 + text::WIDTH * overlay.scale * scalex;
-        let y = 0.5 - text::HEIGHT * overlay.scale * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u2,
-                y: tex_uv.v1,
-            },
-            act: 0.0,
-        }); // top right
-        let x = 0.5 + text::WIDTH * overlay.scale * scalex;
-        let y = 0.5 + text::HEIGHT * overlay.scale * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u2,
-                y: tex_uv.v2,
-            },
-            act: 0.0,
-        }); // bottom right
-        let x = 0.5 - text::WIDTH * overlay.scale * scalex;
-        let y = 0.5 + text::HEIGHT * overlay.scale * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u1,
-                y: tex_uv.v2,
-            },
-            act: 0.0,
-        }); // bottom left
-        let x = 0.5 - text::WIDTH * overlay.scale * scalex;
-        let y = 0.5 - text::HEIGHT * overlay.scale * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u1,
-                y: tex_uv.v1,
-            },
-            act: 0.0,
-        }); // top left
-
-        indices.push(4 * idx + 0);
-        indices.push(4 * idx + 1);
-        indices.push(4 * idx + 2);
-        indices.push(4 * idx + 2);
-        indices.push(4 * idx + 3);
-        indices.push(4 * idx + 0);
-
-        idx = idx + 1;
-
         for s in 0..overlay.lines.len() {
-            let coords = text::string_to_uv(&overlay.lines[s]);
-
-            for l in 0..coords.len() {
-                tex_uv = coords[l];
-
-                let lf = l as f32;
+            let glyphs = fonts.quads(overlay.line_font[s], &overlay.lines[s], overlay.line_x[s], overlay.line_y[s], overlay.scale);
+            let tint = overlay.line_tint[s].unwrap_or_default();
+            for ((x1, y1, x2, y2), tex_uv) in glyphs {
+                if idx as usize >= settings::MAX_QUADS_OVERLAY {
+                    break;
+                }
+                let rect = (x1 * scalex, y1 * scaley, x2 * scalex, y2 * scaley);
+                quad(&mut vertices, &mut indices, &mut idx, rect, tex_uv, 0.0, tint);
+            }
+        }
 
-                let x = (overlay.line_x[s] + (lf + 1.0) * text::WIDTH * overlay.scale) * scalex;
-                let y = overlay.line_y[s] * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v1,
-                    },
-                    act: 0.0,
-                }); // top right
-                let x = (overlay.line_x[s] + (lf + 1.0) * text::WIDTH * overlay.scale) * scalex;
-                let y = (overlay.line_y[s] + overlay.line_height) * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v2,
-                    },
-                    act: 0.0,
-                }); // bottom right
-                let x = (overlay.line_x[s] + lf * text::WIDTH * overlay.scale) * scalex;
-                let y = (overlay.line_y[s] + overlay.line_height) * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v2,
-                    },
-                    act: 0.0,
-                }); // bottom left
-                let x = (overlay.line_x[s] + lf * text::WIDTH * overlay.scale) * scalex;
-                let y = (overlay.line_y[s]) * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v1,
-                    },
-                    act: 0.0,
-                }); // top left
+        Mesh {
+            vertices,
+            indices,
+            num: idx as i32,
+        }
+    }
 
-                indices.push(4 * idx + 0);
-                indices.push(4 * idx + 1);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 3);
-                indices.push(4 * idx + 0);
+    // Overlay text on a translucent box from the top of the screen down
+    // to `height` pixels, glyphs past the size of its buffer are left out
+    pub fn new_console(console: &text::Overlay, fonts: &text::Fonts, height: f32, scalex: f32, scaley: f32) -> Mesh {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<i16> = Vec::new();
+        let mut idx = 0;
 
-                idx = idx + 1;
+        let rect = (0.0, 0.0, 1.0, height * scaley);
+        quad(&mut vertices, &mut indices, &mut idx, rect, fonts.solid_uv(), 0.0, console::BACKGROUND);
+
+        for s in 0..console.lines.len() {
+            let glyphs = fonts.quads(console.line_font[s], &console.lines[s], console.line_x[s], console.line_y[s], console.scale);
+            let tint = console.line_tint[s].unwrap_or_default();
+            for ((x1, y1, x2, y2), tex_uv) in glyphs {
+                if idx as usize >= settings::MAX_QUADS_CONSOLE {
+                    break;
+                }
+                let rect = (x1 * scalex, y1 * scaley, x2 * scalex, y2 * scaley);
+                quad(&mut vertices, &mut indices, &mut idx, rect, tex_uv, 0.0, tint);
             }
         }
 
@@ -533,120 +427,94 @@ impl Mesh {
         }
     }
 
-    pub fn new_gui(gui: &text::GUI, scalex: f32, scaley: f32) -> Mesh {
+    // Plain coloured rectangles under overlay text, all in pixels
+    pub fn new_profiler(
+        rects: &[(Rect, richtext::Color)],
+        text: &text::Overlay,
+        fonts: &text::Fonts,
+        scalex: f32,
+        scaley: f32,
+    ) -> Mesh {
         let mut vertices: Vec<Vertex> = Vec::new();
         let mut indices: Vec<i16> = Vec::new();
         let mut idx = 0;
 
-        let mut tex_uv = text::string_to_uv("=")[0];
+        for &((x1, y1, x2, y2), color) in rects.iter().take(settings::MAX_QUADS_PROFILER) {
+            let rect = (x1 * scalex, y1 * scaley, x2 * scalex, y2 * scaley);
+            quad(&mut vertices, &mut indices, &mut idx, rect, fonts.solid_uv(), 0.0, color);
+        }
 
-        let x = 0.5 * (1.0 + 1.5 * gui.max_width * scalex);
-        let y = (gui.line_y[0] - 1.0 * gui.line_height) * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u2,
-                y: tex_uv.v1,
-            },
-            act: 0.0,
-        }); // top right
-        let x = 0.5 * (1.0 + 1.5 * gui.max_width * scalex);
-        let y = (gui.line_y[gui.lines.len() - 1] + 2.0 * gui.line_height) * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u2,
-                y: tex_uv.v2,
-            },
-            act: 0.0,
-        }); // bottom right
-        let x = 0.5 * (1.0 - 1.5 * gui.max_width * scalex);
-        let y = (gui.line_y[gui.lines.len() - 1] + 2.0 * gui.line_height) * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u1,
-                y: tex_uv.v2,
-            },
-            act: 0.0,
-        }); // bottom left
-        let x = 0.5 * (1.0 - 1.5 * gui.max_width * scalex);
-        let y = (gui.line_y[0] - 1.0 * gui.line_height) * scaley;
-        vertices.push(Vertex {
-            pos: Vec3 { x, y, z: 0.0 },
-            uv: Vec2 {
-                x: tex_uv.u1,
-                y: tex_uv.v1,
-            },
-            act: 0.0,
-        }); // top left
+        for s in 0..text.lines.len() {
+            let glyphs = fonts.quads(text.line_font[s], &text.lines[s], text.line_x[s], text.line_y[s], text.scale);
+            let tint = text.line_tint[s].unwrap_or_default();
+            for ((x1, y1, x2, y2), tex_uv) in glyphs {
+                if idx as usize >= settings::MAX_QUADS_PROFILER {
+                    break;
+                }
+                let rect = (x1 * scalex, y1 * scaley, x2 * scalex, y2 * scaley);
+                quad(&mut vertices, &mut indices, &mut idx, rect, tex_uv, 0.0, tint);
+            }
+        }
 
-        indices.push(4 * idx + 0);
-        indices.push(4 * idx + 1);
-        indices.push(4 * idx + 2);
-        indices.push(4 * idx + 2);
-        indices.push(4 * idx + 3);
-        indices.push(4 * idx + 0);
+        Mesh {
+            vertices,
+            indices,
+            num: idx as i32,
+        }
+    }
 
-        idx = idx + 1;
+    // HUD icons, marked by act, followed by its text
+    pub fn new_status(hud: &hud::Hud, fonts: &text::Fonts, scalex: f32, scaley: f32) -> Mesh {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<i16> = Vec::new();
+        let mut idx = 0;
 
-        for s in 0..gui.lines.len() {
-            let coords = text::string_to_uv(&gui.lines[s]);
+        for k in 0..hud.icons.len() {
+            let (x1, y1, x2, y2) = hud.icon_rect[k];
+            let rect = (x1 * scalex, y1 * scaley, x2 * scalex, y2 * scaley);
+            let tint = hud.icon_tint[k].unwrap_or_default();
+            quad(&mut vertices, &mut indices, &mut idx, rect, hud.icons[k].uv(), 1.0, tint);
+        }
 
-            for l in 0..coords.len() {
-                tex_uv = coords[l];
+        let text = &hud.text;
+        for s in 0..text.lines.len() {
+            let glyphs = fonts.quads(text.line_font[s], &text.lines[s], text.line_x[s], text.line_y[s], text.scale);
+            let tint = text.line_tint[s].unwrap_or_default();
+            for ((x1, y1, x2, y2), tex_uv) in glyphs {
+                if idx as usize >= settings::MAX_QUADS_STATUS {
+                    break;
+                }
+                let rect = (x1 * scalex, y1 * scaley, x2 * scalex, y2 * scaley);
+                quad(&mut vertices, &mut indices, &mut idx, rect, tex_uv, 0.0, tint);
+            }
+        }
 
-                let lf = l as f32;
+        Mesh {
+            vertices,
+            indices,
+            num: idx as i32,
+        }
+    }
 
-                let x = (gui.line_x[s] + (lf + 1.0) * text::WIDTH * gui.scale) * scalex;
-                let y = gui.line_y[s] * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v1,
-                    },
-                    act: gui.line_active[s] as f32,
-                }); // top right
-                let x = (gui.line_x[s] + (lf + 1.0) * text::WIDTH * gui.scale) * scalex;
-                let y = (gui.line_y[s] + gui.line_height) * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u2,
-                        y: tex_uv.v2,
-                    },
-                    act: gui.line_active[s] as f32,
-                }); // bottom right
-                let x = (gui.line_x[s] + lf * text::WIDTH * gui.scale) * scalex;
-                let y = (gui.line_y[s] + gui.line_height) * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v2,
-                    },
-                    act: gui.line_active[s] as f32,
-                }); // bottom left
-                let x = (gui.line_x[s] + lf * text::WIDTH * gui.scale) * scalex;
-                let y = (gui.line_y[s]) * scaley;
-                vertices.push(Vertex {
-                    pos: Vec3 { x, y, z: 0.0 },
-                    uv: Vec2 {
-                        x: tex_uv.u1,
-                        y: tex_uv.v1,
-                    },
-                    act: gui.line_active[s] as f32,
-                }); // top left
+    pub fn new_gui(gui: &text::GUI, fonts: &text::Fonts, scalex: f32, scaley: f32) -> Mesh {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<i16> = Vec::new();
+        let mut idx = 0;
 
-                indices.push(4 * idx + 0);
-                indices.push(4 * idx + 1);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 2);
-                indices.push(4 * idx + 3);
-                indices.push(4 * idx + 0);
+        let rect = (
+            0.5 * (1.0 - 1.5 * gui.max_width * scalex),
+            (gui.line_y[0] - 1.0 * gui.line_height) * scaley,
+            0.5 * (1.0 + 1.5 * gui.max_width * scalex),
+            (gui.line_y[gui.lines.len() - 1] + 2.0 * gui.line_height) * scaley,
+        );
+        quad(&mut vertices, &mut indices, &mut idx, rect, fonts.panel_uv(), 0.0, Default::default());
 
-                idx = idx + 1;
+        for s in 0..gui.lines.len() {
+            let glyphs = fonts.quads(gui.line_font[s], &gui.lines[s], gui.line_x[s], gui.line_y[s], gui.scale);
+            let tint = gui.line_tint[s].unwrap_or_default();
+            for ((x1, y1, x2, y2), tex_uv) in glyphs {
+                let rect = (x1 * scalex, y1 * scaley, x2 * scalex, y2 * scaley);
+                quad(&mut vertices, &mut indices, &mut idx, rect, tex_uv, gui.line_active[s] as f32, tint);
             }
         }
 
@@ -690,6 +558,7 @@ impl Mesh {
                 y: tex_uv.v1,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // top right
         let x = 1.0 - (x_offset) * scalex;
         let y = 1.0 - (y_offset) * scaley;
@@ -700,6 +569,7 @@ impl Mesh {
                 y: tex_uv.v2,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // bottom right
         let x = 1.0 - (x_offset + width) * scalex;
         let y = 1.0 - (y_offset) * scaley;
@@ -710,6 +580,7 @@ impl Mesh {
                 y: tex_uv.v2,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // bottom left
         let x = 1.0 - (x_offset + width) * scalex;
         let y = 1.0 - (y_offset + height) * scaley;
@@ -720,6 +591,7 @@ impl Mesh {
                 y: tex_uv.v1,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // top left
 
         indices.push(0);
@@ -754,6 +626,7 @@ impl Mesh {
                 y: tex_uv.v1,
             },
             act,
+            tint: NO_TINT,
         });
 
         let mut idx = 0;
@@ -781,6 +654,7 @@ impl Mesh {
                         y: tex_uv.v1,
                     },
                     act,
+                    tint: NO_TINT,
                 });
 
                 idx = idx + 1;
@@ -800,6 +674,45 @@ impl Mesh {
         }
     }
 
+    pub fn new_hud(weapon: &weapon::Weapon, scalex: f32, scaley: f32) -> Mesh {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<i16> = Vec::new();
+        let mut idx = 0;
+
+        // weapon, anchored to the bottom centre of the screen
+        let h = 0.45;
+        let w = h * scalex / scaley;
+        let f = weapon.frame() as f32;
+        let mut tex_uv = TextureUV {
+            u1: 64.0 * f,
+            u2: 64.0 * (f + 1.0),
+            v1: 0.0,
+            v2: 64.0,
+        };
+        tex_uv.normalize(256.0, 256.0);
+        quad(&mut vertices, &mut indices, &mut idx, (0.5 - 0.5 * w, 1.0 - h, 0.5 + 0.5 * w, 1.0), tex_uv, 0.0, Default::default());
+
+        // hit marker around the crosshair
+        if weapon.feedback == weapon::Feedback::Hit {
+            let w = 48.0 * scalex;
+            let h = 48.0 * scaley;
+            let mut tex_uv = TextureUV {
+                u1: 192.0,
+                u2: 224.0,
+                v1: 0.0,
+                v2: 32.0,
+            };
+            tex_uv.normalize(256.0, 256.0);
+            quad(&mut vertices, &mut indices, &mut idx, (0.5 - 0.5 * w, 0.5 - 0.5 * h, 0.5 + 0.5 * w, 0.5 + 0.5 * h), tex_uv, 1.0, Default::default());
+        }
+
+        Mesh {
+            vertices,
+            indices,
+            num: idx as i32,
+        }
+    }
+
     pub fn new_screen(
     ) -> Mesh {
         let mut vertices: Vec<Vertex> = Vec::new();
@@ -821,6 +734,7 @@ impl Mesh {
                 y: tex_uv.v1,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // top right
         let x = 1.0;
         let y = -1.0;
@@ -831,6 +745,7 @@ impl Mesh {
                 y: tex_uv.v2,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // bottom right
         let x = -1.0;
         let y = -1.0;
@@ -841,6 +756,7 @@ impl Mesh {
                 y: tex_uv.v2,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // bottom left
         let x = -1.0;
         let y = 1.0;
@@ -851,6 +767,7 @@ impl Mesh {
                 y: tex_uv.v1,
             },
             act: 0.0,
+            tint: NO_TINT,
         }); // top left
 
         indices.push(0);
@@ -867,3 +784,53 @@ impl Mesh {
         }
     }
 }
+
+// (left, top, right, bottom)
+pub type Rect = (f32, f32, f32, f32);
+
+// Screen space quad, rect in 0..1 coordinates, a tint with zero alpha keeps
+// the pipeline colour
+fn quad(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<i16>,
+    idx: &mut i16,
+    rect: Rect,
+    tex_uv: TextureUV,
+    act: f32,
+    tint: (f32, f32, f32, f32),
+) {
+    let (x1, y1, x2, y2) = rect;
+    vertices.push(Vertex {
+        pos: Vec3 { x: x2, y: y1, z: 0.0 },
+        uv: Vec2 { x: tex_uv.u2, y: tex_uv.v1 },
+        act,
+        tint: Vec4 { x: tint.0, y: tint.1, z: tint.2, w: tint.3 },
+    }); // top right
+    vertices.push(Vertex {
+        pos: Vec3 { x: x2, y: y2, z: 0.0 },
+        uv: Vec2 { x: tex_uv.u2, y: tex_uv.v2 },
+        act,
+        tint: Vec4 { x: tint.0, y: tint.1, z: tint.2, w: tint.3 },
+    }); // bottom right
+    vertices.push(Vertex {
+        pos: Vec3 { x: x1, y: y2, z: 0.0 },
+        uv: Vec2 { x: tex_uv.u1, y: tex_uv.v2 },
+        act,
+        tint: Vec4 { x: tint.0, y: tint.1, z: tint.2, w: tint.3 },
+    }); // bottom left
+    vertices.push(Vertex {
+        pos: Vec3 { x: x1, y: y1, z: 0.0 },
+        uv: Vec2 { x: tex_uv.u1, y: tex_uv.v1 },
+        act,
+        tint: Vec4 { x: tint.0, y: tint.1, z: tint.2, w: tint.3 },
+    }); // top left
+
+    indices.push(4 * *idx);
+    indices.push(4 * *idx + 1);
+    indices.push(4 * *idx + 2);
+    indices.push(4 * *idx + 2);
+    indices.push(4 * *idx + 3);
+    indices.push(4 * *idx);
+
+    *idx += 1;
+}
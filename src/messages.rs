@@ -0,0 +1,238 @@
+use std::collections::VecDeque;
+
+use crate::{lang, richtext, settings, text};
+
+// Messages shown at once in the corner feed, the others wait their turn
+const FEED_LINES: usize = 4;
+const HISTORY_MAX: usize = 100;
+pub const HISTORY_LINES: usize = 12;
+// Seconds a message takes to fade out at the end of its time
+const FADE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    // pickups and other chatter
+    Low,
+    Normal,
+    // shown red and for longer
+    Error,
+    // shown large in the middle of the screen, one at a time
+    Center,
+}
+
+impl Priority {
+    fn duration(self) -> f32 {
+        match self {
+            Priority::Low => 2.5,
+            Priority::Normal => 4.0,
+            Priority::Error => 6.0,
+            Priority::Center => 3.0,
+        }
+    }
+
+    fn color(self) -> Option<richtext::Color> {
+        match self {
+            Priority::Error => richtext::color("red"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub text: String,
+    pub priority: Priority,
+    // seconds left on screen
+    pub time: f32,
+}
+
+impl Message {
+    fn alpha(&self) -> f32 {
+        (self.time / FADE).min(1.0)
+    }
+}
+
+// Timed notifications for the HUD: a feed in the top right corner, centre
+// screen announcements and a history of everything shown. Texts are markup.
+pub struct Messages {
+    pub feed: Vec<Message>,
+    // waiting for room in the feed, the most important first
+    pub queue: VecDeque<Message>,
+    // the first one is on screen
+    pub center: VecDeque<Message>,
+    pub history: Vec<(String, Priority)>,
+    pub show_history: bool,
+    // lines scrolled back from the end of the history
+    pub scroll: usize,
+}
+
+impl Messages {
+    pub fn new() -> Messages {
+        Messages {
+            feed: Vec::new(),
+            queue: VecDeque::new(),
+            center: VecDeque::new(),
+            history: Vec::new(),
+            show_history: false,
+            scroll: 0,
+        }
+    }
+
+    pub fn push(&mut self, text: &str, priority: Priority) {
+        self.history.push((text.to_string(), priority));
+        if self.history.len() > HISTORY_MAX {
+            self.history.remove(0);
+        }
+        self.scroll = 0;
+
+        let message = Message { text: text.to_string(), priority, time: priority.duration() };
+        if priority == Priority::Center {
+            self.center.push_back(message);
+        } else if self.feed.len() < FEED_LINES {
+            self.feed.push(message);
+        } else {
+            let at = self.queue.iter().position(|m| m.priority < priority).unwrap_or(self.queue.len());
+            self.queue.insert(at, message);
+        }
+    }
+
+    // Counts the messages down, the oldest one in the feed starts to fade
+    // early when others are waiting
+    pub fn update(&mut self, delta_time: f32) {
+        if !self.queue.is_empty() {
+            if let Some(oldest) = self.feed.first_mut() {
+                oldest.time = oldest.time.min(FADE);
+            }
+        }
+        for message in self.feed.iter_mut() {
+            message.time -= delta_time;
+        }
+        self.feed.retain(|message| message.time > 0.0);
+        while self.feed.len() < FEED_LINES {
+            match self.queue.pop_front() {
+                Some(message) => self.feed.push(message),
+                None => break,
+            }
+        }
+
+        if self.center.len() > 1 {
+            self.center[0].time = self.center[0].time.min(FADE);
+        }
+        if let Some(message) = self.center.front_mut() {
+            message.time -= delta_time;
+            if message.time <= 0.0 {
+                self.center.pop_front();
+            }
+        }
+    }
+
+    // Drops what is on screen or waiting, the history stays
+    pub fn clear(&mut self) {
+        self.feed.clear();
+        self.queue.clear();
+        self.center.clear();
+    }
+
+    pub fn toggle_history(&mut self) {
+        self.show_history = !self.show_history;
+        self.scroll = 0;
+    }
+
+    pub fn scroll_history(&mut self, lines: i32) {
+        let max = self.history.len().saturating_sub(HISTORY_LINES) as i32;
+        self.scroll = (self.scroll as i32 + lines).clamp(0, max) as usize;
+    }
+
+    // Adds the feed or the history and the centre message to the overlay
+    pub fn draw(
+        &self,
+        overlay: &mut text::Overlay,
+        fonts: &text::Fonts,
+        strings: &lang::Strings,
+        settings: &settings::Settings,
+    ) {
+        let max_width = 0.4 * settings.screen_width_f;
+        let right = settings.screen_width_f - overlay.x0;
+        let mut y = overlay.y0;
+
+        if self.show_history {
+            let end = self.history.len().saturating_sub(self.scroll);
+            let start = end.saturating_sub(HISTORY_LINES);
+            let title = if end > 0 {
+                strings.format("messages.range", &[&(start + 1), &end, &self.history.len()])
+            } else {
+                strings.get("messages.none").to_string()
+            };
+            let lines = std::iter::once((title, Priority::Normal))
+                .chain(self.history[start..end].iter().cloned());
+            for (line, priority) in lines {
+                let text_box = richtext::TextBox::new(fonts, &line, text::SANS, Some(max_width), richtext::Align::Right);
+                let first = overlay.lines.len();
+                overlay.push(&text_box, right - text_box.width, y);
+                overlay.tint(first, priority.color(), 1.0);
+                y += text_box.height;
+            }
+        } else {
+            for message in &self.feed {
+                let text_box =
+                    richtext::TextBox::new(fonts, &message.text, text::SANS, Some(max_width), richtext::Align::Right);
+                let first = overlay.lines.len();
+                overlay.push(&text_box, right - text_box.width, y);
+                overlay.tint(first, message.priority.color(), message.alpha());
+                y += text_box.height;
+            }
+        }
+
+        if let Some(message) = self.center.front() {
+            let text_box = richtext::TextBox::new(
+                fonts,
+                &message.text,
+                text::SANS_LARGE,
+                Some(0.8 * settings.screen_width_f),
+                richtext::Align::Center,
+            );
+            let x = ((settings.screen_width_f - text_box.width) * 0.5).round();
+            let y = (settings.screen_height_f * 0.3 - text_box.height * 0.5).round();
+            let first = overlay.lines.len();
+            overlay.push(&text_box, x, y);
+            overlay.tint(first, None, message.alpha());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queues_by_priority_and_fades() {
+        let mut messages = Messages::new();
+        for k in 0..FEED_LINES {
+            messages.push(&format!("{}", k), Priority::Low);
+        }
+        messages.push("low", Priority::Low);
+        messages.push("error", Priority::Error);
+        let waiting: Vec<&str> = messages.queue.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(waiting, vec!["error", "low"]);
+
+        // the oldest message makes room within a fade
+        messages.update(0.5);
+        assert_eq!(messages.feed[0].alpha(), 0.5);
+        messages.update(0.6);
+        assert_eq!(messages.feed.len(), FEED_LINES);
+        assert_eq!(messages.feed.last().unwrap().text, "error");
+        assert_eq!(messages.history.len(), FEED_LINES + 2);
+    }
+
+    #[test]
+    fn shows_one_center_message_at_a_time() {
+        let mut messages = Messages::new();
+        messages.push("first", Priority::Center);
+        messages.push("second", Priority::Center);
+        assert!(messages.feed.is_empty());
+        messages.update(FADE + 0.1);
+        assert_eq!(messages.center.len(), 1);
+        assert_eq!(messages.center[0].text, "second");
+        assert_eq!(messages.center[0].time, Priority::Center.duration());
+    }
+}
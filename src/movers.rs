@@ -0,0 +1,465 @@
+use crate::camera;
+use crate::entities;
+use crate::inventory;
+use crate::lang;
+use crate::map;
+use crate::player;
+use crate::settings;
+
+pub const SWITCH_OFF: u8 = 52;
+pub const SWITCH_ON: u8 = 56;
+
+// Doors and gates stop blocking once they are this far open
+const OPEN_PASSABLE: f32 = 0.9;
+const DOOR_WAIT: f32 = 4.0;
+const PANEL_HALF_WIDTH: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DoorStyle {
+    Slide,
+    Swing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoverKind {
+    Door(DoorStyle),
+    PushWall,
+    Gate,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mover {
+    pub kind: MoverKind,
+    pub i: usize,
+    pub j: usize,
+    pub texture_bot: u8,
+    pub texture_top: u8,
+    pub along_x: bool,
+    pub open: f32,
+    pub target: f32,
+    pub speed: f32,
+    pub wait: f32,
+    pub key: Option<inventory::KeyColor>,
+    // 0 is no tag, switches never move the mover
+    pub tag: u32,
+    pub dir: (i32, i32),
+    pub travel: i32,
+    pub done: bool,
+}
+
+impl Mover {
+    pub fn new(kind: MoverKind, i: usize, j: usize, texture: u8) -> Mover {
+        Mover {
+            kind,
+            i,
+            j,
+            texture_bot: texture,
+            texture_top: texture,
+            along_x: true,
+            open: 0.0,
+            target: 0.0,
+            speed: match kind {
+                MoverKind::Door(_) => 1.5,
+                MoverKind::PushWall => 0.5,
+                MoverKind::Gate => 0.5,
+            },
+            wait: 0.0,
+            key: None,
+            tag: 0,
+            dir: (0, 0),
+            travel: 0,
+            done: false,
+        }
+    }
+
+    // Tiles the mover currently occupies, used for collision
+    fn tiles(&self) -> Vec<(usize, usize)> {
+        match self.kind {
+            MoverKind::PushWall => (0..=self.travel)
+                .map(|k| {
+                    (
+                        (self.i as i32 + k * self.dir.0) as usize,
+                        (self.j as i32 + k * self.dir.1) as usize,
+                    )
+                })
+                .collect(),
+            _ => vec![(self.i, self.j)],
+        }
+    }
+
+    fn solid(&self) -> bool {
+        match self.kind {
+            MoverKind::PushWall => self.target > 0.0,
+            _ => self.open < OPEN_PASSABLE,
+        }
+    }
+
+    fn opaque(&self) -> bool {
+        match self.kind {
+            MoverKind::PushWall => false,
+            _ => self.open == 0.0,
+        }
+    }
+
+    // Footprint of the moving part as four corners in the same order as
+    // the wall faces of a tile, plus its height offset.
+    fn footprint(&self) -> ([(f32, f32); 4], f32) {
+        let xi = self.i as f32;
+        let yj = self.j as f32;
+        match self.kind {
+            MoverKind::Door(style) => {
+                let (a, u) = match (style, self.along_x) {
+                    (DoorStyle::Slide, true) => ((xi + self.open, yj + 0.5), (1.0, 0.0)),
+                    (DoorStyle::Slide, false) => ((xi + 0.5, yj + self.open), (0.0, 1.0)),
+                    (DoorStyle::Swing, along_x) => {
+                        let angle = 0.5 * settings::PI * self.open;
+                        if along_x {
+                            ((xi + PANEL_HALF_WIDTH, yj + 0.5), (angle.cos(), angle.sin()))
+                        } else {
+                            ((xi + 0.5, yj + PANEL_HALF_WIDTH), (angle.sin(), angle.cos()))
+                        }
+                    }
+                };
+                let length = if style == DoorStyle::Swing { 1.0 - 2.0 * PANEL_HALF_WIDTH } else { 1.0 };
+                (panel(a, u, length, PANEL_HALF_WIDTH), 0.0)
+            }
+            MoverKind::PushWall => {
+                let d = self.open * self.travel as f32;
+                let x = xi + d * self.dir.0 as f32;
+                let y = yj + d * self.dir.1 as f32;
+                ([(x, y), (x, y + 1.0), (x + 1.0, y + 1.0), (x + 1.0, y)], 0.0)
+            }
+            MoverKind::Gate => (
+                [(xi, yj), (xi, yj + 1.0), (xi + 1.0, yj + 1.0), (xi + 1.0, yj)],
+                2.0 * self.open,
+            ),
+        }
+    }
+}
+
+// Whether the circle (x, y, radius) reaches into tile (i,j)
+fn overlaps(i: usize, j: usize, (x, y, radius): (f32, f32, f32)) -> bool {
+    let nearest_x = x.clamp(i as f32, i as f32 + 1.0);
+    let nearest_y = y.clamp(j as f32, j as f32 + 1.0);
+    (x - nearest_x).powi(2) + (y - nearest_y).powi(2) < radius * radius
+}
+
+// Rectangle of `length` along the unit vector `u` starting at `a`, with
+// the corners listed clockwise like the tile wall faces.
+fn panel(a: (f32, f32), u: (f32, f32), length: f32, half_width: f32) -> [(f32, f32); 4] {
+    let n = (-u.1 * half_width, u.0 * half_width);
+    let b = (a.0 + u.0 * length, a.1 + u.1 * length);
+    [
+        (a.0 - n.0, a.1 - n.1),
+        (a.0 + n.0, a.1 + n.1),
+        (b.0 + n.0, b.1 + n.1),
+        (b.0 - n.0, b.1 - n.1),
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct Switch {
+    pub i: usize,
+    pub j: usize,
+    pub tag: u32,
+    pub on: bool,
+}
+
+#[derive(Default)]
+pub struct Movers {
+    pub movers: Vec<Mover>,
+    pub switches: Vec<Switch>,
+    pub secrets_found: u32,
+}
+
+impl Movers {
+    pub fn new() -> Movers {
+        Movers {
+            movers: Vec::new(),
+            switches: Vec::new(),
+            secrets_found: 0,
+        }
+    }
+
+    // Doors take their orientation from the surrounding walls and
+    // push-walls take their textures from the wall map.
+    pub fn attach(&mut self, game_map: &mut map::GameMap) {
+        for mover in self.movers.iter_mut() {
+            let (i, j) = (mover.i as i32, mover.j as i32);
+            mover.along_x = game_map.is_solid(i - 1, j) && game_map.is_solid(i + 1, j);
+            if mover.kind == MoverKind::PushWall {
+                mover.texture_bot = game_map.wall_bot_array[mover.i][mover.j];
+                mover.texture_top = game_map.wall_top_array[mover.i][mover.j];
+            }
+        }
+        for switch in self.switches.iter() {
+            game_map.wall_bot_array[switch.i][switch.j] = if switch.on { SWITCH_ON } else { SWITCH_OFF };
+        }
+        self.apply(game_map);
+    }
+
+    fn apply(&self, game_map: &mut map::GameMap) {
+        for mover in self.movers.iter() {
+            for (i, j) in mover.tiles() {
+                game_map.blocked_array[i][j] = mover.solid();
+                game_map.opaque_array[i][j] = mover.opaque();
+            }
+        }
+    }
+
+    pub fn update(&mut self, game_map: &mut map::GameMap, player: &player::Player, entity_list: &entities::EntityList, dt: f32) {
+        for mover in self.movers.iter() {
+            for (i, j) in mover.tiles() {
+                game_map.blocked_array[i][j] = false;
+                game_map.opaque_array[i][j] = false;
+            }
+        }
+
+        // the player and the live entities that collide, as circles
+        let bodies: Vec<(f32, f32, f32)> = std::iter::once((player.position.x, player.position.y, player.radius))
+            .chain(entity_list.entities.iter().filter(|e| e.alive).filter_map(|e| {
+                e.collider.map(|c| (e.position.x, e.position.y, c.radius))
+            }))
+            .collect();
+
+        for mover in self.movers.iter_mut() {
+            if let MoverKind::Door(_) = mover.kind {
+                if bodies.iter().any(|&body| overlaps(mover.i, mover.j, body)) {
+                    // never close on anyone in the doorway
+                    mover.target = 1.0;
+                    mover.wait = DOOR_WAIT;
+                } else if mover.open == 1.0 && mover.target == 1.0 {
+                    mover.wait -= dt;
+                    if mover.wait <= 0.0 {
+                        mover.target = 0.0;
+                    }
+                }
+            }
+
+            let step = mover.speed * dt;
+            if mover.open < mover.target {
+                mover.open = (mover.open + step).min(mover.target);
+            } else if mover.open > mover.target {
+                mover.open = (mover.open - step).max(mover.target);
+            }
+
+            if mover.kind == MoverKind::PushWall && mover.open == 1.0 {
+                // the push-wall settles as a plain wall at its end tile
+                let i = (mover.i as i32 + mover.travel * mover.dir.0) as usize;
+                let j = (mover.j as i32 + mover.travel * mover.dir.1) as usize;
+                game_map.wall_bot_array[i][j] = mover.texture_bot;
+                game_map.wall_top_array[i][j] = mover.texture_top;
+                mover.done = true;
+            }
+        }
+
+        self.movers.retain(|m| !m.done);
+        self.apply(game_map);
+    }
+
+    // Operates whatever the player is facing within reach and returns a
+    // message for the player when there is something to say.
+    pub fn use_at(
+        &mut self,
+        game_map: &mut map::GameMap,
+        player: &player::Player,
+        inventory: &inventory::Inventory,
+    ) -> Option<lang::Text> {
+        for reach in [0.6, 1.2] {
+            let i = (player.position.x + reach * player.position.ax).floor() as usize;
+            let j = (player.position.y + reach * player.position.ay).floor() as usize;
+
+            if let Some(k) = self.switches.iter().position(|s| (s.i, s.j) == (i, j)) {
+                let switch = &mut self.switches[k];
+                switch.on = !switch.on;
+                game_map.wall_bot_array[i][j] = if switch.on { SWITCH_ON } else { SWITCH_OFF };
+                let tag = switch.tag;
+                self.activate(tag);
+                return None;
+            }
+
+            if let Some(mover) = self.movers.iter_mut().find(|m| (m.i, m.j) == (i, j)) {
+                return match mover.kind {
+                    MoverKind::Door(_) => {
+                        if let Some(key) = mover.key {
+                            if !inventory.has_key(key) {
+                                return Some(lang::Text::new(&format!("door.locked.{}", key.name()), &[]));
+                            }
+                        }
+                        mover.target = 1.0 - mover.target;
+                        mover.wait = DOOR_WAIT;
+                        None
+                    }
+                    MoverKind::PushWall => {
+                        if mover.target > 0.0 {
+                            return None;
+                        }
+                        let di = i as i32 - player.position.x.floor() as i32;
+                        let dj = j as i32 - player.position.y.floor() as i32;
+                        if di.abs() + dj.abs() != 1 {
+                            return None;
+                        }
+                        let mut travel = 0;
+                        while travel < 2
+                            && !game_map.is_solid(i as i32 + (travel + 1) * di, j as i32 + (travel + 1) * dj)
+                        {
+                            travel += 1;
+                        }
+                        if travel == 0 {
+                            return None;
+                        }
+                        mover.dir = (di, dj);
+                        mover.travel = travel;
+                        mover.speed = 0.5 / travel as f32;
+                        mover.target = 1.0;
+                        game_map.wall_bot_array[i][j] = 255;
+                        game_map.wall_top_array[i][j] = 255;
+                        self.secrets_found += 1;
+                        Some(lang::Text::new("secret.found", &[]))
+                    }
+                    MoverKind::Gate => Some(lang::Text::new("gate.stuck", &[])),
+                };
+            }
+
+            if game_map.is_solid(i as i32, j as i32) {
+                return None;
+            }
+        }
+        None
+    }
+
+    pub fn open_at(&mut self, i: usize, j: usize) {
+        for mover in self.movers.iter_mut().filter(|m| (m.i, m.j) == (i, j)) {
+            if mover.kind != MoverKind::PushWall {
+                mover.target = 1.0;
+                mover.wait = DOOR_WAIT;
+            }
+        }
+    }
+
+    pub fn activate(&mut self, tag: u32) {
+        if tag == 0 {
+            return;
+        }
+        for mover in self.movers.iter_mut().filter(|m| m.tag == tag) {
+            mover.target = 1.0 - mover.target;
+            mover.wait = DOOR_WAIT;
+        }
+    }
+
+    pub fn faces(&self, game_map: &map::GameMap, player: &player::Player) -> Vec<camera::FaceData> {
+        let mut faces = Vec::new();
+        for mover in self.movers.iter() {
+            let visible = mover
+                .tiles()
+                .iter()
+                .any(|&(i, j)| game_map.floor_visible[i][j] || game_map.wall_visible[i][j]);
+            if !visible || (mover.kind == MoverKind::PushWall && mover.target == 0.0) {
+                continue;
+            }
+            let (corners, z) = mover.footprint();
+            let (textures, storeys) = game_map.storey_textures(mover.i, mover.j, mover.texture_bot, mover.texture_top);
+            for k in 0..4 {
+                let (x1, y1) = corners[k];
+                let (x2, y2) = corners[(k + 1) % 4];
+                let dist = (0.5 * (x1 + x2) - player.position.x).powi(2)
+                    + (0.5 * (y1 + y2) - player.position.y).powi(2);
+                faces.push(camera::FaceData {
+                    top_right_x: x1,
+                    top_right_y: y1,
+                    bottom_right_x: x1,
+                    bottom_right_y: y1,
+                    bottom_left_x: x2,
+                    bottom_left_y: y2,
+                    top_left_x: x2,
+                    top_left_y: y2,
+                    z,
+                    bottom: 0.0,
+                    top: game_map.height,
+                    floor_z: [0.0; 4],
+                    is_wall: true,
+                    textures,
+                    storeys,
+                    dist,
+                });
+            }
+        }
+        faces
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_move_only_their_tag() {
+        let mut movers = Movers::new();
+        movers.movers.push(Mover::new(MoverKind::Door(DoorStyle::Slide), 10, 10, 48));
+        let mut gate = Mover::new(MoverKind::Gate, 12, 10, 60);
+        gate.tag = 1;
+        movers.movers.push(gate);
+
+        movers.activate(0);
+        assert!(movers.movers.iter().all(|m| m.target == 0.0));
+        movers.activate(1);
+        assert_eq!(movers.movers[0].target, 0.0);
+        assert_eq!(movers.movers[1].target, 1.0);
+    }
+
+    #[test]
+    fn doors_reach_the_top_storey() {
+        let settings = settings::Settings::init();
+        let mut game_map = map::GameMap::empty();
+        let mut layer = vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE];
+        layer[10][10] = 7;
+        game_map.wall_layers.push(layer);
+        game_map.wall_layers.push(vec![vec![255; settings::MAPSIZE]; settings::MAPSIZE]);
+        game_map.height = map::CEILING + 2.0;
+        game_map.floor_visible[10][10] = true;
+        let mut player = player::Player::new(&settings);
+        player.place(12.5, 10.5, 0.0, &settings);
+
+        let mut movers = Movers::new();
+        let mut door = Mover::new(MoverKind::Door(DoorStyle::Slide), 10, 10, 48);
+        door.texture_top = 49;
+        movers.movers.push(door);
+        let faces = movers.faces(&game_map, &player);
+        assert_eq!(faces.len(), 4);
+        for face in faces {
+            assert_eq!(face.top, game_map.height);
+            assert_eq!(face.textures[..face.storeys], [48, 49, 7, 7]);
+        }
+    }
+
+    #[test]
+    fn doors_stay_open_while_someone_is_in_the_doorway() {
+        let settings = settings::Settings::init();
+        let mut game_map = map::GameMap::empty();
+        let mut player = player::Player::new(&settings);
+        let mut entity_list = entities::EntityList::new();
+        let mut movers = Movers::new();
+        let mut door = Mover::new(MoverKind::Door(DoorStyle::Slide), 10, 10, 48);
+        door.open = 1.0;
+        door.target = 1.0;
+        movers.movers.push(door);
+        let wait = |movers: &mut Movers, game_map: &mut map::GameMap, player: &player::Player, entity_list: &entities::EntityList| {
+            for _ in 0..600 {
+                movers.update(game_map, player, entity_list, 1.0 / 60.0);
+            }
+            movers.movers[0].target
+        };
+
+        // the player's centre is in the next tile, the body reaches in
+        player.place(9.6, 10.5, 0.0, &settings);
+        assert_eq!(wait(&mut movers, &mut game_map, &player, &entity_list), 1.0);
+
+        player.place(5.5, 5.5, 0.0, &settings);
+        entity_list.spawn(entities::EntityKind::Enemy, 10.5, 10.5);
+        assert_eq!(wait(&mut movers, &mut game_map, &player, &entity_list), 1.0);
+
+        entity_list.entities[0].alive = false;
+        assert_eq!(wait(&mut movers, &mut game_map, &player, &entity_list), 0.0);
+    }
+}
@@ -0,0 +1,299 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Node {
+    cost: f32,
+    i: usize,
+    j: usize,
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed so that BinaryHeap pops the cheapest node first
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+const NEIGHBOURS: [(i32, i32, f32); 8] = [
+    (1, 0, 1.0),
+    (-1, 0, 1.0),
+    (0, 1, 1.0),
+    (0, -1, 1.0),
+    (1, 1, std::f32::consts::SQRT_2),
+    (1, -1, std::f32::consts::SQRT_2),
+    (-1, 1, std::f32::consts::SQRT_2),
+    (-1, -1, std::f32::consts::SQRT_2),
+];
+
+fn octile(i1: usize, j1: usize, i2: usize, j2: usize) -> f32 {
+    let di = (i1 as f32 - i2 as f32).abs();
+    let dj = (j1 as f32 - j2 as f32).abs();
+    di.max(dj) + (std::f32::consts::SQRT_2 - 1.0) * di.min(dj)
+}
+
+// A* on a size x size grid with 8-way moves. `crossable` tells whether the
+// edge between two side by side cells is open. Diagonal moves may not cut
+// wall corners or closed edges. The returned path excludes the start and
+// ends at the goal.
+pub fn find_path<F, G>(
+    start: (usize, usize),
+    goal: (usize, usize),
+    size: usize,
+    max_nodes: usize,
+    passable: F,
+    crossable: G,
+) -> Option<Vec<(usize, usize)>>
+where
+    F: Fn(usize, usize) -> bool,
+    G: Fn((usize, usize), (usize, usize)) -> bool,
+{
+    if start.0 >= size || start.1 >= size || goal.0 >= size || goal.1 >= size {
+        return None;
+    }
+    if !passable(goal.0, goal.1) {
+        return None;
+    }
+    if start == goal {
+        return Some(Vec::new());
+    }
+
+    let mut cost = vec![f32::INFINITY; size * size];
+    let mut came_from = vec![usize::MAX; size * size];
+    let mut closed = vec![false; size * size];
+    let mut open = BinaryHeap::new();
+
+    let idx = |i: usize, j: usize| i * size + j;
+
+    cost[idx(start.0, start.1)] = 0.0;
+    open.push(Node {
+        cost: octile(start.0, start.1, goal.0, goal.1),
+        i: start.0,
+        j: start.1,
+    });
+
+    let mut expanded = 0;
+
+    while let Some(node) = open.pop() {
+        let k = idx(node.i, node.j);
+        if closed[k] {
+            continue;
+        }
+        closed[k] = true;
+
+        if (node.i, node.j) == goal {
+            let mut path = Vec::new();
+            let mut k = k;
+            while k != idx(start.0, start.1) {
+                path.push((k / size, k % size));
+                k = came_from[k];
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        expanded += 1;
+        if expanded > max_nodes {
+            return None;
+        }
+
+        for &(di, dj, step) in NEIGHBOURS.iter() {
+            let ni = node.i as i32 + di;
+            let nj = node.j as i32 + dj;
+            if ni < 0 || nj < 0 || ni >= size as i32 || nj >= size as i32 {
+                continue;
+            }
+            let (ni, nj) = (ni as usize, nj as usize);
+            if !passable(ni, nj) {
+                continue;
+            }
+            let here = (node.i, node.j);
+            if di == 0 || dj == 0 {
+                if !crossable(here, (ni, nj)) {
+                    continue;
+                }
+            } else if !passable(ni, node.j)
+                || !passable(node.i, nj)
+                || !crossable(here, (ni, node.j))
+                || !crossable((ni, node.j), (ni, nj))
+                || !crossable(here, (node.i, nj))
+                || !crossable((node.i, nj), (ni, nj))
+            {
+                continue;
+            }
+            let nk = idx(ni, nj);
+            let new_cost = cost[k] + step;
+            if new_cost < cost[nk] {
+                cost[nk] = new_cost;
+                came_from[nk] = k;
+                open.push(Node {
+                    cost: new_cost + octile(ni, nj, goal.0, goal.1),
+                    i: ni,
+                    j: nj,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+// Walks every grid cell crossed by the segment (x0,y0)-(x1,y1) and reports
+// whether none of them is solid.
+pub fn line_of_sight<F>(x0: f32, y0: f32, x1: f32, y1: f32, solid: F) -> bool
+where
+    F: Fn(i32, i32) -> bool,
+{
+    let mut i = x0.floor() as i32;
+    let mut j = y0.floor() as i32;
+    let i_end = x1.floor() as i32;
+    let j_end = y1.floor() as i32;
+
+    let dx = x1 - x0;
+    let dy = y1 - y0;
+
+    let step_i = if dx > 0.0 { 1 } else { -1 };
+    let step_j = if dy > 0.0 { 1 } else { -1 };
+
+    let t_delta_x = if dx != 0.0 { (1.0 / dx).abs() } else { f32::INFINITY };
+    let t_delta_y = if dy != 0.0 { (1.0 / dy).abs() } else { f32::INFINITY };
+
+    let mut t_max_x = if dx > 0.0 {
+        (i as f32 + 1.0 - x0) * t_delta_x
+    } else if dx < 0.0 {
+        (x0 - i as f32) * t_delta_x
+    } else {
+        f32::INFINITY
+    };
+    let mut t_max_y = if dy > 0.0 {
+        (j as f32 + 1.0 - y0) * t_delta_y
+    } else if dy < 0.0 {
+        (y0 - j as f32) * t_delta_y
+    } else {
+        f32::INFINITY
+    };
+
+    loop {
+        if solid(i, j) {
+            return false;
+        }
+        if i == i_end && j == j_end {
+            return true;
+        }
+        if t_max_x > 1.0 && t_max_y > 1.0 {
+            return true;
+        }
+        if t_max_x < t_max_y {
+            t_max_x += t_delta_x;
+            i += step_i;
+        } else {
+            t_max_y += t_delta_y;
+            j += step_j;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Vec<Vec<bool>> {
+        // rows are given top to bottom, indexed as [i][j] with j pointing up
+        let size = rows.len();
+        let mut cells = vec![vec![true; size]; size];
+        for (r, row) in rows.iter().enumerate() {
+            for (i, c) in row.chars().enumerate() {
+                cells[i][size - r - 1] = c != '#';
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn straight_path_on_open_grid() {
+        let cells = grid(&[".....", ".....", ".....", ".....", "....."]);
+        let path = find_path((0, 0), (4, 0), 5, 1000, |i, j| cells[i][j], |_, _| true).unwrap();
+        assert_eq!(path, vec![(1, 0), (2, 0), (3, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn start_equals_goal() {
+        let cells = grid(&["..", ".."]);
+        let path = find_path((1, 1), (1, 1), 2, 1000, |i, j| cells[i][j], |_, _| true).unwrap();
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn path_goes_around_wall() {
+        let cells = grid(&[
+            ".....",
+            ".###.",
+            ".#...",
+            ".#.#.",
+            "...#.",
+        ]);
+        let path = find_path((0, 0), (2, 1), 5, 1000, |i, j| cells[i][j], |_, _| true).unwrap();
+        assert_eq!(path.last(), Some(&(2, 1)));
+        for &(i, j) in path.iter() {
+            assert!(cells[i][j]);
+        }
+        for w in path.windows(2) {
+            let di = (w[0].0 as i32 - w[1].0 as i32).abs();
+            let dj = (w[0].1 as i32 - w[1].1 as i32).abs();
+            assert!(di <= 1 && dj <= 1);
+        }
+    }
+
+    #[test]
+    fn no_corner_cutting() {
+        let cells = grid(&["#.", ".#"]);
+        assert!(find_path((0, 0), (1, 1), 2, 1000, |i, j| cells[i][j], |_, _| true).is_none());
+        let cells = grid(&["#.", ".."]);
+        let path = find_path((0, 0), (1, 1), 2, 1000, |i, j| cells[i][j], |_, _| true);
+        assert_eq!(path, Some(vec![(1, 0), (1, 1)]));
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        let cells = grid(&["..#..", "..#..", "..#..", "..#..", "..#.."]);
+        assert!(find_path((0, 0), (4, 4), 5, 1000, |i, j| cells[i][j], |_, _| true).is_none());
+        assert!(find_path((0, 0), (2, 2), 5, 1000, |i, j| cells[i][j], |_, _| true).is_none());
+    }
+
+    #[test]
+    fn node_limit_stops_search() {
+        let cells = vec![vec![true; 64]; 64];
+        assert!(find_path((0, 0), (63, 63), 64, 10, |i, j| cells[i][j], |_, _| true).is_none());
+        assert!(find_path((0, 0), (63, 63), 64, 10000, |i, j| cells[i][j], |_, _| true).is_some());
+    }
+
+    #[test]
+    fn sight_blocked_by_wall() {
+        let cells = grid(&[".....", ".....", "..#..", ".....", "....."]);
+        let solid = |i: i32, j: i32| i < 0 || j < 0 || i >= 5 || j >= 5 || !cells[i as usize][j as usize];
+        assert!(!line_of_sight(0.5, 2.5, 4.5, 2.5, solid));
+        assert!(line_of_sight(0.5, 0.5, 4.5, 0.5, solid));
+        assert!(line_of_sight(0.5, 0.5, 0.5, 4.5, solid));
+        assert!(!line_of_sight(0.5, 0.5, 4.5, 4.5, solid));
+        assert!(line_of_sight(3.5, 0.5, 4.5, 4.5, solid));
+    }
+
+    #[test]
+    fn path_goes_around_closed_edge() {
+        let cells = vec![vec![true; 3]; 3];
+        // the edges between column 0 and 1 are closed but in the last row
+        let crossable = |a: (usize, usize), b: (usize, usize)| a.0.min(b.0) != 0 || a.0 == b.0 || a.1 == 2;
+        let path = find_path((0, 0), (1, 0), 3, 1000, |i, j| cells[i][j], crossable).unwrap();
+        assert_eq!(path, vec![(0, 1), (0, 2), (1, 2), (1, 1), (1, 0)]);
+        let crossable = |a: (usize, usize), b: (usize, usize)| a.0.min(b.0) != 0 || a.0 == b.0;
+        assert!(find_path((0, 0), (2, 2), 3, 1000, |i, j| cells[i][j], crossable).is_none());
+    }
+}
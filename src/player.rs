@@ -6,6 +6,7 @@ pub struct PlayerPos {
     pub x: f32,
     pub y: f32,
     pub z: f32,
+    pub eye: f32,
     pub a: f32,
     pub b: f32,
     pub ax: f32,
@@ -14,17 +15,6 @@ pub struct PlayerPos {
     pub bz: f32,
 }
 
-pub struct CollisionState {
-    pub cxp: bool,
-    pub cyp: bool,
-    pub cxm: bool,
-    pub cym: bool,
-    pub cxl: bool,
-    pub cyl: bool,
-    pub cxr: bool,
-    pub cyr: bool,
-}
-
 pub struct Direction {
     pub f: bool,  // forward
     pub b: bool,  // backward
@@ -39,7 +29,7 @@ pub struct Direction {
 }
 
 impl Direction {
-    pub fn _erase(&mut self) {
+    pub fn erase(&mut self) {
         self.f = false;
         self.b = false;
         self.l = false;
@@ -53,10 +43,67 @@ impl Direction {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DamageSource {
+    Enemy,
+    Hazard,
+    Fall,
+}
+
+pub const MAX_HEALTH: f32 = 100.0;
+pub const MAX_ARMOUR: f32 = 100.0;
+pub const HAZARD_DAMAGE: f32 = 20.0;
+// Drops are measured from the floor the player left, so jumping never
+// hurts and dropping down one storey (1.0) does
+pub const FALL_SAFE_HEIGHT: f32 = 0.75;
+pub const FALL_DAMAGE: f32 = 40.0;
+
+// Vertical motion in tiles and seconds. The eye sits `eye` above the feet
+// and the head HEAD_ROOM above the eye. Ledges up to STEP_HEIGHT are
+// stepped up onto.
+pub const GRAVITY: f32 = 16.0;
+pub const JUMP_SPEED: f32 = 5.6;
+pub const JUMP_CUT_SPEED: f32 = 2.0;
+pub const HEAD_ROOM: f32 = 0.1;
+pub const CROUCH_HEIGHT: f32 = 0.25;
+pub const CROUCH_SPEED: f32 = 2.0;
+pub const CROUCH_WALK: f32 = 0.5;
+pub const STEP_HEIGHT: f32 = 0.3;
+
+// Below STOP_SPEED ground friction acts as if at STOP_SPEED so the player
+// comes to rest instead of creeping. An exhausted sprint comes back once
+// stamina refills to STAMINA_RECOVER of the maximum.
+pub const STOP_SPEED: f32 = 2.0;
+pub const STAMINA_RECOVER: f32 = 0.3;
+
+pub struct StatusState {
+    pub health: f32,
+    pub armour: f32,
+    pub alive: bool,
+    pub hurt_time: f32,
+    pub fall_start: f32,
+    pub last_damage: Option<DamageSource>,
+    pub stamina: f32,
+    pub exhausted: bool,
+    pub god: bool,
+}
+
+pub struct Checkpoint {
+    pub x: f32,
+    pub y: f32,
+    pub a: f32,
+}
+
 pub struct MovementState {
     pub moving: bool,
     pub mouse: bool,
     pub dir: Direction,
+    pub vx: f32,
+    pub vy: f32,
+    pub vz: f32,
+    pub grounded: bool,
+    pub sprint: bool,
+    pub noclip: bool,
 }
 
 impl MovementState {
@@ -76,8 +123,9 @@ impl MovementState {
 
 pub struct Player {
     pub position: PlayerPos,
-    pub collision: CollisionState,
     pub movement: MovementState,
+    pub status: StatusState,
+    pub checkpoint: Checkpoint,
     pub radius: f32,
 }
 
@@ -90,6 +138,7 @@ impl Player {
                 x: settings.player_x0,
                 y: settings.player_y0,
                 z: settings.player_height,
+                eye: settings.player_height,
                 a,
                 b,
                 ax: a.cos(),
@@ -97,16 +146,6 @@ impl Player {
                 bxy: b.cos(),
                 bz: b.sin(),
             },
-            collision: CollisionState {
-                cxp: false,
-                cyp: false,
-                cxm: false,
-                cym: false,
-                cxl: false,
-                cyl: false,
-                cxr: false,
-                cyr: false,
-            },
             movement: MovementState {
                 moving: false,
                 mouse: false,
@@ -122,81 +161,324 @@ impl Player {
                     ut: false,
                     dt: false,
                 },
+                vx: 0.0,
+                vy: 0.0,
+                vz: 0.0,
+                grounded: true,
+                sprint: false,
+                noclip: false,
+            },
+            status: StatusState {
+                health: MAX_HEALTH,
+                armour: 0.0,
+                alive: true,
+                hurt_time: 0.0,
+                fall_start: 0.0,
+                last_damage: None,
+                stamina: settings.stamina_max,
+                exhausted: false,
+                god: false,
+            },
+            checkpoint: Checkpoint {
+                x: settings.player_x0,
+                y: settings.player_y0,
+                a,
             },
             radius: settings.player_radius,
         }
     }
 
-    fn coll_check(&mut self, game_map: &map::GameMap) {
-        let i = (self.position.x).floor() as usize;
-        let j = (self.position.y).floor() as usize;
-        let ip = (self.position.x + self.radius * self.position.ax).floor() as usize;
-        let jp = (self.position.y + self.radius * self.position.ay).floor() as usize;
-        let im = (self.position.x - self.radius * self.position.ax).floor() as usize;
-        let jm = (self.position.y - self.radius * self.position.ay).floor() as usize;
-        let il = (self.position.x - self.radius * self.position.ay).floor() as usize;
-        let jl = (self.position.y + self.radius * self.position.ax).floor() as usize;
-        let ir = (self.position.x + self.radius * self.position.ay).floor() as usize;
-        let jr = (self.position.y - self.radius * self.position.ax).floor() as usize;
-
-        self.collision.cxp = false;
-        self.collision.cxm = false;
-        self.collision.cyp = false;
-        self.collision.cym = false;
-        self.collision.cxl = false;
-        self.collision.cxr = false;
-        self.collision.cyl = false;
-        self.collision.cyr = false;
-
-        if game_map.wall_bot_array[ip][jp] < 255
-            || game_map.wall_bot_array[im][jm] < 255
-            || game_map.wall_bot_array[il][jl] < 255
-            || game_map.wall_bot_array[ir][jr] < 255
-        {
-            self.collision.cxp = true;
-            self.collision.cxm = true;
-            self.collision.cyp = true;
-            self.collision.cym = true;
-            self.collision.cxl = true;
-            self.collision.cxr = true;
-            self.collision.cyl = true;
-            self.collision.cyr = true;
+    // Armour soaks up half of the incoming damage while it lasts
+    pub fn damage(&mut self, amount: f32, source: DamageSource) {
+        if !self.status.alive || amount <= 0.0 || self.status.god {
+            return;
+        }
+        let absorbed = (0.5 * amount).min(self.status.armour);
+        self.status.armour -= absorbed;
+        self.status.health -= amount - absorbed;
+        self.status.hurt_time = 0.3;
+        self.status.last_damage = Some(source);
+        if self.status.health <= 0.0 {
+            self.status.health = 0.0;
+            self.status.alive = false;
+            self.movement.dir.erase();
+            self.position.z += 0.15 - self.position.eye;
+        }
+    }
+
+    pub fn tile_effects(&mut self, game_map: &map::GameMap, settings: &settings::Settings) {
+        self.status.hurt_time = (self.status.hurt_time - settings.delta_time).max(0.0);
+        if !self.status.alive {
+            return;
+        }
+        let special = game_map.special(self.position.x, self.position.y);
+        if special == map::SPECIAL_HAZARD && self.movement.grounded {
+            self.damage(HAZARD_DAMAGE * settings.delta_time, DamageSource::Hazard);
+        }
+        if special == map::SPECIAL_CHECKPOINT {
+            self.checkpoint = Checkpoint {
+                x: self.position.x.floor() + 0.5,
+                y: self.position.y.floor() + 0.5,
+                a: self.position.a,
+            };
+        }
+    }
+
+    pub fn respawn(&mut self, settings: &settings::Settings) {
+        self.place(self.checkpoint.x, self.checkpoint.y, self.checkpoint.a, settings);
+        self.status.health = MAX_HEALTH;
+        self.status.armour = 0.0;
+        self.status.alive = true;
+        self.status.hurt_time = 0.0;
+        self.status.last_damage = None;
+        self.status.stamina = settings.stamina_max;
+        self.status.exhausted = false;
+    }
+
+    pub fn place(&mut self, x: f32, y: f32, a: f32, settings: &settings::Settings) {
+        self.position.x = x;
+        self.position.y = y;
+        self.position.eye = settings.player_height;
+        self.position.z = self.position.eye;
+        self.position.a = a;
+        self.position.ax = self.position.a.cos();
+        self.position.ay = self.position.a.sin();
+        self.position.b = settings.player_b0;
+        self.position.bxy = self.position.b.cos();
+        self.position.bz = self.position.b.sin();
+        self.movement.dir.erase();
+        self.movement.sprint = false;
+        self.movement.vx = 0.0;
+        self.movement.vy = 0.0;
+        self.movement.vz = 0.0;
+        self.movement.grounded = true;
+        self.status.fall_start = 0.0;
+    }
+
+    // Walls, ledges higher than a step and ceilings too low for the head
+    // block the way at the point (x,y). Floors are compared where they
+    // meet, so walking along a ramp is never a step.
+    fn blocks(&self, game_map: &map::GameMap, x: f32, y: f32) -> bool {
+        if self.movement.noclip {
+            let size = settings::MAPSIZE as f32;
+            return x < 0.0 || y < 0.0 || x >= size || y >= size;
+        }
+        let (oi, oj) = (self.position.x.floor() as i32, self.position.y.floor() as i32);
+        let own = game_map.floor_plane(oi, oj, x, y);
+        // across a portal the point is looked at on its far side
+        let (di, dj) = (x.floor() as i32 - oi, y.floor() as i32 - oj);
+        match game_map.portal(oi, oj, di, dj) {
+            Some(k) => {
+                let (x, y) = game_map.portals[k].transform.point(x, y);
+                self.blocks_at(game_map, own, x, y)
+            }
+            None => self.blocks_at(game_map, own, x, y),
+        }
+    }
+
+    // `own` is the height of the player's floor where they would step off it
+    fn blocks_at(&self, game_map: &map::GameMap, own: f32, x: f32, y: f32) -> bool {
+        let (i, j) = (x.floor() as i32, y.floor() as i32);
+        if game_map.is_solid(i, j) {
+            return true;
+        }
+        let feet = self.position.z - self.position.eye;
+        let floor = game_map.floor_plane(i, j, x, y);
+        floor > own.max(feet) + STEP_HEIGHT
+            || game_map.ceil_height(i, j) < floor.max(feet) + self.position.eye + HEAD_ROOM
+    }
+
+    // Walking out of tile (oi,oj) through a portal carries the player, their
+    // heading and their momentum over to the far side
+    fn cross_portal(&mut self, game_map: &map::GameMap, oi: i32, oj: i32) {
+        let (di, dj) = (self.position.x.floor() as i32 - oi, self.position.y.floor() as i32 - oj);
+        if let Some(k) = game_map.portal(oi, oj, di, dj) {
+            let transform = game_map.portals[k].transform;
+            (self.position.x, self.position.y) = transform.point(self.position.x, self.position.y);
+            (self.movement.vx, self.movement.vy) = transform.vector(self.movement.vx, self.movement.vy);
+            self.position.a = angle_round(self.position.a + transform.angle());
+            self.position.ax = self.position.a.cos();
+            self.position.ay = self.position.a.sin();
         }
+    }
 
-        if game_map.wall_bot_array[ip][j] == 255 {
-            self.collision.cxp = false;
+    // Thin walls keep the player at arm's length, moving away from one that
+    // is already too close is allowed
+    fn thin_blocks(&self, game_map: &map::GameMap, x: f32, y: f32) -> bool {
+        if self.movement.noclip {
+            return false;
         }
+        let reach = self.position.z - self.position.eye + STEP_HEIGHT;
+        let dist = game_map.thin_wall_dist(x, y, reach);
+        dist < self.radius && dist < game_map.thin_wall_dist(self.position.x, self.position.y, reach)
+    }
 
-        if game_map.wall_bot_array[i][jp] == 255 {
-            self.collision.cyp = false;
+    // Jumping, gravity, landing, steps and crouching
+    fn vertical(&mut self, game_map: &map::GameMap, settings: &settings::Settings) {
+        let dt = settings.delta_time;
+        let ceiling = game_map.ceil_height(self.position.x.floor() as i32, self.position.y.floor() as i32);
+        let ground = game_map.floor_z(self.position.x, self.position.y);
+        let mut feet = self.position.z - self.position.eye;
+
+        // follow steps and ramps, walking off a ledge starts a fall
+        if self.movement.grounded {
+            if feet - ground > STEP_HEIGHT {
+                self.movement.grounded = false;
+            } else {
+                feet = ground;
+            }
         }
 
-        if game_map.wall_bot_array[im][j] == 255 {
-            self.collision.cxm = false;
+        // crouch while the key is held or while there is no room to stand
+        let target = if self.movement.dir.d || feet + settings.player_height + HEAD_ROOM > ceiling {
+            CROUCH_HEIGHT
+        } else {
+            settings.player_height
+        };
+        let step = CROUCH_SPEED * dt;
+        if self.position.eye < target {
+            self.position.eye = (self.position.eye + step).min(target);
+        } else {
+            self.position.eye = (self.position.eye - step).max(target);
         }
 
-        if game_map.wall_bot_array[i][jm] == 255 {
-            self.collision.cym = false;
+        if self.movement.grounded && self.movement.dir.u {
+            self.movement.vz = JUMP_SPEED;
+            self.movement.grounded = false;
         }
 
-        if game_map.wall_bot_array[il][j] == 255 {
-            self.collision.cxl = false;
+        if !self.movement.grounded {
+            // letting go of jump early gives a lower jump
+            if !self.movement.dir.u && self.movement.vz > JUMP_CUT_SPEED {
+                self.movement.vz = JUMP_CUT_SPEED;
+            }
+            self.movement.vz -= GRAVITY * dt;
+            feet += self.movement.vz * dt;
+
+            if feet <= ground {
+                feet = ground;
+                self.movement.vz = 0.0;
+                self.movement.grounded = true;
+                let drop = self.status.fall_start - feet;
+                if drop > FALL_SAFE_HEIGHT {
+                    self.damage((drop - FALL_SAFE_HEIGHT) * FALL_DAMAGE, DamageSource::Fall);
+                }
+            }
         }
 
-        if game_map.wall_bot_array[ir][j] == 255 {
-            self.collision.cxr = false;
+        if self.movement.grounded {
+            self.status.fall_start = feet;
         }
 
-        if game_map.wall_bot_array[i][jl] == 255 {
-            self.collision.cyl = false;
+        // bump the head on low ceilings
+        if feet + self.position.eye + HEAD_ROOM > ceiling {
+            feet = (ceiling - self.position.eye - HEAD_ROOM).max(ground);
+            self.movement.vz = self.movement.vz.min(0.0);
         }
 
-        if game_map.wall_bot_array[i][jr] == 255 {
-            self.collision.cyr = false;
+        if self.status.alive {
+            self.position.z = feet + self.position.eye;
+        }
+    }
+
+    // Velocity based walking. Friction slows the player down on the ground,
+    // the normalised wish direction accelerates towards the target speed and
+    // the move is resolved one axis at a time so walls can be slid along.
+    fn horizontal(&mut self, game_map: &map::GameMap, settings: &settings::Settings) {
+        let dt = settings.delta_time;
+        let (ax, ay) = (self.position.ax, self.position.ay);
+        let dir = &self.movement.dir;
+        let mut wish_x = 0.0;
+        let mut wish_y = 0.0;
+        if dir.f {
+            wish_x += ax;
+            wish_y += ay;
+        }
+        if dir.b {
+            wish_x -= ax;
+            wish_y -= ay;
+        }
+        if dir.l {
+            wish_x -= ay;
+            wish_y += ax;
+        }
+        if dir.r {
+            wish_x += ay;
+            wish_y -= ax;
+        }
+        let wish_len = (wish_x * wish_x + wish_y * wish_y).sqrt();
+        let wishing = wish_len > settings::TOLERANCE;
+        if wishing {
+            wish_x /= wish_len;
+            wish_y /= wish_len;
+        }
+        let crouched = self.position.eye < settings.player_height;
+
+        let sprinting = self.movement.sprint && wishing && self.movement.grounded && !crouched && !self.status.exhausted;
+        if sprinting {
+            self.status.stamina -= dt;
+            if self.status.stamina <= 0.0 {
+                self.status.stamina = 0.0;
+                self.status.exhausted = true;
+            }
+        } else {
+            self.status.stamina = (self.status.stamina + settings.stamina_regen * dt).min(settings.stamina_max);
+            if self.status.exhausted && self.status.stamina >= STAMINA_RECOVER * settings.stamina_max {
+                self.status.exhausted = false;
+            }
+        }
+
+        let speed = (self.movement.vx * self.movement.vx + self.movement.vy * self.movement.vy).sqrt();
+        if self.movement.grounded && speed > 0.0 {
+            let drop = speed.max(STOP_SPEED) * settings.friction * dt;
+            let scale = (speed - drop).max(0.0) / speed;
+            self.movement.vx *= scale;
+            self.movement.vy *= scale;
+        }
+
+        if wishing {
+            let mut target = settings.walk_speed;
+            if crouched {
+                target *= CROUCH_WALK;
+            }
+            if sprinting {
+                target *= settings.sprint_factor;
+            }
+            let mut accel = settings.acceleration * dt;
+            if !self.movement.grounded {
+                accel *= settings.air_control;
+            }
+            let current = self.movement.vx * wish_x + self.movement.vy * wish_y;
+            let add = (target - current).clamp(0.0, accel);
+            self.movement.vx += add * wish_x;
+            self.movement.vy += add * wish_y;
+        }
+
+        let r = self.radius;
+        let (oi, oj) = (self.position.x.floor() as i32, self.position.y.floor() as i32);
+        let x = self.position.x + self.movement.vx * dt;
+        let edge = x + r * self.movement.vx.signum();
+        if self.blocks(game_map, edge, self.position.y) || self.thin_blocks(game_map, x, self.position.y) {
+            self.movement.vx = 0.0;
+        } else {
+            self.position.x = x;
+            self.cross_portal(game_map, oi, oj);
+        }
+        let (oi, oj) = (self.position.x.floor() as i32, self.position.y.floor() as i32);
+        let y = self.position.y + self.movement.vy * dt;
+        let edge = y + r * self.movement.vy.signum();
+        if self.blocks(game_map, self.position.x, edge) || self.thin_blocks(game_map, self.position.x, y) {
+            self.movement.vy = 0.0;
+        } else {
+            self.position.y = y;
+            self.cross_portal(game_map, oi, oj);
         }
     }
 
     pub fn read_key(&mut self, input: &input::InputState) {
+        if !self.status.alive {
+            return;
+        }
         self.movement.dir.f = input.keys.w;
         self.movement.dir.b = input.keys.s;
         self.movement.dir.l = input.keys.a;
@@ -206,9 +488,9 @@ impl Player {
         self.movement.dir.ut = input.keys.up;
         self.movement.dir.dt = input.keys.down;
 
-        if input.keys.space && !self.movement.dir.u && !self.movement.dir.d {
-            self.movement.dir.u = true
-        }
+        self.movement.dir.u = input.keys.space;
+        self.movement.dir.d = input.keys.c;
+        self.movement.sprint = input.keys.shift;
     }
 
     pub fn walk(
@@ -219,7 +501,10 @@ impl Player {
         mouse_dy: f32,
         mouse_moving: bool,
     ) {
-        self.coll_check(game_map);
+        if !self.status.alive {
+            return;
+        }
+
         self.movement.check();
 
         let mut up_movement = mouse_moving;
@@ -237,58 +522,9 @@ impl Player {
             self.movement.mouse = false;
         }
 
-        if self.movement.dir.u {
-            if self.position.z >= 1.5 {
-                self.movement.dir.u = false;
-                self.movement.dir.d = true;
-            } else {
-                self.position.z = self.position.z + 0.2 * settings.player_speed;
-            }
-        }
+        self.vertical(game_map, settings);
 
-        if self.movement.dir.d {
-            if self.position.z <= 0.5 {
-                self.movement.dir.d = false;
-            } else {
-                self.position.z = self.position.z - 0.25 * settings.player_speed;
-            }
-        }
-
-        if self.movement.dir.f {
-            if !self.collision.cxp {
-                self.position.x = self.position.x + settings.player_speed * self.position.ax;
-            }
-            if !self.collision.cyp {
-                self.position.y = self.position.y + settings.player_speed * self.position.ay;
-            }
-        }
-
-        if self.movement.dir.b {
-            if !self.collision.cxm {
-                self.position.x = self.position.x - settings.player_speed * self.position.ax;
-            }
-            if !self.collision.cym {
-                self.position.y = self.position.y - settings.player_speed * self.position.ay;
-            }
-        }
-
-        if self.movement.dir.l {
-            if !self.collision.cxl {
-                self.position.x = self.position.x - settings.player_speed * self.position.ay;
-            }
-            if !self.collision.cyl {
-                self.position.y = self.position.y + settings.player_speed * self.position.ax;
-            }
-        }
-
-        if self.movement.dir.r {
-            if !self.collision.cxr {
-                self.position.x = self.position.x + settings.player_speed * self.position.ay;
-            }
-            if !self.collision.cyr {
-                self.position.y = self.position.y - settings.player_speed * self.position.ax;
-            }
-        }
+        self.horizontal(game_map, settings);
 
         if self.movement.dir.lt {
             self.position.a = angle_round(self.position.a + 0.2 * settings.player_speed);
@@ -342,3 +578,47 @@ pub fn angle_round(angle: f32) -> f32 {
     }
     in_degrees * settings::PI / 180.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(player: &mut Player, game_map: &map::GameMap, settings: &settings::Settings, ticks: usize) {
+        for _ in 0..ticks {
+            player.walk(game_map, settings, 0.0, 0.0, false);
+        }
+    }
+
+    #[test]
+    fn dropping_a_storey_hurts() {
+        let settings = settings::Settings::init();
+        let mut game_map = map::GameMap::empty();
+        // a ledge one storey up, the player walks off its east edge
+        for i in 0..=10 {
+            game_map.floor_height_array[i][10] = 1.0;
+        }
+        let mut player = Player::new(&settings);
+        player.place(10.5, 10.5, 0.0, &settings);
+        player.movement.dir.f = true;
+        step(&mut player, &game_map, &settings, 60);
+        assert!(player.position.x > 11.0);
+        assert!(player.movement.grounded);
+        assert_eq!(player.status.last_damage, Some(DamageSource::Fall));
+        assert!(player.status.health < MAX_HEALTH);
+    }
+
+    #[test]
+    fn jumping_does_not_hurt() {
+        let settings = settings::Settings::init();
+        let game_map = map::GameMap::empty();
+        let mut player = Player::new(&settings);
+        player.place(10.5, 10.5, 0.0, &settings);
+        player.movement.dir.u = true;
+        step(&mut player, &game_map, &settings, 5);
+        player.movement.dir.u = false;
+        assert!(!player.movement.grounded);
+        step(&mut player, &game_map, &settings, 120);
+        assert!(player.movement.grounded);
+        assert_eq!(player.status.health, MAX_HEALTH);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use crate::mesh;
+use crate::richtext::{self, Color};
+use crate::settings;
+use crate::text;
+
+// Frames the graph goes back, one pixel each
+pub const GRAPH_FRAMES: usize = 240;
+const GRAPH_HEIGHT: f32 = 60.0;
+// Frame time at the top of the graph
+const GRAPH_MAX: f32 = 0.05;
+// Weight of the newest sample in the smoothed timings
+const SMOOTHING: f64 = 0.1;
+// Top left corner of the panel, below the debug text
+const LEFT: f32 = 20.0;
+const TOP: f32 = 180.0;
+const PADDING: f32 = 8.0;
+pub const BACKGROUND: Color = (0.06, 0.05, 0.08, 0.75);
+
+// Parts of a frame that are timed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    RayCast,
+    Faces,
+    Sprites,
+    Meshes,
+    Upload,
+    Draw,
+}
+
+const SECTIONS: [(Section, &str); 6] = [
+    (Section::RayCast, "ray_cast"),
+    (Section::Faces, "faces"),
+    (Section::Sprites, "sprites"),
+    (Section::Meshes, "meshes"),
+    (Section::Upload, "upload"),
+    (Section::Draw, "draw"),
+];
+
+// Frame times, CPU time of the stages of a frame and how much it drew,
+// shown as a panel with a graph while `settings.show_profiler` is on
+pub struct Profiler {
+    // seconds, the newest last
+    pub frames: VecDeque<f32>,
+    // smoothed seconds per section
+    pub times: [f64; SECTIONS.len()],
+    // what the last frame drew, with the limit it has to stay under
+    pub counts: Vec<(&'static str, usize, Option<usize>)>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler { frames: VecDeque::new(), times: [0.0; SECTIONS.len()], counts: Vec::new() }
+    }
+
+    // Starts a new frame that took `frame_time` seconds
+    pub fn frame(&mut self, frame_time: f64) {
+        self.frames.push_back(frame_time as f32);
+        if self.frames.len() > GRAPH_FRAMES {
+            self.frames.pop_front();
+        }
+        self.counts.clear();
+    }
+
+    // Time spent in `section` since `start`, from date::now()
+    pub fn record(&mut self, section: Section, start: f64) {
+        let elapsed = miniquad::date::now() - start;
+        let k = SECTIONS.iter().position(|(s, _)| *s == section).unwrap_or(0);
+        self.times[k] += (elapsed - self.times[k]) * SMOOTHING;
+    }
+
+    pub fn count(&mut self, name: &'static str, value: usize, limit: Option<usize>) {
+        self.counts.push((name, value, limit));
+    }
+
+    // The text of the panel, frame times on top of the graph and the rest
+    // below it
+    pub fn overlay(&self, fonts: &text::Fonts) -> text::Overlay {
+        let last = self.frames.back().copied().unwrap_or(0.0);
+        let average = self.frames.iter().sum::<f32>() / self.frames.len().max(1) as f32;
+        let worst = self.frames.iter().copied().fold(0.0, f32::max);
+        let mut lines = vec![format!(
+            "frame {:5.1} ms  avg {:5.1}  max {:5.1}",
+            1000.0 * last,
+            1000.0 * average,
+            1000.0 * worst
+        )];
+        let graph_line = lines.len();
+        for (k, (_, name)) in SECTIONS.iter().enumerate() {
+            lines.push(format!("{:<10}{:7.2} ms", name, 1000.0 * self.times[k]));
+        }
+        for (name, value, limit) in &self.counts {
+            let line = match limit {
+                Some(limit) => format!("{:<10}{:7} / {}", name, value, limit),
+                None => format!("{:<10}{:7}", name, value),
+            };
+            // close to the limit is as bad as over it, the next change may
+            // cross it
+            if limit.is_some_and(|limit| *value * 10 >= limit * 9) {
+                lines.push(format!("[color=red]{}[/color]", line));
+            } else {
+                lines.push(line);
+            }
+        }
+
+        let mut overlay = text::Overlay::new_from(fonts, Vec::new());
+        let mut y = TOP + PADDING;
+        for (k, line) in lines.iter().enumerate() {
+            if k == graph_line {
+                y += GRAPH_HEIGHT + PADDING;
+            }
+            let text_box = richtext::TextBox::new(fonts, line, overlay.font, None, richtext::Align::Left);
+            overlay.push(&text_box, LEFT + PADDING, y);
+            y += text_box.height;
+        }
+        overlay
+    }
+
+    // Background, budget line and one bar per frame, green within the
+    // frame rate the game aims at, then yellow and red
+    pub fn rects(&self, text: &text::Overlay) -> Vec<(mesh::Rect, Color)> {
+        let x0 = LEFT + PADDING;
+        let right = (0..text.lines.len())
+            .map(|s| text.line_x[s] + text.line_width[s])
+            .fold(x0 + GRAPH_FRAMES as f32, f32::max);
+        let bottom = text.line_y.iter().fold(TOP, |y, &line_y| y.max(line_y + text.line_height));
+        let mut rects = vec![((LEFT, TOP, right + PADDING, bottom + PADDING), BACKGROUND)];
+
+        let bottom = TOP + PADDING + text.line_height + GRAPH_HEIGHT;
+        let budget = settings::FT_DESIRED as f32;
+        for (k, &frame_time) in self.frames.iter().enumerate() {
+            let color = if frame_time <= 1.1 * budget {
+                (0.35, 0.75, 0.3, 1.0)
+            } else if frame_time <= 2.0 * budget {
+                (0.95, 0.85, 0.3, 1.0)
+            } else {
+                (0.85, 0.2, 0.2, 1.0)
+            };
+            let h = (frame_time / GRAPH_MAX).min(1.0) * GRAPH_HEIGHT;
+            let x = x0 + k as f32;
+            rects.push(((x, bottom - h, x + 1.0, bottom), color));
+        }
+        let y = bottom - budget / GRAPH_MAX * GRAPH_HEIGHT;
+        rects.push(((x0, y, x0 + GRAPH_FRAMES as f32, y + 1.0), (1.0, 1.0, 1.0, 0.5)));
+        rects
+    }
+}
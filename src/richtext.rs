@@ -0,0 +1,310 @@
+use crate::text;
+
+pub type Color = (f32, f32, f32, f32);
+
+// Names [color=...] knows besides #rrggbb
+const COLORS: [(&str, Color); 9] = [
+    ("white", (1.0, 1.0, 1.0, 1.0)),
+    ("black", (0.0, 0.0, 0.0, 1.0)),
+    ("grey", (0.55, 0.55, 0.55, 1.0)),
+    ("red", (0.85, 0.2, 0.2, 1.0)),
+    ("green", (0.35, 0.75, 0.3, 1.0)),
+    ("blue", (0.3, 0.5, 0.95, 1.0)),
+    ("yellow", (0.95, 0.85, 0.3, 1.0)),
+    ("orange", (0.95, 0.55, 0.15, 1.0)),
+    ("peach", (0.9960784, 0.7607843, 0.5568627, 1.0)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// How a piece of text is drawn, without a colour it takes the one of
+// whatever draws it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub font: usize,
+    pub color: Option<Color>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+// Splits markup into spans of one style. [b]bold[/b], [i]italic[/i] and
+// [color=red]colour[/color] nest, [[ is a bracket and any other bracket
+// is plain text.
+pub fn parse(markup: &str, font: usize) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut text = String::new();
+    let mut bold = 0;
+    let mut italic = 0;
+    let mut colors: Vec<Color> = Vec::new();
+
+    let mut rest = markup;
+    while let Some(c) = rest.chars().next() {
+        let style = Style { font: text::styled(font, bold > 0, italic > 0), color: colors.last().copied() };
+        if let Some(after) = rest.strip_prefix("[[") {
+            text.push('[');
+            rest = after;
+            continue;
+        }
+        if let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            let tag_color = tag.strip_prefix("color=").and_then(color);
+            if matches!(tag, "b" | "/b" | "i" | "/i" | "/color") || tag_color.is_some() {
+                push(&mut spans, std::mem::take(&mut text), style);
+                match tag {
+                    "b" => bold += 1,
+                    "/b" => bold = (bold - 1).max(0),
+                    "i" => italic += 1,
+                    "/i" => italic = (italic - 1).max(0),
+                    "/color" => {
+                        colors.pop();
+                    }
+                    _ => colors.extend(tag_color),
+                }
+                rest = after;
+                continue;
+            }
+        }
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    let style = Style { font: text::styled(font, bold > 0, italic > 0), color: colors.last().copied() };
+    push(&mut spans, text, style);
+    spans
+}
+
+// Text that is shown as it is, whatever brackets it has
+pub fn escape(text: &str) -> String {
+    text.replace('[', "[[")
+}
+
+// The text of markup without its tags
+pub fn plain(markup: &str) -> String {
+    parse(markup, text::CLASSIC).into_iter().map(|span| span.text).collect()
+}
+
+// Appends to the last span when the style is the same
+fn push(spans: &mut Vec<Span>, text: String, style: Style) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(span) if span.style == style => span.text.push_str(&text),
+        _ => spans.push(Span { text, style }),
+    }
+}
+
+// A colour by name or as #rrggbb
+pub fn color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |k: usize| u8::from_str_radix(hex.get(k..k + 2)?, 16).ok().map(|v| v as f32 / 255.0);
+        return Some((channel(0)?, channel(2)?, channel(4)?, 1.0));
+    }
+    COLORS.iter().find(|(n, _)| *n == name).map(|(_, c)| *c)
+}
+
+// A piece of one style placed in a text box, x and y from the top left
+// corner of the box
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub text: String,
+    pub style: Style,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+}
+
+pub struct Line {
+    pub runs: Vec<Run>,
+    pub width: f32,
+    pub height: f32,
+}
+
+// Markup wrapped into lines and aligned within the widest of them
+pub struct TextBox {
+    pub lines: Vec<Line>,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl TextBox {
+    // Words wrap at `max_width`, None never wraps. A newline always breaks
+    // the line and a word longer than a whole line is cut.
+    pub fn new(fonts: &text::Fonts, markup: &str, font: usize, max_width: Option<f32>, align: Align) -> TextBox {
+        let max_width = max_width.unwrap_or(f32::INFINITY);
+        let mut lines: Vec<Vec<Span>> = vec![Vec::new()];
+        let mut width = 0.0;
+        // a wrapped line does not start with the space it wrapped at
+        let mut wrapped = false;
+        for piece in pieces(parse(markup, font)) {
+            match piece {
+                Piece::Newline => {
+                    lines.push(Vec::new());
+                    width = 0.0;
+                    wrapped = false;
+                }
+                Piece::Space(span) => {
+                    if !(wrapped && width == 0.0) {
+                        width += fonts.width(span.style.font, &span.text);
+                        push(lines.last_mut().unwrap(), span.text, span.style);
+                    }
+                }
+                Piece::Word(word) => {
+                    let word_width: f32 = word.iter().map(|span| fonts.width(span.style.font, &span.text)).sum();
+                    if width > 0.0 && width + word_width > max_width {
+                        lines.push(Vec::new());
+                        width = 0.0;
+                        wrapped = true;
+                    }
+                    for span in word {
+                        if word_width <= max_width {
+                            width += fonts.width(span.style.font, &span.text);
+                            push(lines.last_mut().unwrap(), span.text, span.style);
+                            continue;
+                        }
+                        for c in span.text.chars() {
+                            let char_width = fonts.width(span.style.font, &c.to_string());
+                            if width > 0.0 && width + char_width > max_width {
+                                lines.push(Vec::new());
+                                width = 0.0;
+                                wrapped = true;
+                            }
+                            width += char_width;
+                            push(lines.last_mut().unwrap(), c.to_string(), span.style);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut text_box = TextBox { lines: Vec::new(), width: 0.0, height: 0.0 };
+        for mut spans in lines {
+            // trailing spaces take no room
+            while let Some(last) = spans.last_mut() {
+                let trimmed = last.text.trim_end().len();
+                last.text.truncate(trimmed);
+                if !last.text.is_empty() {
+                    break;
+                }
+                spans.pop();
+            }
+            let line_fonts = || spans.iter().map(|span| span.style.font).chain(std::iter::once(font));
+            let height = line_fonts().map(|f| fonts.line_height(f)).fold(0.0, f32::max);
+            let base = line_fonts().map(|f| fonts.font(f).base).fold(0.0, f32::max);
+            let mut x = 0.0;
+            let mut runs = Vec::new();
+            for span in spans {
+                let width = fonts.width(span.style.font, &span.text);
+                // runs of a line share the baseline
+                let y = text_box.height + base - fonts.font(span.style.font).base;
+                runs.push(Run { text: span.text, style: span.style, x, y, width });
+                x += width;
+            }
+            text_box.lines.push(Line { runs, width: x, height });
+            text_box.width = f32::max(text_box.width, x);
+            text_box.height += height;
+        }
+
+        for line in text_box.lines.iter_mut() {
+            let shift = match align {
+                Align::Left => 0.0,
+                Align::Center => ((text_box.width - line.width) * 0.5).round(),
+                Align::Right => text_box.width - line.width,
+            };
+            for run in line.runs.iter_mut() {
+                run.x += shift;
+            }
+        }
+        text_box
+    }
+}
+
+// Size of the box the markup needs, for widgets to size themselves by
+pub fn measure(fonts: &text::Fonts, markup: &str, font: usize, max_width: Option<f32>) -> (f32, f32) {
+    let text_box = TextBox::new(fonts, markup, font, max_width, Align::Left);
+    (text_box.width, text_box.height)
+}
+
+enum Piece {
+    // a word may change style half way
+    Word(Vec<Span>),
+    Space(Span),
+    Newline,
+}
+
+// Words, runs of whitespace and newlines of the spans
+fn pieces(spans: Vec<Span>) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    // whether the last piece is a word the next span may go on with
+    let mut in_word = false;
+    for span in spans {
+        let mut rest = span.text.as_str();
+        while let Some(c) = rest.chars().next() {
+            let kind = |c: char| if c == '\n' { 0 } else if c.is_whitespace() { 1 } else { 2 };
+            let end = if c == '\n' { 1 } else { rest.find(|d: char| kind(d) != kind(c)).unwrap_or(rest.len()) };
+            let text = rest[..end].to_string();
+            rest = &rest[end..];
+            match kind(c) {
+                0 => pieces.push(Piece::Newline),
+                1 => pieces.push(Piece::Space(Span { text, style: span.style })),
+                _ => match pieces.last_mut() {
+                    Some(Piece::Word(word)) if in_word => word.push(Span { text, style: span.style }),
+                    _ => pieces.push(Piece::Word(vec![Span { text, style: span.style }])),
+                },
+            }
+            in_word = kind(c) == 2;
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A font whose every glyph is 10 pixels wide
+    fn fonts() -> text::Fonts {
+        let mut source = String::from("common lineHeight=20 base=16\n");
+        for c in "abc ".chars() {
+            source += &format!("char id={} width=10 height=20 xadvance=10\n", c as u32);
+        }
+        let (font, _) = text::Font::parse(&source);
+        text::Fonts { fonts: vec![font], atlas: image::ImageBuffer::new(1, 1), solid: 0.0 }
+    }
+
+    #[test]
+    fn parses_markup() {
+        let spans = parse("a [b]b[/b] [color=#ff0000]c[/color] [x] [[b]", text::SANS);
+        let texts: Vec<&str> = spans.iter().map(|span| span.text.as_str()).collect();
+        assert_eq!(texts, vec!["a ", "b", " ", "c", " [x] [b]"]);
+        assert_eq!(spans[1].style.font, text::SANS_BOLD);
+        assert_eq!(spans[3].style.color, Some((1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(spans[4].style, spans[0].style);
+    }
+
+    #[test]
+    fn wraps_and_aligns() {
+        let text_box = TextBox::new(&fonts(), "aaa bbb ccc", 0, Some(75.0), Align::Center);
+        let texts: Vec<&str> = text_box.lines.iter().map(|line| line.runs[0].text.as_str()).collect();
+        assert_eq!(texts, vec!["aaa bbb", "ccc"]);
+        assert_eq!(text_box.width, 70.0);
+        assert_eq!(text_box.height, 40.0);
+        assert_eq!((text_box.lines[1].runs[0].x, text_box.lines[1].runs[0].y), (20.0, 20.0));
+    }
+
+    #[test]
+    fn cuts_long_words() {
+        let (width, height) = measure(&fonts(), "aaaaaaaaaa\nb", 0, Some(35.0));
+        assert_eq!((width, height), (30.0, 100.0));
+    }
+}
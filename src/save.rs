@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::entities;
+use crate::inventory;
+use crate::map;
+use crate::player;
+use crate::settings;
+use crate::weapon;
+
+pub const SAVE_PATH: &str = "savegame.txt";
+
+// Save games are plain text, one `key=value` pair per line.
+pub fn save(
+    path: &str,
+    level: usize,
+    player: &player::Player,
+    weapon: &weapon::Weapon,
+    inventory: &inventory::Inventory,
+    entity_list: &entities::EntityList,
+) -> bool {
+    let keys: Vec<&str> = inventory.keys.iter().map(|k| k.name()).collect();
+    let removed: Vec<String> = entity_list.removed.iter().map(|id| id.0.to_string()).collect();
+
+    let lines = [
+        format!("level={}", level),
+        format!("x={}", player.position.x),
+        format!("y={}", player.position.y),
+        format!("a={}", player.position.a),
+        format!("health={}", player.status.health),
+        format!("armour={}", player.status.armour),
+        format!("checkpoint={},{},{}", player.checkpoint.x, player.checkpoint.y, player.checkpoint.a),
+        format!("ammo={}", weapon.ammo),
+        format!("keys={}", keys.join(",")),
+        format!("collectibles={}", inventory.collectibles),
+        format!("kills={}", entity_list.kills),
+        format!("enemies={}", entity_list.enemies),
+        format!("removed={}", removed.join(",")),
+    ];
+
+    fs::write(path, lines.join("\n") + "\n").is_ok()
+}
+
+// Campaign level of a save game, the game loads that level before the rest
+pub fn level(path: &str) -> Option<usize> {
+    let text = fs::read_to_string(path).ok()?;
+    text.lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| k.trim() == "level")
+        .and_then(|(_, v)| v.trim().parse().ok())
+}
+
+pub fn load(
+    path: &str,
+    game_map: &map::GameMap,
+    settings: &settings::Settings,
+    player: &mut player::Player,
+    weapon: &mut weapon::Weapon,
+    inventory: &mut inventory::Inventory,
+    entity_list: &mut entities::EntityList,
+) -> bool {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return false,
+    };
+
+    let values: HashMap<&str, &str> = text
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .collect();
+
+    let float = |key: &str, default: f32| values.get(key).and_then(|v| v.parse().ok()).unwrap_or(default);
+    let list = |key: &str| -> Vec<&str> {
+        values
+            .get(key)
+            .map(|v| v.split(',').filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default()
+    };
+
+    player.place(
+        float("x", settings.player_x0),
+        float("y", settings.player_y0),
+        float("a", settings.player_a0),
+        settings,
+    );
+    player.status.health = float("health", player::MAX_HEALTH);
+    player.status.armour = float("armour", 0.0);
+    player.status.alive = player.status.health > 0.0;
+    player.status.last_damage = None;
+
+    let checkpoint: Vec<f32> = list("checkpoint").iter().filter_map(|v| v.parse().ok()).collect();
+    if let [x, y, a] = checkpoint[..] {
+        player.checkpoint = player::Checkpoint { x, y, a };
+    }
+
+    weapon.ammo = float("ammo", weapon.ammo as f32) as i32;
+
+    // removed entities are dropped straight away, going through despawn
+    // would count the enemies among them as killed once more
+    *entity_list = entities::EntityList::spawn_from_map(game_map);
+    let removed: Vec<entities::EntityId> =
+        list("removed").iter().filter_map(|v| v.parse().ok()).map(entities::EntityId).collect();
+    entity_list.entities.retain(|e| !removed.contains(&e.id));
+    entity_list.removed = removed;
+    entity_list.kills = float("kills", 0.0) as u32;
+    // enemies spawned later are not saved, the total still counts them
+    entity_list.enemies = entity_list.enemies.max(float("enemies", 0.0) as u32);
+
+    inventory.keys = list("keys").iter().filter_map(|k| inventory::KeyColor::from_name(k)).collect();
+    inventory.collectibles = float("collectibles", 0.0) as u32;
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading_keeps_the_kill_count() {
+        let settings = settings::Settings::init();
+        let mut game_map = map::GameMap::empty();
+        game_map.entity_array[10][10] = entities::MAP_ENEMY;
+        game_map.entity_array[20][20] = entities::MAP_ENEMY;
+        let mut player = player::Player::new(&settings);
+        player.place(100.5, 100.5, 0.0, &settings);
+        let mut weapon = weapon::Weapon::init();
+        let mut inventory = inventory::Inventory::new(0);
+
+        // one enemy of the map killed and one more spawned by a trigger
+        let mut entity_list = entities::EntityList::spawn_from_map(&game_map);
+        entity_list.get_mut(entities::EntityId(0)).unwrap().alive = false;
+        entity_list.update(&game_map, &mut player, &settings);
+        entity_list.spawn(entities::EntityKind::Enemy, 30.5, 30.5);
+        assert_eq!((entity_list.kills, entity_list.enemies), (1, 3));
+
+        let path = std::env::temp_dir().join("save_keeps_kill_count.txt");
+        let path = path.to_string_lossy();
+        assert!(save(&path, 0, &player, &weapon, &inventory, &entity_list));
+        let mut loaded = entities::EntityList::new();
+        assert!(load(&path, &game_map, &settings, &mut player, &mut weapon, &mut inventory, &mut loaded));
+        fs::remove_file(path.as_ref()).ok();
+
+        loaded.update(&game_map, &mut player, &settings);
+        assert_eq!((loaded.kills, loaded.enemies), (1, 3));
+        assert_eq!(loaded.count(entities::EntityKind::Enemy), 1);
+    }
+}
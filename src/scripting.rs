@@ -0,0 +1,376 @@
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT, INT};
+
+use crate::entities;
+use crate::inventory;
+use crate::map;
+use crate::player;
+use crate::settings;
+use crate::triggers;
+
+// Keeps runaway scripts from freezing a frame
+const MAX_OPERATIONS: u64 = 200_000;
+
+// Everything a script can reach during a call. The live map and entity
+// list are swapped in for the duration of the call, the player is a
+// snapshot and changes go out as trigger actions.
+pub struct ScriptWorld {
+    pub game_map: map::GameMap,
+    pub entities: entities::EntityList,
+    pub player_x: f32,
+    pub player_y: f32,
+    pub player_a: f32,
+    pub player_health: f32,
+    pub keys: Vec<inventory::KeyColor>,
+    pub actions: Vec<triggers::Action>,
+}
+
+pub struct ScriptEngine {
+    engine: Engine,
+    ast: Option<AST>,
+    scope: Scope<'static>,
+    state: Dynamic,
+    world: Rc<RefCell<ScriptWorld>>,
+    failed: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// Teleports off the map or into a wall are script errors
+fn teleport(world: &mut ScriptWorld, x: FLOAT, y: FLOAT, a: Option<f32>) -> ScriptResult<()> {
+    match world.game_map.unplaceable(x as f32, y as f32) {
+        Some(reason) => Err(format!("can't teleport to ({}, {}), {}", x, y, reason).into()),
+        None => {
+            world.actions.push(triggers::Action::Teleport(x as f32, y as f32, a));
+            Ok(())
+        }
+    }
+}
+
+fn layer<'a>(game_map: &'a mut map::GameMap, name: &str) -> ScriptResult<&'a mut Vec<Vec<u8>>> {
+    match name {
+        "wall_bot" => Ok(&mut game_map.wall_bot_array),
+        "wall_top" => Ok(&mut game_map.wall_top_array),
+        "floor" => Ok(&mut game_map.floor_array),
+        "ceil" => Ok(&mut game_map.ceil_array),
+        "sprite" => Ok(&mut game_map.sprite_array),
+        "special" => Ok(&mut game_map.special_array),
+        // upper wall storeys, wall_2 and up
+        _ => name
+            .strip_prefix("wall_")
+            .and_then(|storey| storey.parse::<usize>().ok())
+            .and_then(|storey| game_map.wall_layers.get_mut(storey.checked_sub(2)?))
+            .ok_or_else(|| format!("unknown tile layer '{}'", name).into()),
+    }
+}
+
+fn tile_index(i: INT, j: INT) -> Option<(usize, usize)> {
+    let size = settings::MAPSIZE as INT;
+    if i < 0 || j < 0 || i >= size || j >= size {
+        None
+    } else {
+        Some((i as usize, j as usize))
+    }
+}
+
+fn entity_kind(name: &str) -> ScriptResult<entities::EntityKind> {
+    entities::EntityKind::from_name(name).ok_or_else(|| format!("unknown entity kind '{}'", name).into())
+}
+
+impl ScriptEngine {
+    pub fn new() -> ScriptEngine {
+        let world = Rc::new(RefCell::new(ScriptWorld {
+            game_map: map::GameMap::empty(),
+            entities: entities::EntityList::new(),
+            player_x: 0.0,
+            player_y: 0.0,
+            player_a: 0.0,
+            player_health: 0.0,
+            keys: Vec::new(),
+            actions: Vec::new(),
+        }));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_string_size(4096);
+        engine.set_max_array_size(4096);
+        engine.set_max_map_size(4096);
+
+        let w = world.clone();
+        engine.on_print(move |text| w.borrow_mut().actions.push(triggers::Action::Message(text.to_string())));
+        engine.on_debug(|_, _, _| {});
+
+        // player
+        let w = world.clone();
+        engine.register_fn("player_x", move || w.borrow().player_x as FLOAT);
+        let w = world.clone();
+        engine.register_fn("player_y", move || w.borrow().player_y as FLOAT);
+        let w = world.clone();
+        engine.register_fn("player_angle", move || w.borrow().player_a as FLOAT);
+        let w = world.clone();
+        engine.register_fn("player_health", move || w.borrow().player_health as FLOAT);
+        let w = world.clone();
+        engine.register_fn("has_key", move |name: &str| {
+            inventory::KeyColor::from_name(name).is_some_and(|key| w.borrow().keys.contains(&key))
+        });
+
+        // tile layers
+        let w = world.clone();
+        engine.register_fn("tile", move |name: &str, i: INT, j: INT| -> ScriptResult<INT> {
+            let mut world = w.borrow_mut();
+            let layer = layer(&mut world.game_map, name)?;
+            Ok(tile_index(i, j).map_or(-1, |(i, j)| layer[i][j] as INT))
+        });
+        let w = world.clone();
+        engine.register_fn("set_tile", move |name: &str, i: INT, j: INT, value: INT| -> ScriptResult<()> {
+            let mut world = w.borrow_mut();
+            let layer = layer(&mut world.game_map, name)?;
+            if let Some((i, j)) = tile_index(i, j) {
+                layer[i][j] = value.clamp(0, 255) as u8;
+            }
+            Ok(())
+        });
+        let w = world.clone();
+        engine.register_fn("is_solid", move |i: INT, j: INT| w.borrow().game_map.is_solid(i as i32, j as i32));
+
+        // entities
+        let w = world.clone();
+        engine.register_fn("count", move |name: &str| -> ScriptResult<INT> {
+            Ok(w.borrow().entities.count(entity_kind(name)?) as INT)
+        });
+        let w = world.clone();
+        engine.register_fn("spawn_entity", move |name: &str, x: FLOAT, y: FLOAT| -> ScriptResult<INT> {
+            let kind = entity_kind(name)?;
+            match w.borrow_mut().entities.spawn(kind, x as f32, y as f32) {
+                Some(id) => Ok(id.0 as INT),
+                None => Err(format!("can't spawn at ({}, {}), outside the map", x, y).into()),
+            }
+        });
+        let w = world.clone();
+        engine.register_fn("despawn_entity", move |id: INT| {
+            w.borrow_mut().entities.despawn(entities::EntityId(id as u32));
+        });
+        let w = world.clone();
+        engine.register_fn("alive", move |id: INT| {
+            w.borrow().entities.get(entities::EntityId(id as u32)).is_some_and(|e| e.alive)
+        });
+
+        // commands, applied by the game after the call
+        let w = world.clone();
+        engine.register_fn("message", move |text: &str| {
+            w.borrow_mut().actions.push(triggers::Action::Message(text.to_string()));
+        });
+        let w = world.clone();
+        engine.register_fn("announce", move |text: &str| {
+            w.borrow_mut().actions.push(triggers::Action::Announce(text.to_string()));
+        });
+        let w = world.clone();
+        engine.register_fn("sound", move |name: &str| {
+            w.borrow_mut().actions.push(triggers::Action::Sound(name.to_string()));
+        });
+        let w = world.clone();
+        engine.register_fn("open", move |i: INT, j: INT| {
+            if let Some((i, j)) = tile_index(i, j) {
+                w.borrow_mut().actions.push(triggers::Action::Open(i, j));
+            }
+        });
+        let w = world.clone();
+        engine.register_fn("teleport", move |x: FLOAT, y: FLOAT, a: FLOAT| -> ScriptResult<()> {
+            teleport(&mut w.borrow_mut(), x, y, Some(a as f32))
+        });
+        let w = world.clone();
+        engine.register_fn("teleport", move |x: FLOAT, y: FLOAT| -> ScriptResult<()> {
+            teleport(&mut w.borrow_mut(), x, y, None)
+        });
+        let w = world.clone();
+        engine.register_fn("end_level", move || {
+            w.borrow_mut().actions.push(triggers::Action::EndLevel);
+        });
+
+        ScriptEngine {
+            engine,
+            ast: None,
+            scope: Scope::new(),
+            state: Dynamic::from_map(Map::new()),
+            world,
+            failed: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    // A missing or broken script leaves the level without one, the error
+    // is kept for the HUD.
+    pub fn load(&mut self, path: &str) {
+        self.ast = None;
+        self.scope = Scope::new();
+        self.state = Dynamic::from_map(Map::new());
+        self.failed.clear();
+
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(_) => {
+                self.errors.push(format!("Script error: can't read {}", path));
+                return;
+            }
+        };
+        match self.engine.compile(source) {
+            Ok(ast) => self.ast = Some(ast),
+            Err(err) => self.errors.push(format!("Script error: {}", err)),
+        }
+    }
+
+    pub fn has_fn(&self, name: &str) -> bool {
+        match self.ast.as_ref() {
+            Some(ast) => ast.iter_functions().any(|f| f.name == name),
+            None => false,
+        }
+    }
+
+    // Runs the top level statements of the script once, then `on_start`
+    pub fn start(
+        &mut self,
+        game_map: &mut map::GameMap,
+        entity_list: &mut entities::EntityList,
+        player: &player::Player,
+        inventory: &inventory::Inventory,
+    ) -> Vec<triggers::Action> {
+        let ast = match self.ast.as_ref() {
+            Some(ast) => ast,
+            None => return Vec::new(),
+        };
+        self.enter(game_map, entity_list, player, inventory);
+        if let Err(err) = self.engine.run_ast_with_scope(&mut self.scope, ast) {
+            self.errors.push(format!("Script error: {}", err));
+        }
+        let mut actions = self.leave(game_map, entity_list);
+        actions.extend(self.call("on_start", Vec::new(), game_map, entity_list, player, inventory));
+        actions
+    }
+
+    // Calls a script function if it exists. Functions see the persistent
+    // level state as `this`. A function that fails once is not called again.
+    pub fn call(
+        &mut self,
+        name: &str,
+        args: Vec<Dynamic>,
+        game_map: &mut map::GameMap,
+        entity_list: &mut entities::EntityList,
+        player: &player::Player,
+        inventory: &inventory::Inventory,
+    ) -> Vec<triggers::Action> {
+        if !self.has_fn(name) || self.failed.iter().any(|f| f == name) {
+            return Vec::new();
+        }
+        let ast = match self.ast.as_ref() {
+            Some(ast) => ast,
+            None => return Vec::new(),
+        };
+
+        self.enter(game_map, entity_list, player, inventory);
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut self.scope, ast, name, args);
+        if let Err(err) = result {
+            self.errors.push(format!("Script error in {}: {}", name, err));
+            self.failed.push(name.to_string());
+        }
+        self.leave(game_map, entity_list)
+    }
+
+    fn enter(
+        &self,
+        game_map: &mut map::GameMap,
+        entity_list: &mut entities::EntityList,
+        player: &player::Player,
+        inventory: &inventory::Inventory,
+    ) {
+        let mut world = self.world.borrow_mut();
+        std::mem::swap(&mut world.game_map, game_map);
+        std::mem::swap(&mut world.entities, entity_list);
+        world.player_x = player.position.x;
+        world.player_y = player.position.y;
+        world.player_a = player.position.a;
+        world.player_health = player.status.health;
+        world.keys = inventory.keys.clone();
+    }
+
+    fn leave(&self, game_map: &mut map::GameMap, entity_list: &mut entities::EntityList) -> Vec<triggers::Action> {
+        let mut world = self.world.borrow_mut();
+        std::mem::swap(&mut world.game_map, game_map);
+        std::mem::swap(&mut world.entities, entity_list);
+        std::mem::take(&mut world.actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An engine with `source` loaded as the level script, run headless on an
+    // empty map
+    fn engine(name: &str, source: &str) -> (ScriptEngine, map::GameMap, entities::EntityList, player::Player) {
+        let path = std::env::temp_dir().join(format!("script_{}.rhai", name));
+        fs::write(&path, source).unwrap();
+        let mut script = ScriptEngine::new();
+        script.load(&path.to_string_lossy());
+        fs::remove_file(&path).ok();
+        let settings = settings::Settings::init();
+        (script, map::GameMap::empty(), entities::EntityList::new(), player::Player::new(&settings))
+    }
+
+    #[test]
+    fn bindings_reach_the_world() {
+        let source = r#"
+            fn on_start() {
+                let id = spawn_entity("barrel", 10.5, 12.5);
+                message("spawned " + id);
+                set_tile("wall_bot", 3, 4, 7);
+                teleport(5.0, 6.0);
+            }
+
+            fn teleport_into_wall() {
+                teleport(3.5, 4.5);
+            }
+        "#;
+        let (mut script, mut game_map, mut entity_list, player) = engine("bindings", source);
+        let actions = script.start(&mut game_map, &mut entity_list, &player, &inventory::Inventory::new(0));
+        assert!(script.errors.is_empty(), "{:?}", script.errors);
+        assert_eq!(actions, vec![
+            triggers::Action::Message("spawned 0".to_string()),
+            triggers::Action::Teleport(5.0, 6.0, None),
+        ]);
+        assert_eq!(entity_list.count(entities::EntityKind::Barrel), 1);
+        assert_eq!(game_map.wall_bot_array[3][4], 7);
+
+        // the wall set above is in the way
+        let inventory = inventory::Inventory::new(0);
+        let actions = script.call("teleport_into_wall", Vec::new(), &mut game_map, &mut entity_list, &player, &inventory);
+        assert!(actions.is_empty());
+        assert_eq!(script.errors.len(), 1);
+        assert!(script.errors[0].contains("inside a wall"), "{}", script.errors[0]);
+    }
+
+    #[test]
+    fn errors_are_kept_and_the_function_is_dropped() {
+        let source = r#"
+            fn spawn_outside() {
+                spawn_entity("enemy", 300.0, 1.0);
+            }
+        "#;
+        let (mut script, mut game_map, mut entity_list, player) = engine("errors", source);
+        let inventory = inventory::Inventory::new(0);
+        script.call("spawn_outside", Vec::new(), &mut game_map, &mut entity_list, &player, &inventory);
+        assert_eq!(script.errors.len(), 1);
+        assert!(script.errors[0].contains("outside the map"), "{}", script.errors[0]);
+        assert!(entity_list.entities.is_empty());
+
+        script.call("spawn_outside", Vec::new(), &mut game_map, &mut entity_list, &player, &inventory);
+        assert_eq!(script.errors.len(), 1);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::lang;
+
 pub const PI: f32 = 3.1415926538;
 pub const MAPSIZE: usize = 256;
 pub const WIDTH0: i32 = 1280;
@@ -15,38 +17,132 @@ pub const MAX_INDICES_OVERLAY: usize = MAX_QUADS_OVERLAY*6;
 pub const MAX_QUADS_GUI: usize = 1000;
 pub const MAX_VERTICES_GUI: usize = MAX_QUADS_GUI*4;
 pub const MAX_INDICES_GUI: usize = MAX_QUADS_GUI*6;
+pub const MAX_QUADS_CONSOLE: usize = 4000;
+pub const MAX_VERTICES_CONSOLE: usize = MAX_QUADS_CONSOLE*4;
+pub const MAX_INDICES_CONSOLE: usize = MAX_QUADS_CONSOLE*6;
+pub const MAX_QUADS_PROFILER: usize = 1500;
+pub const MAX_VERTICES_PROFILER: usize = MAX_QUADS_PROFILER*4;
+pub const MAX_INDICES_PROFILER: usize = MAX_QUADS_PROFILER*6;
+pub const MAX_QUADS_STATUS: usize = 500;
+pub const MAX_VERTICES_STATUS: usize = MAX_QUADS_STATUS*4;
+pub const MAX_INDICES_STATUS: usize = MAX_QUADS_STATUS*6;
+pub const MAX_QUADS_HUD: usize = 100;
+pub const MAX_VERTICES_HUD: usize = MAX_QUADS_HUD*4;
+pub const MAX_INDICES_HUD: usize = MAX_QUADS_HUD*6;
 pub const MAX_VERTICES_MAP: usize = 5000;
 pub const MAX_INDICES_MAP: usize = MAX_VERTICES_MAP*3;
 
-pub struct Settings {
-    pub screen_width: i32,
-    pub screen_height: i32,
-    pub full_screen: bool,
-    pub draw_map: bool,
-    pub draw_menu: bool,
-    pub screen_width_f: f32,
-    pub screen_height_f: f32,
-    pub screen_aspect: f32,
-    pub player_height: f32,
-    pub tile_screen_size: f32,
-    pub map_size_f: f32,
-    pub map_offset_x: f32,
-    pub map_offset_y: f32,
-    pub player_x0: f32,
-    pub player_y0: f32,
-    pub player_a0: f32,
-    pub player_b0: f32,
-    pub fov_xy: f32,
-    pub fov_z: f32,
-    pub delta_time: f32,
-    pub player_speed: f32,
-    pub player_radius: f32,
-    pub draw_max_dist: f32,
-    pub draw_min_dist: usize,
-    pub light_dist: f32,
-    pub draw_rays_num: usize,
-    pub mouse_sensitivity: f32,
-    pub music_playing: bool,
+// Declares the settings together with their console variables, every cvar
+// can be read and written by name as text. Derived values are left out.
+macro_rules! settings {
+    (cvars { $($field:ident: $ty:ty,)* } derived { $($derived:ident: $derived_ty:ty,)* }) => {
+        pub struct Settings {
+            $(pub $field: $ty,)*
+            $(pub $derived: $derived_ty,)*
+        }
+
+        pub const CVARS: &[&str] = &[$(stringify!($field)),*];
+
+        impl Settings {
+            pub fn cvar(&self, name: &str) -> Option<String> {
+                match name {
+                    $(stringify!($field) => Some(self.$field.to_string()),)*
+                    _ => None,
+                }
+            }
+
+            pub fn set_cvar(&mut self, name: &str, value: &str) -> Result<(), String> {
+                check_cvar(name, value)?;
+                match name {
+                    $(stringify!($field) => {
+                        self.$field = value.parse().map_err(|_| format!("{} is not a valid {}", value, stringify!($ty)))?
+                    })*
+                    _ => return Err(format!("Unknown cvar {}", name)),
+                }
+                self.derive();
+                Ok(())
+            }
+        }
+    };
+}
+
+// Cvars the game can't run with outside these limits. The map view has a
+// vertex per ray and a few more in a buffer of MAX_VERTICES_MAP.
+const CVAR_RANGES: [(&str, f32, f32); 8] = [
+    ("draw_rays_num", 1.0, (MAX_VERTICES_MAP - 5) as f32),
+    ("fov_xy", 0.2, 1.4),
+    ("player_height", 0.1, 1.5),
+    ("tile_screen_size", 0.25, 8.0),
+    ("draw_max_dist", 1.0, 2.0 * MAPSIZE as f32),
+    ("player_radius", 0.05, 0.5),
+    ("light_dist", 0.1, 100.0),
+    ("hud_scale", 0.25, 4.0),
+];
+
+// Numbers have to be finite and within the range of their cvar
+fn check_cvar(name: &str, value: &str) -> Result<(), String> {
+    let number: f32 = match value.parse() {
+        Ok(number) => number,
+        Err(_) => return Ok(()),
+    };
+    if !number.is_finite() {
+        return Err(format!("{} is not a finite number", value));
+    }
+    match CVAR_RANGES.iter().find(|(cvar, _, _)| *cvar == name) {
+        Some(&(_, min, max)) if !(min..=max).contains(&number) => {
+            Err(format!("{} must be between {} and {}", name, min, max))
+        }
+        _ => Ok(()),
+    }
+}
+
+settings! {
+    cvars {
+        full_screen: bool,
+        draw_map: bool,
+        draw_menu: bool,
+        player_height: f32,
+        tile_screen_size: f32,
+        map_offset_x: f32,
+        player_x0: f32,
+        player_y0: f32,
+        player_a0: f32,
+        player_b0: f32,
+        fov_xy: f32,
+        player_radius: f32,
+        walk_speed: f32,
+        sprint_factor: f32,
+        acceleration: f32,
+        friction: f32,
+        air_control: f32,
+        stamina_max: f32,
+        stamina_regen: f32,
+        draw_max_dist: f32,
+        draw_min_dist: usize,
+        light_dist: f32,
+        draw_rays_num: usize,
+        mouse_sensitivity: f32,
+        music_playing: bool,
+        hud_show: bool,
+        hud_scale: f32,
+        hud_compass: bool,
+        crosshair: usize,
+        show_profiler: bool,
+        language: String,
+    }
+    // follow the window, the frame time or the cvars above
+    derived {
+        screen_width: i32,
+        screen_height: i32,
+        screen_width_f: f32,
+        screen_height_f: f32,
+        screen_aspect: f32,
+        map_size_f: f32,
+        map_offset_y: f32,
+        fov_z: f32,
+        delta_time: f32,
+        player_speed: f32,
+    }
 }
 
 impl Settings {
@@ -73,6 +169,14 @@ impl Settings {
         let delta_time = 1.0/60.0;
         let player_speed = 12.0*delta_time;
         let player_radius = 0.5;
+        // horizontal movement in tiles and seconds, stamina in seconds of sprint
+        let walk_speed = 12.0;
+        let sprint_factor = 1.5;
+        let acceleration = 240.0;
+        let friction = 8.0;
+        let air_control = 0.3;
+        let stamina_max = 3.0;
+        let stamina_regen = 1.0;
         let draw_max_dist = 100.0;
         let draw_min_dist = 3*3;
         let light_dist = 5.0;
@@ -101,15 +205,34 @@ impl Settings {
             delta_time,
             player_speed,
             player_radius,
+            walk_speed,
+            sprint_factor,
+            acceleration,
+            friction,
+            air_control,
+            stamina_max,
+            stamina_regen,
             draw_max_dist,
             draw_min_dist,
             light_dist,
             draw_rays_num,
             mouse_sensitivity,
             music_playing: true,
+            hud_show: true,
+            hud_scale: 1.0,
+            hud_compass: true,
+            crosshair: 1,
+            show_profiler: false,
+            language: lang::FALLBACK.to_string(),
         }
     }
 
+    // Values that follow from the cvars
+    fn derive(&mut self) {
+        self.fov_z = self.fov_xy / self.screen_aspect;
+        self.map_offset_y = self.screen_height_f - self.tile_screen_size * (MAPSIZE as f32) - 20.0;
+    }
+
     pub fn screen_change(&mut self, screen_width: f32, screen_height: f32) {
         self.screen_width = screen_width as i32;
         self.screen_height = screen_height as i32;
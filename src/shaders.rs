@@ -43,6 +43,10 @@ vec4 col;
 
 void main() {
     col = texture(tex, texcoord);
+    // cut-out textures must not hide what is drawn behind them later
+    if (col.w < 0.1) {
+        discard;
+    }
     FragColor = vec4(col.xyz * cols.xyz, col.w);
 }"#;
 
@@ -50,34 +54,40 @@ pub const VERTEX_OVERLAY: &str = r#"#version 330 core
 in vec3 pos;
 in vec2 uv;
 in float act;
+in vec4 tint;
+
+uniform vec4 fontcolor;
 
 out vec2 texcoord;
+out vec4 cols;
 
 void main() {
     gl_Position = vec4((pos.x-0.5)*2.0, (0.5-pos.y)*2.0, 0.0, 1.0);
     texcoord = uv;
+    cols = tint.w > 0.0 ? tint : fontcolor;
 }"#;
 
 pub const FRAGMENT_OVERLAY: &str = r#"#version 330 core
 in vec2 texcoord;
+in vec4 cols;
 
 out vec4 FragColor;
 
 uniform sampler2D tex;
-uniform vec4 fontcolor;
 
 vec4 col;
 
 void main() {
     col = texture(tex, texcoord);
 
-    FragColor = vec4(fontcolor.xyz,col.w);
+    FragColor = vec4(cols.xyz,col.w*cols.w);
 }"#;
 
 pub const VERTEX_GUI: &str = r#"#version 330 core
 in vec3 pos;
 in vec2 uv;
 in float act;
+in vec4 tint;
 
 uniform vec4 fontcolor;
 uniform vec4 actcolor;
@@ -91,7 +101,7 @@ void main() {
     posxy = vec2((pos.x-0.5)*2.0, (0.5-pos.y)*2.0);
     gl_Position = vec4(posxy + act*vec2(0.005,-0.005), 0.0, 1.0);
     texcoord = uv;
-    cols = actcolor*act + fontcolor*(1.0-act);
+    cols = actcolor*act + (tint.w > 0.0 ? tint : fontcolor)*(1.0-act);
 }"#;
 
 pub const FRAGMENT_GUI: &str = r#"#version 330 core
@@ -152,6 +162,77 @@ void main() {
 }"#;
 
 
+pub const VERTEX_HUD: &str = r#"#version 330 core
+in vec3 pos;
+in vec2 uv;
+in float act;
+
+out vec2 texcoord;
+out float acts;
+
+void main() {
+    gl_Position = vec4((pos.x-0.5)*2.0, (0.5-pos.y)*2.0, 0.0, 1.0);
+    texcoord = uv;
+    acts = act;
+}"#;
+
+pub const FRAGMENT_HUD: &str = r#"#version 330 core
+in vec2 texcoord;
+in float acts;
+
+out vec4 FragColor;
+
+uniform sampler2D tex;
+uniform vec4 actcolor;
+
+vec4 col;
+
+void main() {
+    col = texture(tex, texcoord);
+    FragColor = vec4(col.xyz*(actcolor.xyz*acts+vec3(1.0)*(1.0-acts)), col.w);
+}"#;
+
+pub const VERTEX_STATUS: &str = r#"#version 330 core
+in vec3 pos;
+in vec2 uv;
+in float act;
+in vec4 tint;
+
+uniform vec4 fontcolor;
+
+out vec2 texcoord;
+out vec4 cols;
+out float icon;
+
+void main() {
+    gl_Position = vec4((pos.x-0.5)*2.0, (0.5-pos.y)*2.0, 0.0, 1.0);
+    texcoord = uv;
+    icon = act;
+    cols = tint.w > 0.0 ? tint : (act > 0.5 ? vec4(1.0) : fontcolor);
+}"#;
+
+// Icons keep their colours, glyphs only give their shape
+pub const FRAGMENT_STATUS: &str = r#"#version 330 core
+in vec2 texcoord;
+in vec4 cols;
+in float icon;
+
+out vec4 FragColor;
+
+uniform sampler2D tex;
+uniform sampler2D font;
+
+vec4 col;
+
+void main() {
+    if (icon > 0.5) {
+        FragColor = texture(tex, texcoord) * cols;
+    } else {
+        col = texture(font, texcoord);
+        FragColor = vec4(cols.xyz, col.w*cols.w);
+    }
+}"#;
+
 pub const VERTEX_SCREEN: &str = r#"#version 330 core
 in vec3 pos;
 in vec2 uv;
@@ -226,6 +307,28 @@ pub fn meta_map() -> ShaderMeta {
     }
 }
 
+pub fn meta_hud() -> ShaderMeta {
+    ShaderMeta {
+        images: vec!["tex".to_string()],
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("actcolor", UniformType::Float4),
+            ],
+        },
+    }
+}
+
+pub fn meta_status() -> ShaderMeta {
+    ShaderMeta {
+        images: vec!["tex".to_string(), "font".to_string()],
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("fontcolor", UniformType::Float4),
+            ],
+        },
+    }
+}
+
 pub fn meta_screen() -> ShaderMeta {
     ShaderMeta {
         images: vec!["tex".to_string()],
@@ -262,6 +365,16 @@ pub struct UniformsMap {
     pub cent: (f32, f32, f32, f32)
 }
 
+#[repr(C)]
+pub struct UniformsHUD {
+    pub actcolor: (f32, f32, f32, f32),
+}
+
+#[repr(C)]
+pub struct UniformsStatus {
+    pub fontcolor: (f32, f32, f32, f32),
+}
+
 #[repr(C)]
 pub struct UniformsScreen {
 }
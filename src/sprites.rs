@@ -3,6 +3,7 @@ use std::cmp::Ordering;
 use crate::settings;
 use crate::map;
 use crate::player;
+use crate::entities;
 
 #[derive(Debug, Clone)]
 pub struct Sprite {
@@ -23,6 +24,7 @@ pub struct Sprite {
     pub bottom_left_z: f32,
     pub texture: u8,
     pub dist: f32,
+    pub light: bool,
 }
 
 impl Sprite {
@@ -35,31 +37,73 @@ impl Sprite {
             let xi = i as f32;
             let yj = j as f32;
             let dist = (xi+2.0/4.0-xp).powi(2)+(yj+2.0/4.0-yp).powi(2);
-            let offset_z = -0.1;
+            // hanging from the ceiling
+            let z = game_map.ceil_height_array[i][j] - 0.1;
             let sprite = Sprite {
                 x: xi + 0.5,
                 y: yj + 0.5,
-                z: 2.0 + offset_z,
+                z,
                 top_right_x: xi + 1.0, 
                 top_right_y: yj + 1.0, 
-                top_right_z: 2.0 + offset_z, 
+                top_right_z: z, 
                 bottom_right_x: xi + 1.0, 
                 bottom_right_y: yj, 
-                bottom_right_z: 2.0 + offset_z, 
+                bottom_right_z: z, 
                 bottom_left_x: xi, 
                 bottom_left_y: yj, 
-                bottom_left_z: 2.0 + offset_z, 
+                bottom_left_z: z, 
                 top_left_x: xi, 
                 top_left_y: yj + 1.0, 
-                top_left_z: 2.0 + offset_z, 
+                top_left_z: z, 
                 texture, 
                 dist, 
+                light: true,
             };
             sprites.push(sprite);
             res = 1;
         }
         res
     }
+
+    fn add_entity_sprite(entity: &entities::Entity, sprites: &mut Vec<Sprite>, player: &player::Player) -> usize {
+        let info = match entity.sprite {
+            Some(info) => info,
+            None => return 0,
+        };
+        let xp = player.position.x;
+        let yp = player.position.y;
+        let x = entity.position.x;
+        let y = entity.position.y;
+        let z = entity.position.z;
+        let dist = (x-xp).powi(2)+(y-yp).powi(2);
+
+        // billboard faces the camera plane
+        let rx = 0.5 * info.width * player.position.ay;
+        let ry = -0.5 * info.width * player.position.ax;
+
+        let sprite = Sprite {
+            x,
+            y,
+            z,
+            top_right_x: x + rx,
+            top_right_y: y + ry,
+            top_right_z: z + info.height,
+            bottom_right_x: x + rx,
+            bottom_right_y: y + ry,
+            bottom_right_z: z,
+            bottom_left_x: x - rx,
+            bottom_left_y: y - ry,
+            bottom_left_z: z,
+            top_left_x: x - rx,
+            top_left_y: y - ry,
+            top_left_z: z + info.height,
+            texture: info.texture,
+            dist,
+            light: false,
+        };
+        sprites.push(sprite);
+        1
+    }
 }
 
 pub struct SpriteBuffer {
@@ -68,7 +112,7 @@ pub struct SpriteBuffer {
 }
 
 impl SpriteBuffer {
-    pub fn generate(game_map: &map::GameMap, player: &player::Player, settings: &settings::Settings) -> SpriteBuffer {
+    pub fn generate(game_map: &map::GameMap, entity_list: &entities::EntityList, player: &player::Player, settings: &settings::Settings) -> SpriteBuffer {
         let mut sprites: Vec<Sprite> = Vec::new();
         let mut len = 0;
         let xp = player.position.x;
@@ -90,6 +134,20 @@ impl SpriteBuffer {
             }
         }
 
+        let max_dist = settings.draw_max_dist.powi(2);
+        for entity in entity_list.entities.iter() {
+            let (i, j) = (entity.position.x.floor() as i32, entity.position.y.floor() as i32);
+            let size = settings::MAPSIZE as i32;
+            if i < 0 || j < 0 || i >= size || j >= size {
+                continue;
+            }
+            let (i, j) = (i as usize, j as usize);
+            let dist = (entity.position.x-xp).powi(2)+(entity.position.y-yp).powi(2);
+            if dist < max_dist && (game_map.floor_visible[i][j] || dist < settings.draw_min_dist as f32) {
+                len += Sprite::add_entity_sprite(entity, &mut sprites, player);
+            }
+        }
+
         sprites.sort_by(cmp_dist);
 
         SpriteBuffer {
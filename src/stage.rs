@@ -1,18 +1,37 @@
 use glam::{vec3, Mat4};
 use image::{self, EncodableLayout, ImageBuffer, Rgba};
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{self, Receiver, Sender};
 use miniquad::*;
 
 use crate::assets;
+use crate::audio;
 use crate::camera;
+use crate::entities;
+use crate::inventory;
+use crate::lang;
+use crate::level;
 use crate::map;
 use crate::mesh;
 use crate::player;
+use crate::save;
 use crate::settings;
 use crate::shaders;
 use crate::sprites;
 use crate::text;
-use crate::input::{TimeState, InputState};
+use crate::triggers;
+use crate::scripting;
+use crate::campaign;
+use crate::movers;
+use crate::weapon;
+use crate::gamepad;
+use crate::hud;
+use crate::profiler::{self, Section};
+use crate::console;
+use crate::messages::{self, Priority};
+use crate::richtext;
+use crate::input::{TimeState, InputState, Nav};
+use crate::menus;
+use crate::widgets;
 
 struct Proj {
     proj: Mat4,
@@ -77,47 +96,71 @@ pub struct Stage {
     face_buffer: camera::FaceBuffer,
     sprite_buffer: sprites::SpriteBuffer,
     game_map: map::GameMap,
+    campaign: campaign::Campaign,
+    level: level::Level,
+    script: scripting::ScriptEngine,
+    stats: campaign::Stats,
+    entities: entities::EntityList,
+    weapon: weapon::Weapon,
+    inventory: inventory::Inventory,
+    messages: messages::Messages,
+    finished: bool,
+    fonts: text::Fonts,
+    strings: lang::Strings,
     overlay: text::Overlay,
     gui: text::GUI,
+    menus: Vec<widgets::Menu>,
+    console: console::Console,
+    hud: hud::Hud,
+    profiler: profiler::Profiler,
     mesh: Vec<mesh::Mesh>,
     render_pass: RenderPass,
     pipeline: Vec<Pipeline>,
     bindings: Vec<Bindings>,
     proj: Proj,
-    tx: Sender<bool>,
+    tx: Sender<audio::AudioCommand>,
+    pad: Receiver<Nav>,
 
     time_state: TimeState,
     input_state: InputState,
-    request: bool,
 }
 
 impl Stage {
-    pub fn new(tx: &Sender<bool>) -> Stage {
+    pub fn new(tx: &Sender<audio::AudioCommand>) -> Stage {
         let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
 
         let settings = settings::Settings::init();
-        let ass = assets::Ass::load();
+        let campaign = campaign::Campaign::load(campaign::CAMPAIGN_PATH);
+        let ass = assets::Ass::load(&campaign.level().dir);
         let player = player::Player::new(&settings);
 
         let mut game_map = map::GameMap::new(&ass);
+        let level = level::Level::load(&format!("{}/{}", campaign.level().dir, level::LEVEL_FILE), &mut game_map);
+        let entities = entities::EntityList::spawn_from_map(&game_map);
+        let inventory = inventory::Inventory::new(
+            entities.count(entities::EntityKind::Pickup(inventory::Item::Collectible)) as u32,
+        );
 
         let rays = camera::ray_cast(&mut game_map, &player, &settings);
-        let face_buffer = camera::FaceBuffer::generate(&game_map, &player, &settings);
+        let face_buffer = camera::FaceBuffer::generate(&game_map, &level.movers, &player, &settings);
 
-        let sprite_buffer = sprites::SpriteBuffer::generate(&game_map, &player, &settings);
+        let sprite_buffer = sprites::SpriteBuffer::generate(&game_map, &entities, &player, &settings);
 
-        let overlay = text::Overlay::new_from(vec!["Text default"]);
-        let gui = text::GUI::new_from(vec!["Text default"], settings.screen_width_f, settings.screen_height_f);
+        let fonts = text::Fonts::load();
+        let strings = lang::Strings::load(&settings.language);
+        let overlay = text::Overlay::new_from(&fonts, vec!["Text default"]);
+        let gui = text::GUI::new_from(&fonts, vec!["Text default"], settings.screen_width_f, settings.screen_height_f);
 
         let mesh_main = mesh::Mesh::new_main(&face_buffer, &sprite_buffer);
         let mesh_overlay = mesh::Mesh::new_overlay(
             &overlay,
+            &fonts,
             1.0 / settings.screen_width_f,
             1.0 / settings.screen_height_f,
-            false,
         );
         let mesh_gui = mesh::Mesh::new_gui(
             &gui,
+            &fonts,
             1.0 / settings.screen_width_f,
             1.0 / settings.screen_height_f,
         );
@@ -129,6 +172,29 @@ impl Stage {
             1.0 / settings.screen_height_f,
         );
         let mesh_screen = mesh::Mesh::new_screen();
+        let console = console::Console::new();
+        let mesh_console = mesh::Mesh::new_console(
+            &console.overlay(&fonts, &settings),
+            &fonts,
+            console.height(&fonts),
+            1.0 / settings.screen_width_f,
+            1.0 / settings.screen_height_f,
+        );
+        let weapon = weapon::Weapon::init();
+        let mesh_hud = mesh::Mesh::new_hud(
+            &weapon,
+            1.0 / settings.screen_width_f,
+            1.0 / settings.screen_height_f,
+        );
+        // built only while the profiler is shown
+        let mesh_profiler = mesh::Mesh { vertices: Vec::new(), indices: Vec::new(), num: 0 };
+        let hud = hud::Hud::new(&fonts, &strings, &settings, &player, &weapon, &inventory, false);
+        let mesh_status = mesh::Mesh::new_status(
+            &hud,
+            &fonts,
+            1.0 / settings.screen_width_f,
+            1.0 / settings.screen_height_f,
+        );
 
         let vertex_buffer_main = ctx.new_buffer(
             BufferType::VertexBuffer,
@@ -160,6 +226,30 @@ impl Stage {
             BufferSource::slice(&mesh_screen.vertices),
         );
 
+        let vertex_buffer_hud = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<mesh::Vertex>(settings::MAX_VERTICES_HUD),
+        );
+
+        let vertex_buffer_status = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<mesh::Vertex>(settings::MAX_VERTICES_STATUS),
+        );
+
+        let vertex_buffer_profiler = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<mesh::Vertex>(settings::MAX_VERTICES_PROFILER),
+        );
+
+        let vertex_buffer_console = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<mesh::Vertex>(settings::MAX_VERTICES_CONSOLE),
+        );
+
         let index_buffer_main = ctx.new_buffer(
             BufferType::IndexBuffer,
             BufferUsage::Stream,
@@ -190,6 +280,30 @@ impl Stage {
             BufferSource::slice(&mesh_screen.indices),
         );
 
+        let index_buffer_hud = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<i16>(2*settings::MAX_INDICES_HUD),
+        );
+
+        let index_buffer_status = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<i16>(2*settings::MAX_INDICES_STATUS),
+        );
+
+        let index_buffer_profiler = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<i16>(2*settings::MAX_INDICES_PROFILER),
+        );
+
+        let index_buffer_console = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<i16>(2*settings::MAX_INDICES_CONSOLE),
+        );
+
         let pixels: ImageBuffer<Rgba<u8>, Vec<u8>> = ass.tile_atlas;
         let dims = pixels.dimensions();
 
@@ -208,7 +322,7 @@ impl Stage {
         let texture_main = ctx.new_texture_from_data_and_format(pixels.as_bytes(), t_params);
         ctx.texture_generate_mipmaps(texture_main);
 
-        let pixels: ImageBuffer<Rgba<u8>, Vec<u8>> = ass.font;
+        let pixels: &ImageBuffer<Rgba<u8>, Vec<u8>> = &fonts.atlas;
         let dims = pixels.dimensions();
         t_params.mipmap_filter = MipmapFilterMode::None;
         t_params.width = dims.0;
@@ -224,6 +338,20 @@ impl Stage {
 
         let texture_map = ctx.new_texture_from_data_and_format(pixels.as_bytes(), t_params);
 
+        let pixels: ImageBuffer<Rgba<u8>, Vec<u8>> = ass.hud_atlas;
+        let dims = pixels.dimensions();
+        t_params.width = dims.0;
+        t_params.height = dims.1;
+
+        let texture_hud = ctx.new_texture_from_data_and_format(pixels.as_bytes(), t_params);
+
+        let pixels: ImageBuffer<Rgba<u8>, Vec<u8>> = ass.hud_icons;
+        let dims = pixels.dimensions();
+        t_params.width = dims.0;
+        t_params.height = dims.1;
+
+        let texture_icons = ctx.new_texture_from_data_and_format(pixels.as_bytes(), t_params);
+
         t_params = TextureParams {
             kind: TextureKind::Texture2D,
             format: TextureFormat::RGBA8,
@@ -270,12 +398,36 @@ impl Stage {
             images: vec![texture_overlay],
         };
 
+        let bindings_status = Bindings {
+            vertex_buffers: vec![vertex_buffer_status],
+            index_buffer: index_buffer_status,
+            images: vec![texture_icons, texture_overlay],
+        };
+
+        let bindings_profiler = Bindings {
+            vertex_buffers: vec![vertex_buffer_profiler],
+            index_buffer: index_buffer_profiler,
+            images: vec![texture_overlay],
+        };
+
+        let bindings_console = Bindings {
+            vertex_buffers: vec![vertex_buffer_console],
+            index_buffer: index_buffer_console,
+            images: vec![texture_overlay],
+        };
+
         let bindings_map = Bindings {
             vertex_buffers: vec![vertex_buffer_map],
             index_buffer: index_buffer_map,
             images: vec![texture_map],
         };
 
+        let bindings_hud = Bindings {
+            vertex_buffers: vec![vertex_buffer_hud],
+            index_buffer: index_buffer_hud,
+            images: vec![texture_hud],
+        };
+
         let bindings_screen = Bindings {
             vertex_buffers: vec![vertex_buffer_screen],
             index_buffer: index_buffer_screen,
@@ -322,6 +474,26 @@ impl Stage {
             )
             .unwrap();
 
+        let shader_hud = ctx
+            .new_shader(
+                miniquad::ShaderSource::Glsl {
+                    vertex: shaders::VERTEX_HUD,
+                    fragment: shaders::FRAGMENT_HUD,
+                },
+                shaders::meta_hud(),
+            )
+            .unwrap();
+
+        let shader_status = ctx
+            .new_shader(
+                miniquad::ShaderSource::Glsl {
+                    vertex: shaders::VERTEX_STATUS,
+                    fragment: shaders::FRAGMENT_STATUS,
+                },
+                shaders::meta_status(),
+            )
+            .unwrap();
+
         let shader_screen = ctx
             .new_shader(
                 miniquad::ShaderSource::Glsl {
@@ -357,6 +529,7 @@ impl Stage {
                 VertexAttribute::new("pos", VertexFormat::Float3),
                 VertexAttribute::new("uv", VertexFormat::Float2),
                 VertexAttribute::new("act", VertexFormat::Float1),
+                VertexAttribute::new("tint", VertexFormat::Float4),
             ],
             shader_main,
             p_params,
@@ -387,6 +560,7 @@ impl Stage {
                 VertexAttribute::new("pos", VertexFormat::Float3),
                 VertexAttribute::new("uv", VertexFormat::Float2),
                 VertexAttribute::new("act", VertexFormat::Float1),
+                VertexAttribute::new("tint", VertexFormat::Float4),
             ],
             shader_overlay,
             p_params,
@@ -398,6 +572,7 @@ impl Stage {
                 VertexAttribute::new("pos", VertexFormat::Float3),
                 VertexAttribute::new("uv", VertexFormat::Float2),
                 VertexAttribute::new("act", VertexFormat::Float1),
+                VertexAttribute::new("tint", VertexFormat::Float4),
             ],
             shader_gui,
             p_params,
@@ -409,11 +584,36 @@ impl Stage {
                 VertexAttribute::new("pos", VertexFormat::Float3),
                 VertexAttribute::new("uv", VertexFormat::Float2),
                 VertexAttribute::new("act", VertexFormat::Float1),
+                VertexAttribute::new("tint", VertexFormat::Float4),
             ],
             shader_map,
             p_params,
         );
 
+        let pipeline_hud = ctx.new_pipeline_with_params(
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("pos", VertexFormat::Float3),
+                VertexAttribute::new("uv", VertexFormat::Float2),
+                VertexAttribute::new("act", VertexFormat::Float1),
+                VertexAttribute::new("tint", VertexFormat::Float4),
+            ],
+            shader_hud,
+            p_params,
+        );
+
+        let pipeline_status = ctx.new_pipeline_with_params(
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("pos", VertexFormat::Float3),
+                VertexAttribute::new("uv", VertexFormat::Float2),
+                VertexAttribute::new("act", VertexFormat::Float1),
+                VertexAttribute::new("tint", VertexFormat::Float4),
+            ],
+            shader_status,
+            p_params,
+        );
+
         let p_params = PipelineParams {
             cull_face: CullFace::Nothing,
             front_face_order: FrontFaceOrder::CounterClockwise,
@@ -433,63 +633,447 @@ impl Stage {
                 VertexAttribute::new("pos", VertexFormat::Float3),
                 VertexAttribute::new("uv", VertexFormat::Float2),
                 VertexAttribute::new("act", VertexFormat::Float1),
+                VertexAttribute::new("tint", VertexFormat::Float4),
             ],
             shader_screen,
             p_params,
         );
 
-        let gui = text::GUI::new_from(vec!["Text default"], settings.screen_width_f, settings.screen_height_f);
+        let gui = text::GUI::new_from(&fonts, vec!["Text default"], settings.screen_width_f, settings.screen_height_f);
         //gui.show = false;
 
         let proj = Proj::new(&player, &settings);
 
+        let (pad_tx, pad) = mpsc::channel();
+        gamepad::listen(pad_tx);
+
         let render_pass = ctx.new_render_pass(texture, Some(depth_tex));
 
-        Stage {
+        let mut stage = Stage {
             ctx,
 
             settings,
             player,
             game_map,
+            campaign,
+            level,
+            script: scripting::ScriptEngine::new(),
+            stats: campaign::Stats::new(0, 0),
+            entities,
+            weapon,
+            inventory,
+            messages: messages::Messages::new(),
+            finished: false,
             face_buffer,
             sprite_buffer,
-            overlay: text::Overlay::new_from(vec!["Text default"]),
+            overlay: text::Overlay::new_from(&fonts, vec!["Text default"]),
+            fonts,
+            strings,
             gui,
-            pipeline: vec![pipeline_main, pipeline_overlay, pipeline_gui, pipeline_map, pipeline_screen],
-            bindings: vec![bindings_main, bindings_overlay, bindings_gui, bindings_map, bindings_screen],
-            mesh: vec![mesh_main, mesh_overlay, mesh_gui, mesh_map, mesh_screen],
+            menus: Vec::new(),
+            console,
+            hud,
+            profiler: profiler::Profiler::new(),
+            // the console and the profiler are overlay text with bindings of their own
+            pipeline: vec![pipeline_main, pipeline_overlay, pipeline_gui, pipeline_map, pipeline_screen, pipeline_hud, pipeline_overlay, pipeline_status, pipeline_overlay],
+            bindings: vec![bindings_main, bindings_overlay, bindings_gui, bindings_map, bindings_screen, bindings_hud, bindings_console, bindings_status, bindings_profiler],
+            mesh: vec![mesh_main, mesh_overlay, mesh_gui, mesh_map, mesh_screen, mesh_hud, mesh_console, mesh_status, mesh_profiler],
             render_pass,
             proj,
             tx: tx.clone(),
+            pad,
 
             time_state: TimeState::init(),
             input_state: InputState::init(),
-            request: false,
+        };
+
+        stage.check_glyphs();
+        stage.start_level();
+        stage.menus.push(menus::title(&stage.strings));
+        stage
+    }
+
+    // Everything a level needs besides its map: player start, stats, music
+    // and the level script.
+    fn start_level(&mut self) {
+        self.player = player::Player::new(&self.settings);
+        if let Some((x, y, a)) = self.level.start {
+            self.player.place(x, y, a, &self.settings);
+            self.player.checkpoint = player::Checkpoint { x, y, a };
+        }
+        self.stats = campaign::Stats::new(
+            self.entities.enemies,
+            self.level.movers.movers.iter().filter(|m| m.kind == movers::MoverKind::PushWall).count() as u32,
+        );
+        self.finished = false;
+        self.messages.clear();
+        for error in self.level.errors.clone() {
+            self.notify(&richtext::escape(&error), Priority::Error);
+        }
+        self.menus.clear();
+        let _ = self.tx.send(audio::AudioCommand::Track(self.campaign.level().music.clone()));
+
+        self.script = scripting::ScriptEngine::new();
+        if let Some(path) = &self.level.script {
+            self.script.load(path);
         }
+        let actions = self.script.start(&mut self.game_map, &mut self.entities, &self.player, &self.inventory);
+        self.run_actions(actions);
+    }
+
+    // Swaps in another level of the campaign without touching the GPU
+    // resources, only the minimap texture follows the new wall map.
+    fn load_level(&mut self, index: usize) {
+        self.campaign.current = index;
+        let dir = self.campaign.level().dir.clone();
+        let ass = assets::Ass::load(&dir);
+
+        self.game_map = map::GameMap::new(&ass);
+        self.level = level::Level::load(&format!("{}/{}", dir, level::LEVEL_FILE), &mut self.game_map);
+        self.entities = entities::EntityList::spawn_from_map(&self.game_map);
+        self.inventory = inventory::Inventory::new(
+            self.entities.count(entities::EntityKind::Pickup(inventory::Item::Collectible)) as u32,
+        );
+        self.ctx.texture_update(self.bindings[3].images[0], ass.wall_image_bot.as_bytes());
+
+        self.start_level();
     }
 
     fn show_data(&mut self) {
-        self.overlay = text::Overlay::new_from(vec![
-            &format!("FPS: {}", self.time_state.fps + 1),
-            &format!("Press (Esc) for menu."),
-            &format!("Position: ({:.1},{:.1})", self.player.position.x, self.player.position.y),
-            &format!("Press (K) to take a screenshot."),
+        let strings = &self.strings;
+        let (x, y) = (format!("{:.1}", self.player.position.x), format!("{:.1}", self.player.position.y));
+        self.overlay = text::Overlay::new_from(&self.fonts, vec![
+            &strings.format("hint.fps", &[&(self.time_state.fps + 1)]),
+            strings.get("hint.menu"),
+            &strings.format("hint.position", &[&x, &y]),
+            &strings.format("hint.stamina", &[&(100.0 * self.player.status.stamina / self.settings.stamina_max).round()]),
+            &strings.format("hint.inventory", &[
+                &self.inventory.keys_text(strings),
+                &self.inventory.collectibles,
+                &self.inventory.collectibles_total,
+            ]),
+            strings.get("hint.tools"),
         ]);
+        self.messages.draw(&mut self.overlay, &self.fonts, &self.strings, &self.settings);
+    }
+
+    // One simulation step: player, moving walls, triggers, weapon and entities
+    fn tick(&mut self) {
+        self.player.read_key(&self.input_state);
+
+        let using = self.input_state.keys.e && self.input_state.apply_change && self.player.status.alive;
+        self.level.movers.update(&mut self.game_map, &self.player, &self.entities, self.settings.delta_time);
+        if using {
+            if let Some(message) = self.level.movers.use_at(&mut self.game_map, &self.player, &self.inventory) {
+                self.notify(&self.strings.text(&message), Priority::Normal);
+            }
+            self.input_state.apply_change = false;
+        }
+
+        self.input_state.mouse.moving = false;
+
+        self.player.walk(
+            &self.game_map,
+            &self.settings,
+            self.input_state.mouse.dx,
+            self.input_state.mouse.dy,
+            self.input_state.mouse.moving,
+        );
+
+        if self.player.status.alive && self.game_map.special(self.player.position.x, self.player.position.y) == map::SPECIAL_EXIT {
+            self.finished = true;
+        }
+
+        let mut actions = self.level.triggers.update(&self.player, using, self.settings.delta_time);
+        actions.extend(self.call_script("on_tick", vec![(self.settings.delta_time as rhai::FLOAT).into()]));
+        self.run_actions(actions);
+
+        self.weapon.update(self.settings.delta_time);
+        if self.input_state.mouse.left && !self.gui.show && self.player.status.alive {
+            self.weapon.trigger(&self.game_map, &mut self.entities, &self.player);
+        }
+
+        self.entities.update(&self.game_map, &mut self.player, &self.settings);
+        self.player.tile_effects(&self.game_map, &self.settings);
+        self.pick_up();
+        self.messages.update(self.settings.delta_time);
+
+        self.stats.time += self.settings.delta_time;
+        self.stats.kills = self.entities.kills;
+        self.stats.kills_total = self.entities.enemies;
+        self.stats.secrets = self.level.movers.secrets_found;
     }
 
-    fn show_gui(&mut self) {
-        self.gui = text::GUI::new_from(vec![
-            &format!("Continue"),
-            &format!("-"),
-            &format!("Fullscreen"),
-            &format!("Light >"),
-            &format!("Light <"),
-            &format!("{}", if self.settings.music_playing {"Pause music"} else {"Resume music"}),
-            &format!("-"),
-            &format!("Quit game"),
-        ], self.settings.screen_width_f, self.settings.screen_height_f);
+    fn run_actions(&mut self, actions: Vec<triggers::Action>) {
+        for action in triggers::run(actions, &self.settings, &self.game_map, &mut self.player, &mut self.level.movers, &mut self.entities) {
+            match action {
+                triggers::Action::Message(message) => self.notify(&message, Priority::Normal),
+                triggers::Action::Announce(message) => self.notify(&message, Priority::Center),
+                triggers::Action::Sound(name) => {
+                    let _ = self.tx.send(audio::AudioCommand::Sound(name));
+                }
+                triggers::Action::Script(name) => {
+                    let actions = self.call_script(&name, Vec::new());
+                    self.run_actions(actions);
+                }
+                triggers::Action::EndLevel => self.finished = true,
+                _ => {}
+            }
+        }
+        // script errors are reported, the level keeps running
+        for error in std::mem::take(&mut self.script.errors) {
+            self.notify(&richtext::escape(&error), Priority::Error);
+        }
+    }
+
+    fn call_script(&mut self, name: &str, args: Vec<rhai::Dynamic>) -> Vec<triggers::Action> {
+        self.script.call(name, args, &mut self.game_map, &mut self.entities, &self.player, &self.inventory)
+    }
 
-        self.gui.gui_highlight(self.input_state.mouse.x, self.input_state.mouse.y);
+    // Shows a line of markup on the HUD and keeps it in the console
+    fn notify(&mut self, message: &str, priority: Priority) {
+        self.messages.push(message, priority);
+        self.console.print(&richtext::plain(message));
+    }
+
+    // Loads the strings of the language in the settings and builds the open
+    // menus again in it
+    fn set_language(&mut self) {
+        self.strings = lang::Strings::load(&self.settings.language);
+        self.check_glyphs();
+        for menu in self.menus.iter_mut() {
+            if let Some(mut page) = menus::page(menu.name, &self.strings) {
+                page.focus = menu.focus;
+                *menu = page;
+            }
+        }
+    }
+
+    // Characters of the language the fonts can't draw are only reported,
+    // they show as a replacement glyph
+    fn check_glyphs(&mut self) {
+        let missing = self.strings.missing_glyphs(&self.fonts);
+        if !missing.is_empty() {
+            let codes: Vec<String> = missing.iter().map(|&c| format!("U+{:04X}", c as u32)).collect();
+            self.console.print(&format!("No glyphs for {} in language {}", codes.join(" "), self.strings.language));
+        }
+    }
+
+    // Counts what the frame draws against the buffer limits and builds the
+    // profiler panel
+    fn show_profiler(&mut self) {
+        let tiles = self.game_map.floor_visible.iter().flatten().filter(|&&seen| seen).count();
+        self.profiler.count("tiles", tiles, Some(settings::MAPSIZE * settings::MAPSIZE));
+        self.profiler.count("faces", self.face_buffer.len, Some(settings::MAX_FACES_MAIN));
+        self.profiler.count("sprites", self.sprite_buffer.len, None);
+        self.profiler.count("quads", self.mesh[0].num as usize, Some(settings::MAX_FACES_MAIN));
+        self.profiler.count("indices", self.mesh[0].indices.len(), Some(settings::MAX_INDICES_MAIN));
+        self.profiler.count("map", self.mesh[3].vertices.len(), Some(settings::MAX_VERTICES_MAP));
+        self.profiler.count("overlay", self.mesh[1].num as usize, Some(settings::MAX_QUADS_OVERLAY));
+        self.profiler.count("hud", self.mesh[7].num as usize, Some(settings::MAX_QUADS_STATUS));
+
+        let text = self.profiler.overlay(&self.fonts);
+        self.mesh[8] = mesh::Mesh::new_profiler(
+            &self.profiler.rects(&text),
+            &text,
+            &self.fonts,
+            1.0 / self.settings.screen_width_f,
+            1.0 / self.settings.screen_height_f,
+        );
+    }
+
+    // Saves the last rendered frame next to the game
+    fn screenshot(&mut self) -> Result<String, String> {
+        let cap = (settings::WIDTH*settings::HEIGHT*4) as usize;
+        let mut image: Vec<u8> = vec![0; cap];
+        self.ctx.texture_read_pixels(self.ctx.render_pass_texture(self.render_pass), &mut image);
+        let name = format!("screenshot-{}.png", self.time_state.frame_count);
+        image::save_buffer_with_format(&name, &image,
+            settings::WIDTH, settings::HEIGHT, image::ColorType::Rgba8,
+            image::ImageFormat::Png).map_err(|error| error.to_string())?;
+        Ok(name)
+    }
+
+    // Carries out what the console can't do by itself
+    fn console_command(&mut self, command: console::Command) {
+        match command {
+            console::Command::Teleport(x, y) => {
+                if let Some(reason) = self.game_map.unplaceable(x, y) {
+                    self.console.print(&format!("Can't move to ({}, {}), {}", x, y, reason));
+                } else {
+                    let a = self.player.position.a;
+                    self.player.place(x, y, a, &self.settings);
+                    self.console.print(&format!("Moved to ({:.1},{:.1})", x, y));
+                }
+            }
+            console::Command::Noclip => {
+                self.player.movement.noclip = !self.player.movement.noclip;
+                self.console.print(if self.player.movement.noclip { "noclip ON" } else { "noclip OFF" });
+            }
+            console::Command::God => {
+                self.player.status.god = !self.player.status.god;
+                self.console.print(if self.player.status.god { "god ON" } else { "god OFF" });
+            }
+            console::Command::Map(name) => match self.campaign.find(&name) {
+                Some(index) => {
+                    self.load_level(index);
+                    self.console.print(&format!("Playing {}", self.campaign.level().title));
+                }
+                None => self.console.print(&format!("No level {}", name)),
+            },
+            console::Command::Screenshot => match self.screenshot() {
+                Ok(name) => self.console.print(&format!("Saved {}", name)),
+                Err(error) => self.console.print(&format!("Can't save screenshot: {}", error)),
+            },
+            console::Command::Quit => miniquad::window::quit(),
+            console::Command::Changed(name) => match name.as_str() {
+                "full_screen" => {
+                    miniquad::window::set_fullscreen(self.settings.full_screen);
+                    let screen = miniquad::window::screen_size();
+                    self.settings.screen_change(screen.0, screen.1);
+                }
+                "music_playing" => {
+                    let _ = self.tx.send(audio::AudioCommand::Music(self.settings.music_playing));
+                }
+                "language" => self.set_language(),
+                _ => {}
+            },
+        }
+    }
+
+    fn pick_up(&mut self) {
+        if !self.player.status.alive {
+            return;
+        }
+        for (id, item) in self.entities.pickups_in_reach(self.player.position.x, self.player.position.y, 0.6) {
+            if let Some(message) = self.inventory.collect(item, &mut self.player, &mut self.weapon) {
+                self.entities.despawn(id);
+                self.notify(&self.strings.text(&message), Priority::Low);
+            }
+        }
+    }
+
+    fn show_death(&mut self) {
+        let cause = match self.player.status.last_damage {
+            Some(player::DamageSource::Enemy) => "death.enemy",
+            Some(player::DamageSource::Hazard) => "death.hazard",
+            Some(player::DamageSource::Fall) => "death.fall",
+            None => "death.unknown",
+        };
+        self.menus.push(menus::death(&self.strings, self.strings.get(cause)));
+    }
+
+    // Intermission between levels
+    fn show_end(&mut self) {
+        let title = &self.campaign.level().title;
+        let strings = &self.strings;
+        let title = if title.is_empty() {
+            strings.get("end.heading_untitled").to_string()
+        } else {
+            strings.format("end.heading", &[title])
+        };
+        let stats = vec![
+            strings.format("end.time", &[&self.stats.time_text()]),
+            strings.format("end.kills", &[&self.stats.kills, &self.stats.kills_total]),
+            strings.format("end.secrets", &[&self.stats.secrets, &self.stats.secrets_total]),
+            strings.format("end.gems", &[&self.inventory.collectibles, &self.inventory.collectibles_total]),
+        ];
+        self.menus.push(menus::end(strings, &title, stats, self.campaign.is_last()));
+    }
+
+    // Esc leaves the screen on top, or opens the pause menu during play
+    fn back(&mut self) {
+        match self.menus.last() {
+            None if self.player.status.alive && !self.finished => self.menus.push(menus::pause(&self.strings)),
+            Some(menu) if menu.name == "pause" => self.menus.clear(),
+            Some(menu) if !menus::is_root(menu) => {
+                self.menus.pop();
+            }
+            _ => {}
+        }
+    }
+
+    // Keys and pad buttons move through the menu on top, going back from
+    // the game itself opens the pause menu
+    fn navigate(&mut self, nav: Nav) {
+        if nav == Nav::Back {
+            if !self.menus.last_mut().is_some_and(|menu| menu.close_popups()) {
+                self.back();
+            }
+            return;
+        }
+        if let Some(menu) = self.menus.last_mut() {
+            let name = menu.name;
+            if let Some(id) = menu.navigate(nav, &self.fonts, &mut self.settings) {
+                self.menu_event(name, id);
+            }
+        }
+    }
+
+    // A control of the menu `name` was pressed or changed
+    fn menu_event(&mut self, name: &'static str, id: &'static str) {
+        match (name, id) {
+            (_, "back") | (_, "no") => {
+                self.menus.pop();
+            }
+            (_, "continue") => self.menus.clear(),
+            (_, "new") => self.load_level(0),
+            (_, "load") => self.load_game(),
+            (_, "options") => self.menus.push(menus::options(&self.strings)),
+            (_, "video") => self.menus.push(menus::video(&self.strings)),
+            (_, "audio") => self.menus.push(menus::audio(&self.strings)),
+            (_, "hud") => self.menus.push(menus::hud(&self.strings)),
+            (_, "controls") => self.menus.push(menus::controls(&self.strings)),
+            (_, "music") => {
+                let _ = self.tx.send(audio::AudioCommand::Music(self.settings.music_playing));
+            }
+            (_, "language") => self.set_language(),
+            (_, "title") => {
+                let menu = menus::confirm(&self.strings, "quit_title", self.strings.get("confirm.quit_title"));
+                self.menus.push(menu);
+            }
+            (_, "quit") => {
+                let menu = menus::confirm(&self.strings, "quit_game", self.strings.get("confirm.quit_game"));
+                self.menus.push(menu);
+            }
+            ("quit_title", "yes") => {
+                self.menus.clear();
+                self.menus.push(menus::title(&self.strings));
+            }
+            ("quit_game", "yes") => miniquad::window::quit(),
+            (_, "respawn") => {
+                self.player.respawn(&self.settings);
+                self.menus.clear();
+            }
+            (_, "next") => self.load_level(self.campaign.current + 1),
+            _ => {}
+        }
+    }
+
+    fn load_game(&mut self) {
+        // the level is always loaded again, movers and triggers start over
+        // as the save does not keep them
+        match save::level(save::SAVE_PATH) {
+            Some(index) if index < self.campaign.levels.len() => self.load_level(index),
+            _ => {}
+        }
+        if save::load(
+            save::SAVE_PATH,
+            &self.game_map,
+            &self.settings,
+            &mut self.player,
+            &mut self.weapon,
+            &mut self.inventory,
+            &mut self.entities,
+        ) {
+            self.menus.clear();
+            self.finished = false;
+            let message = self.strings.get("game.loaded").to_string();
+            self.notify(&message, Priority::Normal);
+        } else {
+            let message = self.strings.get("game.no_save").to_string();
+            self.notify(&message, Priority::Normal);
+        }
     }
 }
 
@@ -501,49 +1085,101 @@ impl EventHandler for Stage {
 
     fn update(&mut self) {
         self.time_state.frame_time(&mut self.settings);
+        self.profiler.frame(self.time_state.frame_time);
         self.show_data();
 
-        if self.gui.show {
-            self.show_gui();
-            self.request = self.gui.gui_control(&self.input_state, &mut self.settings);
-            if self.request {
-                self.tx.send(self.settings.music_playing).unwrap();
+        if !self.player.status.alive && self.menus.is_empty() {
+            self.show_death();
+        }
+        if self.finished && self.menus.is_empty() {
+            self.show_end();
+        }
+
+        while let Ok(nav) = self.pad.try_recv() {
+            self.input_state.nav.push(nav);
+        }
+        for nav in std::mem::take(&mut self.input_state.nav) {
+            self.navigate(nav);
+        }
+
+        if let Some(menu) = self.menus.last_mut() {
+            let name = menu.name;
+            if let Some(id) = menu.update(&self.input_state, &self.fonts, &mut self.settings) {
+                self.menu_event(name, id);
             }
             self.input_state.apply_change = false;
-            self.request = false
         }
+        self.gui.show = !self.menus.is_empty();
 
-        if self.input_state.keys.esc {
-            self.gui.show = true
+        // the game stands still while a menu or the console is open
+        if self.menus.is_empty() && !self.console.open {
+            self.tick();
         }
 
-        self.player.read_key(&self.input_state);
-
-        self.input_state.mouse.moving = false;
+        // only a live player in the game can be saved
+        let can_save = self.player.status.alive && self.menus.is_empty();
+        if self.input_state.keys.f5 && self.input_state.apply_change && can_save {
+            if save::save(save::SAVE_PATH, self.campaign.current, &self.player, &self.weapon, &self.inventory, &self.entities) {
+                let message = self.strings.get("game.saved").to_string();
+                self.notify(&message, Priority::Normal);
+            } else {
+                let message = self.strings.get("game.save_failed").to_string();
+                self.notify(&message, Priority::Error);
+            }
+            self.input_state.apply_change = false;
+        }
+        if self.input_state.keys.f9 && self.input_state.apply_change {
+            self.load_game();
+            self.input_state.apply_change = false;
+        }
 
-        self.player.walk(
-            &self.game_map,
-            &self.settings,
-            self.input_state.mouse.dx,
-            self.input_state.mouse.dy,
-            self.input_state.mouse.moving,
-        );
-        
+        let start = date::now();
         let rays = camera::ray_cast(&mut self.game_map, &self.player, &self.settings);
+        self.profiler.record(Section::RayCast, start);
+
+        let start = date::now();
         self.face_buffer =
-            camera::FaceBuffer::generate(&self.game_map, &self.player, &self.settings);
+            camera::FaceBuffer::generate(&self.game_map, &self.level.movers, &self.player, &self.settings);
+        self.profiler.record(Section::Faces, start);
 
-        self.sprite_buffer = sprites::SpriteBuffer::generate(&self.game_map, &self.player, &self.settings);
+        let start = date::now();
+        self.sprite_buffer = sprites::SpriteBuffer::generate(&self.game_map, &self.entities, &self.player, &self.settings);
+        self.profiler.record(Section::Sprites, start);
 
+        let start = date::now();
         self.mesh[0] = mesh::Mesh::new_main(&self.face_buffer, &self.sprite_buffer);
         self.mesh[1] = mesh::Mesh::new_overlay(
             &self.overlay,
+            &self.fonts,
             1.0 / self.settings.screen_width_f,
             1.0 / self.settings.screen_height_f,
-            self.input_state.mouse.left
         );
+        if let Some(menu) = self.menus.last() {
+            menu.fill(&mut self.gui, &self.fonts, &self.settings);
+        }
         self.mesh[2] = mesh::Mesh::new_gui(
             &self.gui,
+            &self.fonts,
+            1.0 / self.settings.screen_width_f,
+            1.0 / self.settings.screen_height_f,
+        );
+        self.mesh[5] = mesh::Mesh::new_hud(
+            &self.weapon,
+            1.0 / self.settings.screen_width_f,
+            1.0 / self.settings.screen_height_f,
+        );
+        self.hud = hud::Hud::new(
+            &self.fonts,
+            &self.strings,
+            &self.settings,
+            &self.player,
+            &self.weapon,
+            &self.inventory,
+            self.input_state.mouse.left,
+        );
+        self.mesh[7] = mesh::Mesh::new_status(
+            &self.hud,
+            &self.fonts,
             1.0 / self.settings.screen_width_f,
             1.0 / self.settings.screen_height_f,
         );
@@ -555,13 +1191,34 @@ impl EventHandler for Stage {
             1.0 / self.settings.screen_height_f,
         );
 
+        if self.console.open {
+            self.mesh[6] = mesh::Mesh::new_console(
+                &self.console.overlay(&self.fonts, &self.settings),
+                &self.fonts,
+                self.console.height(&self.fonts),
+                1.0 / self.settings.screen_width_f,
+                1.0 / self.settings.screen_height_f,
+            );
+        }
+        self.profiler.record(Section::Meshes, start);
+
+        if self.settings.show_profiler {
+            self.show_profiler();
+        }
+
         if self.input_state.keys.k && self.input_state.apply_change {
-            let cap = (settings::WIDTH*settings::HEIGHT*4) as usize;
-            let mut image: Vec<u8> = vec![0; cap];
-            self.ctx.texture_read_pixels(self.ctx.render_pass_texture(self.render_pass), &mut image);
-            image::save_buffer_with_format(format!("screenshot-{}.png", self.time_state.frame_count), &image, 
-            settings::WIDTH as u32, settings::HEIGHT as u32, image::ColorType::Rgba8, 
-                image::ImageFormat::Png).expect("Can't save screenshot");
+            if let Err(error) = self.screenshot() {
+                let message = self.strings.format("screenshot.failed", &[&richtext::escape(&error)]);
+                self.notify(&message, Priority::Error);
+            }
+            self.input_state.apply_change = false;
+        }
+        if self.input_state.keys.h && self.input_state.apply_change {
+            self.messages.toggle_history();
+            self.input_state.apply_change = false;
+        }
+        if self.input_state.keys.f3 && self.input_state.apply_change {
+            self.settings.show_profiler = !self.settings.show_profiler;
             self.input_state.apply_change = false;
         }
 
@@ -573,7 +1230,7 @@ impl EventHandler for Stage {
     // ============================
 
     fn draw(&mut self) {
-        window::show_mouse(self.gui.show);
+        window::show_mouse(self.gui.show || self.console.open);
 
         self.ctx.begin_default_pass(PassAction::Clear { color: Some((0.0, 0.0, 0.0, 1.0)), depth: None, stencil: None });
 
@@ -592,10 +1249,13 @@ impl EventHandler for Stage {
         self.ctx
             .begin_pass(Some(self.render_pass), PassAction::default());
 
+        let start = date::now();
         for j in 0..self.bindings.len() {
             self.ctx.buffer_update(self.bindings[j].vertex_buffers[0], BufferSource::slice(&self.mesh[j].vertices));
             self.ctx.buffer_update(self.bindings[j].index_buffer, BufferSource::slice(&self.mesh[j].indices));
         }
+        self.profiler.record(Section::Upload, start);
+        let start = date::now();
 
         self.ctx.apply_pipeline(&self.pipeline[0]);
 
@@ -603,11 +1263,13 @@ impl EventHandler for Stage {
 
         self.proj.update(&self.player, &self.settings);
 
-        let lightpos = if self.sprite_buffer.len > 0 {
+        let light = self.sprite_buffer.sprites_dist.iter().rev().find(|s| s.light);
+
+        let lightpos = if let Some(light) = light {
             (
-                self.sprite_buffer.sprites_dist[self.sprite_buffer.len-1].x,
-                self.sprite_buffer.sprites_dist[self.sprite_buffer.len-1].y,
-                self.sprite_buffer.sprites_dist[self.sprite_buffer.len-1].z,
+                light.x,
+                light.y,
+                light.z,
             )
         } else {
             (
@@ -630,6 +1292,17 @@ impl EventHandler for Stage {
             }));
         self.ctx.draw(0, self.mesh[0].num * 6, 1);
 
+        self.ctx.apply_pipeline(&self.pipeline[5]);
+
+        self.ctx.apply_bindings(&self.bindings[5]);
+
+        self.ctx
+            .apply_uniforms(miniquad::UniformsSource::table(&shaders::UniformsHUD {
+                actcolor: (0.8, 0.0, 0.2, 1.0),
+            }));
+
+        self.ctx.draw(0, self.mesh[5].num * 6, 1);
+
         self.ctx.apply_pipeline(&self.pipeline[1]);
 
         self.ctx.apply_bindings(&self.bindings[1]);
@@ -639,7 +1312,18 @@ impl EventHandler for Stage {
                 fontcolor: self.overlay.font_col,
             }));
 
-        self.ctx.draw(0, self.mesh[1].num * 6 + 6, 1);
+        self.ctx.draw(0, self.mesh[1].num * 6, 1);
+
+        self.ctx.apply_pipeline(&self.pipeline[7]);
+
+        self.ctx.apply_bindings(&self.bindings[7]);
+
+        self.ctx
+            .apply_uniforms(miniquad::UniformsSource::table(&shaders::UniformsStatus {
+                fontcolor: self.hud.text.font_col,
+            }));
+
+        self.ctx.draw(0, self.mesh[7].num * 6, 1);
 
         self.ctx.apply_pipeline(&self.pipeline[3]);
 
@@ -678,10 +1362,37 @@ impl EventHandler for Stage {
     
             self.ctx.draw(0, self.mesh[2].num * 6, 1);    
         }
+
+        if self.settings.show_profiler {
+            self.ctx.apply_pipeline(&self.pipeline[8]);
+
+            self.ctx.apply_bindings(&self.bindings[8]);
+
+            self.ctx
+                .apply_uniforms(miniquad::UniformsSource::table(&shaders::UniformsOverlay {
+                    fontcolor: self.overlay.font_col,
+                }));
+
+            self.ctx.draw(0, self.mesh[8].num * 6, 1);
+        }
+
+        if self.console.open {
+            self.ctx.apply_pipeline(&self.pipeline[6]);
+
+            self.ctx.apply_bindings(&self.bindings[6]);
+
+            self.ctx
+                .apply_uniforms(miniquad::UniformsSource::table(&shaders::UniformsOverlay {
+                    fontcolor: self.overlay.font_col,
+                }));
+
+            self.ctx.draw(0, self.mesh[6].num * 6, 1);
+        }
         
         self.ctx.end_render_pass();
 
         self.ctx.commit_frame();
+        self.profiler.record(Section::Draw, start);
 
         self.time_state.last_frame = date::now();
 
@@ -693,12 +1404,40 @@ impl EventHandler for Stage {
     // ============================
 
     fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+        if keycode == KeyCode::GraveAccent {
+            self.console.toggle();
+            // keys held when the console opens would stay down
+            self.input_state.keys = InputState::init().keys;
+            return;
+        }
+        if self.console.open {
+            if let Some(command) = self.console.key(keycode, &mut self.settings) {
+                self.console_command(command);
+            }
+            return;
+        }
+        if self.messages.show_history {
+            match keycode {
+                KeyCode::PageUp => self.messages.scroll_history(messages::HISTORY_LINES as i32 / 2),
+                KeyCode::PageDown => self.messages.scroll_history(-(messages::HISTORY_LINES as i32) / 2),
+                _ => {}
+            }
+        }
         self.input_state.keys.read_key(keycode, true);
+        if let Some(nav) = Nav::from_key(keycode) {
+            self.input_state.nav.push(nav);
+        }
         if !self.input_state.apply_change {
             self.input_state.apply_change = true
         }
     }
 
+    fn char_event(&mut self, character: char, _keymods: KeyMods, _repeat: bool) {
+        if self.console.open {
+            self.console.type_char(character);
+        }
+    }
+
     fn key_up_event(&mut self, keycode: KeyCode, _keymods: KeyMods) {
         self.input_state.keys.read_key(keycode, false);
         self.input_state.apply_change = false
@@ -716,6 +1455,12 @@ impl EventHandler for Stage {
         }
     }
 
+    fn mouse_wheel_event(&mut self, _x: f32, y: f32) {
+        if self.messages.show_history {
+            self.messages.scroll_history(y.signum() as i32);
+        }
+    }
+
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
         self.input_state.mouse.x = x;
         self.input_state.mouse.y = y;
@@ -1,61 +1,361 @@
-use crate::{mesh::TextureUV, input, settings};
-use std::char;
-
-pub const WIDTH: f32 = 12.0;
-pub const HEIGHT: f32 = 20.0;
-pub const HEIGHT_CAP: f32 = 20.0;
-const X1: f32 = 3.0;
-const Y1: f32 = 2.0;
-const Y2: f32 = Y1+HEIGHT_CAP;
-const Y3: f32 = Y2+HEIGHT_CAP;
-const Y4: f32 = Y3+HEIGHT;
-const Y5: f32 = Y4+HEIGHT;
-const Y6: f32 = Y5+HEIGHT;
-const TEXWIDTH: f32 = 256.0;
-const TEXHEIGHT: f32 = 256.0;
+use image::{self, GenericImage, ImageBuffer, Rgba};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::mesh::TextureUV;
+use crate::richtext;
+
+// BMFont text descriptors, the index in this list is the font id
+const FONT_FILES: [&str; 6] = [
+    "assets/fonts/monospace.fnt",
+    "assets/fonts/dejavu_sans_20.fnt",
+    "assets/fonts/dejavu_sans_32.fnt",
+    "assets/fonts/dejavu_sans_mono_16.fnt",
+    "assets/fonts/dejavu_sans_bold_20.fnt",
+    "assets/fonts/dejavu_sans_oblique_20.fnt",
+];
+// The old fixed grid font, it also has the crosshair and the menu panel
+pub const CLASSIC: usize = 0;
+pub const SANS: usize = 1;
+pub const SANS_LARGE: usize = 2;
+pub const MONO: usize = 3;
+pub const SANS_BOLD: usize = 4;
+pub const SANS_ITALIC: usize = 5;
+// Regular, bold and italic faces of a family, the other fonts draw
+// emphasis with their regular face
+const FAMILIES: [(usize, usize, usize); 1] = [(SANS, SANS_BOLD, SANS_ITALIC)];
+
+// Menu panel background, in pixels on the page of the classic font
+const PANEL: TextureUV = TextureUV { u1: 0.0, u2: 128.0, v1: 128.0, v2: 256.0 };
+// Side of the white patch under the fonts, in pixels
+const SOLID: u32 = 4;
+// Drawn in turn for characters a font has no glyph for
+const FALLBACK: [char; 3] = ['\u{FFFD}', '?', ' '];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub xoffset: f32,
+    pub yoffset: f32,
+    pub xadvance: f32,
+    pub page: usize,
+}
+
+pub struct Font {
+    pub face: String,
+    pub size: f32,
+    pub line_height: f32,
+    pub base: f32,
+    pub glyphs: HashMap<char, Glyph>,
+    pub kerning: HashMap<(char, char), f32>,
+    // where each page of the font starts in the shared texture
+    pub pages: Vec<f32>,
+}
+
+impl Font {
+    // Reads a BMFont text descriptor, returns the font and its page image
+    // files in page order
+    pub fn parse(source: &str) -> (Font, Vec<String>) {
+        let mut font = Font {
+            face: String::new(),
+            size: 0.0,
+            line_height: 0.0,
+            base: 0.0,
+            glyphs: HashMap::new(),
+            kerning: HashMap::new(),
+            pages: Vec::new(),
+        };
+        let mut files = Vec::new();
+
+        for line in source.lines() {
+            let (tag, values) = fields(line);
+            let num = |key: &str| values.get(key).and_then(|v| v.parse::<f32>().ok()).unwrap_or(0.0);
+            let id = |key: &str| values.get(key).and_then(|v| v.parse::<u32>().ok()).and_then(char::from_u32);
+            match tag {
+                "info" => {
+                    font.face = values.get("face").unwrap_or(&"").to_string();
+                    font.size = num("size").abs();
+                }
+                "common" => {
+                    font.line_height = num("lineHeight");
+                    font.base = num("base");
+                }
+                "page" => files.push((num("id") as usize, values.get("file").unwrap_or(&"").to_string())),
+                "char" => {
+                    if let Some(c) = id("id") {
+                        font.glyphs.insert(c, Glyph {
+                            x: num("x"),
+                            y: num("y"),
+                            width: num("width"),
+                            height: num("height"),
+                            xoffset: num("xoffset"),
+                            yoffset: num("yoffset"),
+                            xadvance: num("xadvance"),
+                            page: num("page") as usize,
+                        });
+                    }
+                }
+                "kerning" => {
+                    if let (Some(first), Some(second)) = (id("first"), id("second")) {
+                        font.kerning.insert((first, second), num("amount"));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        files.sort_by_key(|(id, _)| *id);
+        (font, files.into_iter().map(|(_, file)| file).collect())
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| FALLBACK.iter().find_map(|f| self.glyphs.get(f)))
+    }
+
+    pub fn kern(&self, first: char, second: char) -> f32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0.0)
+    }
+
+    // Pen advance over the whole text, kerning included
+    pub fn width(&self, text: &str) -> f32 {
+        let mut width = 0.0;
+        let mut prev = None;
+        for c in text.chars() {
+            if let Some(p) = prev {
+                width += self.kern(p, c);
+            }
+            width += self.glyph(c).map(|g| g.xadvance).unwrap_or(0.0);
+            prev = Some(c);
+        }
+        width
+    }
+}
+
+// `char id=65 x=3 ...` into the tag and its values, which may be quoted
+fn fields(line: &str) -> (&str, HashMap<&str, &str>) {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+    let mut values = HashMap::new();
+    while let Some((key, after)) = rest.trim_start().split_once('=') {
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after.split_once(' ').unwrap_or((after, "")),
+        };
+        values.insert(key, value);
+        rest = after;
+    }
+    (tag, values)
+}
+
+// The face of the family of `font` for bold or italic text, bold wins
+// when both are asked for
+pub fn styled(font: usize, bold: bool, italic: bool) -> usize {
+    match FAMILIES.iter().find(|family| [family.0, family.1, family.2].contains(&font)) {
+        Some(&(_, b, _)) if bold => b,
+        Some(&(_, _, i)) if italic => i,
+        Some(&(regular, _, _)) => regular,
+        None => font,
+    }
+}
+
+// Every font with its pages stacked into one texture, so the overlay and
+// GUI pipelines keep binding a single image. A white patch under them
+// fills boxes drawn with the same pipelines.
+pub struct Fonts {
+    pub fonts: Vec<Font>,
+    pub atlas: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub solid: f32,
+}
+
+impl Fonts {
+    pub fn load() -> Fonts {
+        Fonts::load_from(&FONT_FILES)
+    }
+
+    // A missing descriptor leaves an empty font so the ids stay in place,
+    // a missing page is blank
+    pub fn load_from(paths: &[&str]) -> Fonts {
+        let mut fonts = Vec::new();
+        let mut pages: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = Vec::new();
+        let mut height = 0;
+
+        for path in paths {
+            let (mut font, files) = Font::parse(&fs::read_to_string(path).unwrap_or_default());
+            let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+            for file in files {
+                let page = match image::open(dir.join(file)) {
+                    Ok(image) => image.to_rgba8(),
+                    Err(_) => ImageBuffer::new(1, 1),
+                };
+                font.pages.push(height as f32);
+                height += page.height();
+                pages.push(page);
+            }
+            fonts.push(font);
+        }
+
+        let solid = height;
+        pages.push(ImageBuffer::from_pixel(SOLID, SOLID, Rgba([255, 255, 255, 255])));
+        height += SOLID;
+
+        let width = pages.iter().map(|page| page.width()).max().unwrap_or(1);
+        let mut atlas = ImageBuffer::new(width, height);
+        let mut y = 0;
+        for page in pages {
+            atlas.copy_from(&page, 0, y).ok();
+            y += page.height();
+        }
+
+        Fonts { fonts, atlas, solid: solid as f32 }
+    }
+
+    pub fn font(&self, font: usize) -> &Font {
+        &self.fonts[font]
+    }
+
+    pub fn width(&self, font: usize, text: &str) -> f32 {
+        self.font(font).width(text)
+    }
+
+    pub fn line_height(&self, font: usize) -> f32 {
+        self.font(font).line_height
+    }
+
+    // Characters of `text` one of `fonts` has no glyph for, they would be
+    // drawn as a replacement
+    pub fn missing(&self, fonts: &[usize], text: &str) -> Vec<char> {
+        let mut missing: Vec<char> = Vec::new();
+        for c in text.chars().filter(|c| !c.is_control()) {
+            if !missing.contains(&c) && fonts.iter().any(|&font| !self.font(font).glyphs.contains_key(&c)) {
+                missing.push(c);
+            }
+        }
+        missing
+    }
+
+    // Where a glyph of `font` is in the texture, in 0..1 coordinates
+    pub fn uv(&self, font: usize, glyph: &Glyph) -> TextureUV {
+        let top = self.font(font).pages.get(glyph.page).copied().unwrap_or(0.0);
+        self.normalized(TextureUV {
+            u1: glyph.x,
+            u2: glyph.x + glyph.width,
+            v1: top + glyph.y,
+            v2: top + glyph.y + glyph.height,
+        })
+    }
+
+    pub fn panel_uv(&self) -> TextureUV {
+        let top = self.font(CLASSIC).pages.first().copied().unwrap_or(0.0);
+        self.normalized(TextureUV { v1: top + PANEL.v1, v2: top + PANEL.v2, ..PANEL })
+    }
+
+    // The middle of the white patch, away from its edges
+    pub fn solid_uv(&self) -> TextureUV {
+        let (a, b) = (1.0, SOLID as f32 - 1.0);
+        self.normalized(TextureUV { u1: a, u2: b, v1: self.solid + a, v2: self.solid + b })
+    }
+
+    // Screen rectangles, (left, top, right, bottom) in pixels, and texture
+    // coordinates of the glyphs of a line whose top left corner is at x, y
+    pub fn quads(&self, font: usize, text: &str, x: f32, y: f32, scale: f32) -> Vec<((f32, f32, f32, f32), TextureUV)> {
+        let f = self.font(font);
+        let mut quads = Vec::new();
+        // whole pixels keep the glyphs sharp with nearest filtering
+        let mut pen = x.round();
+        let y = y.round();
+        let mut prev = None;
+        for c in text.chars() {
+            if let Some(p) = prev {
+                pen += f.kern(p, c) * scale;
+            }
+            prev = Some(c);
+            let Some(glyph) = f.glyph(c) else { continue };
+            if glyph.width > 0.0 && glyph.height > 0.0 {
+                let x1 = pen + glyph.xoffset * scale;
+                let y1 = y + glyph.yoffset * scale;
+                let rect = (x1, y1, x1 + glyph.width * scale, y1 + glyph.height * scale);
+                quads.push((rect, self.uv(font, glyph)));
+            }
+            pen += glyph.xadvance * scale;
+        }
+        quads
+    }
+
+    fn normalized(&self, mut uv: TextureUV) -> TextureUV {
+        uv.normalize(self.atlas.width() as f32, self.atlas.height() as f32);
+        uv
+    }
+}
 
 pub struct Overlay {
     pub lines: Vec<String>,
     pub line_width: Vec<f32>,
     pub line_x: Vec<f32>,
     pub line_y: Vec<f32>,
+    pub line_font: Vec<usize>,
+    pub line_tint: Vec<Option<richtext::Color>>,
     pub font_col: (f32, f32, f32, f32),
     pub x0: f32,
     pub y0: f32,
     pub scale: f32,
     pub line_height: f32,
+    pub font: usize,
 }
 
 impl Overlay {
-    pub fn new_from(lines: Vec<&str>) -> Overlay {
+    // One line of markup per line, none of them wraps
+    pub fn new_from(fonts: &Fonts, lines: Vec<&str>) -> Overlay {
         let x0 = 20.0;
         let y0 = 20.0;
         let scale = 1.0;
-        let line_height = HEIGHT*scale;
-        let mut lines1 = Vec::new();
-        let mut line_width = Vec::new();
-        let mut line_x = Vec::new();
-        let mut line_y = Vec::new();
-        for l in 0..lines.len() {
-            lines1.push(lines[l].to_string());
-            let letters: Vec<char> = lines[l].chars().collect();
-            line_width.push(letters.len() as f32 * WIDTH * scale);
-            line_x.push(x0*scale);
-            line_y.push(y0*scale + (l as f32)*line_height)
-        }
-        let overlay = Overlay {
-            lines: lines1,
-            line_width,
-            line_x,
-            line_y,
+        let font = MONO;
+        let mut overlay = Overlay {
+            lines: Vec::new(),
+            line_width: Vec::new(),
+            line_x: Vec::new(),
+            line_y: Vec::new(),
+            line_font: Vec::new(),
+            line_tint: Vec::new(),
             font_col: (0.9960784, 0.7607843, 0.5568627, 1.0),
             x0,
             y0,
             scale,
-            line_height,
+            line_height: fonts.line_height(font)*scale,
+            font,
         };
+        let mut y = y0*scale;
+        for line in lines {
+            let text_box = richtext::TextBox::new(fonts, line, font, None, richtext::Align::Left);
+            overlay.push(&text_box, x0*scale, y);
+            y += text_box.height*scale;
+        }
         overlay
     }
+
+    // Adds the runs of a text box with its top left corner at x, y
+    pub fn push(&mut self, text_box: &richtext::TextBox, x: f32, y: f32) {
+        for run in text_box.lines.iter().flat_map(|line| &line.runs) {
+            self.lines.push(run.text.clone());
+            self.line_width.push(run.width*self.scale);
+            self.line_x.push(x + run.x*self.scale);
+            self.line_y.push(y + run.y*self.scale);
+            self.line_font.push(run.style.font);
+            self.line_tint.push(run.style.color);
+        }
+    }
+
+    // Colours the runs from `first` on that have no colour of their own
+    // and fades all of them by `alpha`
+    pub fn tint(&mut self, first: usize, color: Option<richtext::Color>, alpha: f32) {
+        for tint in self.line_tint[first..].iter_mut() {
+            let (r, g, b, a) = tint.or(color).unwrap_or(self.font_col);
+            *tint = Some((r, g, b, a * alpha));
+        }
+    }
 }
 
 pub struct GUI {
@@ -64,9 +364,10 @@ pub struct GUI {
     pub line_x: Vec<f32>,
     pub line_y: Vec<f32>,
     pub line_active: Vec<i32>,
+    pub line_font: Vec<usize>,
+    pub line_tint: Vec<Option<richtext::Color>>,
     pub font_col: (f32, f32, f32, f32),
     pub act_col: (f32, f32, f32, f32),
-    pub act_no: usize,
     pub x0: f32,
     pub y0: f32,
     pub max_width: f32,
@@ -76,221 +377,66 @@ pub struct GUI {
 }
 
 impl GUI {
-    pub fn new_from(lines: Vec<&str>, width: f32, height: f32) -> GUI {
+    // The lines centred on the screen both ways
+    pub fn new_from(fonts: &Fonts, lines: Vec<&str>, width: f32, height: f32) -> GUI {
         let scale = 1.0;
-        let line_height = HEIGHT*scale;
-        let x0 = width*0.5;
-        let y0 = (height - (lines.len() as f32 - 1.0)*line_height)*0.5/scale;
-        let mut lines1 = Vec::new();
-        let mut line_width = Vec::new();
-        let mut line_x = Vec::new();
-        let mut line_y = Vec::new();
-        let mut line_active = Vec::new();
-        for l in 0..lines.len() {
-            lines1.push(lines[l].to_string());
-            let letters: Vec<char> = lines[l].chars().collect();
-            line_width.push(letters.len() as f32 * WIDTH * scale);
-            line_x.push(x0*scale);
-            line_y.push(y0*scale + (l as f32)*line_height);
-            line_active.push(0);
-        }
-        let max_width = vec_max(&line_width);
-        let mut gui = GUI {
-            lines: lines1,
-            line_width,
-            line_x,
-            line_y,
-            line_active,
+        let text_box = richtext::TextBox::new(fonts, &lines.join("\n"), SANS, None, richtext::Align::Center);
+        let x0 = (width - text_box.width*scale)*0.5;
+        let y0 = (height - text_box.height*scale)*0.5;
+        let runs: Vec<&richtext::Run> = text_box.lines.iter().flat_map(|line| &line.runs).collect();
+        GUI {
+            lines: runs.iter().map(|run| run.text.clone()).collect(),
+            line_width: runs.iter().map(|run| run.width*scale).collect(),
+            line_x: runs.iter().map(|run| x0 + run.x*scale).collect(),
+            line_y: runs.iter().map(|run| y0 + run.y*scale).collect(),
+            line_active: vec![0; runs.len()],
+            line_font: runs.iter().map(|run| run.style.font).collect(),
+            line_tint: runs.iter().map(|run| run.style.color).collect(),
             font_col: (0.14117647, 0.07843137, 0.13333333, 1.0),
             act_col: (0.9960784, 0.7607843, 0.5568627, 1.0),
-            act_no: 0,
             x0,
             y0,
-            max_width,
+            max_width: text_box.width*scale,
             scale,
-            line_height,
+            line_height: fonts.line_height(SANS)*scale,
             show: true,
-        };
-        gui.center();
-        gui
-    }
-
-    fn center(&mut self) {
-        self.x0 = self.x0 - 0.5*self.max_width;
-        for l in 0..self.lines.len() {
-            self.line_x[l] = self.x0 + 0.5*self.max_width - 0.5*self.line_width[l]
         }
     }
-
-    pub fn gui_control(&mut self, input_state: &input::InputState, settings: &mut settings::Settings) -> bool {
-        let mut request = false;
-        if input_state.mouse.left && input_state.apply_change {
-            if self.act_no == self.lines.len() {
-                request = true;
-                miniquad::window::quit()
-            }
-            if self.act_no == 1 {
-                request = true;
-                self.show = false
-            }
-            if self.act_no == 3 && !settings.full_screen {
-                request = true;
-                miniquad::window::set_fullscreen(true);
-                let screen = miniquad::window::screen_size();
-                settings.full_screen = true;
-                settings.screen_change(screen.0, screen.1);
-            }
-            if self.act_no == 4 {
-                request = true;
-                settings.light_dist += 1.0*settings.player_speed;
-            }
-            if self.act_no == 5 {
-                request = true;
-                settings.light_dist -= 1.0*settings.player_speed;
-            }
-            if self.act_no == 6 {
-                request = true;
-                settings.music_playing = !settings.music_playing
-            }
-        }
-        request
-    }
-
-    pub fn gui_highlight(&mut self, x: f32, y: f32) {
-        let mut some_active = false;
-        for l in 0..self.lines.len() {
-            if x > self.line_x[l]
-                && x < self.line_x[l] + self.line_width[l]
-                && y > self.line_y[l]
-                && y < self.line_y[l] + self.line_height
-            {
-                self.act_no = l + 1;
-                self.line_active[l] = 1;
-                some_active = true
-            }
-        }
-
-        if !some_active {
-            self.act_no = 0;
-        }
-    }
-}
-
-fn char_to_uv(c: char) -> TextureUV {
-    let tex;
-
-    match c {
-        'A' => tex = TextureUV{u1: X1, u2: X1+WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'B' => tex = TextureUV{u1: X1+WIDTH, u2: X1+2.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'C' => tex = TextureUV{u1: X1+2.0*WIDTH, u2: X1+3.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'D' => tex = TextureUV{u1: X1+3.0*WIDTH, u2: X1+4.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'E' => tex = TextureUV{u1: X1+4.0*WIDTH, u2: X1+5.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'F' => tex = TextureUV{u1: X1+5.0*WIDTH, u2: X1+6.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'G' => tex = TextureUV{u1: X1+6.0*WIDTH, u2: X1+7.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'H' => tex = TextureUV{u1: X1+7.0*WIDTH, u2: X1+8.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'I' => tex = TextureUV{u1: X1+8.0*WIDTH, u2: X1+9.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'J' => tex = TextureUV{u1: X1+9.0*WIDTH, u2: X1+10.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'K' => tex = TextureUV{u1: X1+10.0*WIDTH, u2: X1+11.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'L' => tex = TextureUV{u1: X1+11.0*WIDTH, u2: X1+12.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        'M' => tex = TextureUV{u1: X1+12.0*WIDTH, u2: X1+13.0*WIDTH, v1: Y1, v2: Y1+HEIGHT_CAP},
-        '=' => tex = TextureUV{u1: 0.0, u2: 128.0, v1: 128.0, v2: 256.0},
-        'N' => tex = TextureUV{u1: X1+0.0*WIDTH, u2: X1+1.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'O' => tex = TextureUV{u1: X1+1.0*WIDTH, u2: X1+2.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'P' => tex = TextureUV{u1: X1+2.0*WIDTH, u2: X1+3.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'Q' => tex = TextureUV{u1: X1+3.0*WIDTH, u2: X1+4.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'R' => tex = TextureUV{u1: X1+4.0*WIDTH, u2: X1+5.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'S' => tex = TextureUV{u1: X1+5.0*WIDTH, u2: X1+6.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'T' => tex = TextureUV{u1: X1+6.0*WIDTH, u2: X1+7.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'U' => tex = TextureUV{u1: X1+7.0*WIDTH, u2: X1+8.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'V' => tex = TextureUV{u1: X1+8.0*WIDTH, u2: X1+9.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'W' => tex = TextureUV{u1: X1+9.0*WIDTH, u2: X1+10.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'X' => tex = TextureUV{u1: X1+10.0*WIDTH, u2: X1+11.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'Y' => tex = TextureUV{u1: X1+11.0*WIDTH, u2: X1+12.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'Z' => tex = TextureUV{u1: X1+12.0*WIDTH, u2: X1+13.0*WIDTH, v1: Y2, v2: Y2+HEIGHT_CAP},
-        'a' => tex = TextureUV{u1: X1+0.0*WIDTH, u2: X1+1.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'b' => tex = TextureUV{u1: X1+1.0*WIDTH, u2: X1+2.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'c' => tex = TextureUV{u1: X1+2.0*WIDTH, u2: X1+3.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'd' => tex = TextureUV{u1: X1+3.0*WIDTH, u2: X1+4.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'e' => tex = TextureUV{u1: X1+4.0*WIDTH, u2: X1+5.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'f' => tex = TextureUV{u1: X1+5.0*WIDTH, u2: X1+6.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'g' => tex = TextureUV{u1: X1+6.0*WIDTH, u2: X1+7.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'h' => tex = TextureUV{u1: X1+7.0*WIDTH, u2: X1+8.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'i' => tex = TextureUV{u1: X1+8.0*WIDTH, u2: X1+9.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'j' => tex = TextureUV{u1: X1+9.0*WIDTH, u2: X1+10.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'k' => tex = TextureUV{u1: X1+10.0*WIDTH, u2: X1+11.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'l' => tex = TextureUV{u1: X1+11.0*WIDTH, u2: X1+12.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'm' => tex = TextureUV{u1: X1+12.0*WIDTH, u2: X1+13.0*WIDTH, v1: Y3, v2: Y3+HEIGHT},
-        'n' => tex = TextureUV{u1: X1+0.0*WIDTH, u2: X1+1.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'o' => tex = TextureUV{u1: X1+1.0*WIDTH, u2: X1+2.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'p' => tex = TextureUV{u1: X1+2.0*WIDTH, u2: X1+3.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'q' => tex = TextureUV{u1: X1+3.0*WIDTH, u2: X1+4.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'r' => tex = TextureUV{u1: X1+4.0*WIDTH, u2: X1+5.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        's' => tex = TextureUV{u1: X1+5.0*WIDTH, u2: X1+6.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        't' => tex = TextureUV{u1: X1+6.0*WIDTH, u2: X1+7.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'u' => tex = TextureUV{u1: X1+7.0*WIDTH, u2: X1+8.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'v' => tex = TextureUV{u1: X1+8.0*WIDTH, u2: X1+9.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'w' => tex = TextureUV{u1: X1+9.0*WIDTH, u2: X1+10.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'x' => tex = TextureUV{u1: X1+10.0*WIDTH, u2: X1+11.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'y' => tex = TextureUV{u1: X1+11.0*WIDTH, u2: X1+12.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        'z' => tex = TextureUV{u1: X1+12.0*WIDTH, u2: X1+13.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        '*' => tex = TextureUV{u1: X1+13.0*WIDTH, u2: X1+14.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        '“' => tex = TextureUV{u1: X1+14.0*WIDTH, u2: X1+15.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        '”' => tex = TextureUV{u1: X1+15.0*WIDTH, u2: X1+16.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        '!' => tex = TextureUV{u1: X1+16.0*WIDTH, u2: X1+17.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        '’' => tex = TextureUV{u1: X1+17.0*WIDTH, u2: X1+18.0*WIDTH, v1: Y4, v2: Y4+HEIGHT},
-        '●' => tex = TextureUV{u1: X1+18.0*WIDTH+1.0, u2: X1+19.0*WIDTH-1.0, v1: 68.0, v2: 78.0},
-        '1' => tex = TextureUV{u1: X1+0.0*WIDTH, u2: X1+1.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '2' => tex = TextureUV{u1: X1+1.0*WIDTH, u2: X1+2.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '3' => tex = TextureUV{u1: X1+2.0*WIDTH, u2: X1+3.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '4' => tex = TextureUV{u1: X1+3.0*WIDTH, u2: X1+4.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '5' => tex = TextureUV{u1: X1+4.0*WIDTH, u2: X1+5.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '6' => tex = TextureUV{u1: X1+5.0*WIDTH, u2: X1+6.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '7' => tex = TextureUV{u1: X1+6.0*WIDTH, u2: X1+7.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '8' => tex = TextureUV{u1: X1+7.0*WIDTH, u2: X1+8.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '9' => tex = TextureUV{u1: X1+8.0*WIDTH, u2: X1+9.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '0' => tex = TextureUV{u1: X1+9.0*WIDTH, u2: X1+10.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        ' ' => tex = TextureUV{u1: X1+10.0*WIDTH, u2: X1+11.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '.' => tex = TextureUV{u1: X1+11.0*WIDTH, u2: X1+12.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        ':' => tex = TextureUV{u1: X1+12.0*WIDTH, u2: X1+13.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        ',' => tex = TextureUV{u1: X1+13.0*WIDTH, u2: X1+14.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '?' => tex = TextureUV{u1: X1+14.0*WIDTH, u2: X1+15.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '(' => tex = TextureUV{u1: X1+15.0*WIDTH, u2: X1+16.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        ')' => tex = TextureUV{u1: X1+16.0*WIDTH, u2: X1+17.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        ';' => tex = TextureUV{u1: X1+17.0*WIDTH, u2: X1+18.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-        '■' => tex = TextureUV{u1: X1+18.0*WIDTH+1.0, u2: X1+19.0*WIDTH-1.0, v1: 88.0, v2: 98.0},
-        '+' => tex = TextureUV{u1: X1+13.0*WIDTH, u2: X1+14.0*WIDTH, v1: Y6, v2: Y6+HEIGHT},
-        '&' => tex = TextureUV{u1: X1+14.0*WIDTH, u2: X1+15.0*WIDTH, v1: Y6, v2: Y6+HEIGHT},
-        '#' => tex = TextureUV{u1: X1+15.0*WIDTH, u2: X1+16.0*WIDTH, v1: Y6, v2: Y6+HEIGHT},
-        '-' => tex = TextureUV{u1: X1+16.0*WIDTH, u2: X1+17.0*WIDTH, v1: Y6, v2: Y6+HEIGHT},
-        '>' => tex = TextureUV{u1: X1+17.0*WIDTH, u2: X1+18.0*WIDTH, v1: Y6, v2: Y6+HEIGHT},
-        '<' => tex = TextureUV{u1: X1+18.0*WIDTH, u2: X1+19.0*WIDTH, v1: Y6, v2: Y6+HEIGHT},
-        _ => tex = TextureUV{u1: X1+10.0*WIDTH, u2: X1+11.0*WIDTH, v1: Y5, v2: Y5+HEIGHT},
-    }
-
-    tex
 }
 
-pub fn string_to_uv(text: &str) -> Vec<TextureUV> {
-
-    let mut coords = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let letters: Vec<char> = text.chars().collect();
+    const SOURCE: &str = r#"info face="Test Sans" size=-20 bold=0 italic=0
+common lineHeight=24 base=19 scaleW=64 scaleH=64 pages=2
+page id=1 file="second.png"
+page id=0 file="first.png"
+chars count=3
+char id=65 x=1 y=2 width=10 height=14 xoffset=0 yoffset=3 xadvance=11 page=0 chnl=15
+char id=86 x=12 y=2 width=10 height=14 xoffset=0 yoffset=3 xadvance=10 page=1 chnl=15
+char id=63 x=23 y=2 width=8 height=14 xoffset=1 yoffset=3 xadvance=9 page=0 chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-2
+"#;
 
-    for letter in letters {
-        coords.push(char_to_uv(letter))
+    #[test]
+    fn parses_descriptor() {
+        let (font, files) = Font::parse(SOURCE);
+        assert_eq!(font.face, "Test Sans");
+        assert_eq!(font.size, 20.0);
+        assert_eq!(font.line_height, 24.0);
+        assert_eq!(files, vec!["first.png", "second.png"]);
+        assert_eq!(font.glyphs.len(), 3);
+        assert_eq!(font.glyphs[&'V'].page, 1);
     }
 
-    for c in 0..coords.len() {
-        coords[c].normalize(TEXWIDTH, TEXHEIGHT);
+    #[test]
+    fn width_kerns_and_falls_back() {
+        let (font, _) = Font::parse(SOURCE);
+        assert_eq!(font.width("AV"), 11.0 - 2.0 + 10.0);
+        assert_eq!(font.width("VA"), 10.0 + 11.0);
+        // no glyph for Я, drawn as ?
+        assert_eq!(font.width("AЯ"), 11.0 + 9.0);
     }
-
-    coords
 }
-
-fn vec_max(vect: &Vec<f32>) -> f32{
-    let mut m = 0.0;
-    for e in 0..vect.len() {
-        if m < vect[e] {m = vect[e]}
-    }
-    m
-}
\ No newline at end of file
@@ -0,0 +1,299 @@
+use crate::entities;
+use crate::map;
+use crate::movers;
+use crate::player;
+use crate::settings;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    Enter,
+    Use,
+    Timer(f32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Message(String),
+    // a message in the middle of the screen
+    Announce(String),
+    Open(usize, usize),
+    Sound(String),
+    // without an angle the player keeps their heading
+    Teleport(f32, f32, Option<f32>),
+    Spawn(entities::EntityKind, f32, f32),
+    Script(String),
+    EndLevel,
+}
+
+// Trigger region in tiles, corners included
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub i1: usize,
+    pub j1: usize,
+    pub i2: usize,
+    pub j2: usize,
+    pub condition: Condition,
+    pub actions: Vec<Action>,
+    pub repeat: bool,
+    pub fired: bool,
+    pub inside: bool,
+    pub time: f32,
+}
+
+impl Trigger {
+    pub fn new(i1: usize, j1: usize, i2: usize, j2: usize, condition: Condition) -> Trigger {
+        Trigger {
+            i1: i1.min(i2),
+            j1: j1.min(j2),
+            i2: i1.max(i2),
+            j2: j1.max(j2),
+            condition,
+            actions: Vec::new(),
+            repeat: false,
+            fired: false,
+            inside: false,
+            time: 0.0,
+        }
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let i = x.floor() as i32;
+        let j = y.floor() as i32;
+        i >= self.i1 as i32 && i <= self.i2 as i32 && j >= self.j1 as i32 && j <= self.j2 as i32
+    }
+
+    // Timers count while the player stays inside the region
+    fn check(&mut self, player: &player::Player, using: bool, dt: f32) -> bool {
+        let inside = self.contains(player.position.x, player.position.y);
+        let entered = inside && !self.inside;
+        self.inside = inside;
+
+        if self.fired && !self.repeat {
+            return false;
+        }
+
+        let fire = match self.condition {
+            Condition::Enter => entered,
+            Condition::Use => inside && using,
+            Condition::Timer(period) => {
+                if inside {
+                    self.time += dt;
+                }
+                if self.time >= period {
+                    self.time -= period;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if fire {
+            self.fired = true;
+        }
+        fire
+    }
+}
+
+#[derive(Default)]
+pub struct Triggers {
+    pub triggers: Vec<Trigger>,
+}
+
+impl Triggers {
+    pub fn new() -> Triggers {
+        Triggers {
+            triggers: Vec::new(),
+        }
+    }
+
+    // Evaluated once per simulation tick, returns the actions of every
+    // trigger that fired in order.
+    pub fn update(&mut self, player: &player::Player, using: bool, dt: f32) -> Vec<Action> {
+        if !player.status.alive {
+            return Vec::new();
+        }
+        let mut actions = Vec::new();
+        for trigger in self.triggers.iter_mut() {
+            if trigger.check(player, using, dt) {
+                actions.extend(trigger.actions.iter().cloned());
+            }
+        }
+        actions
+    }
+}
+
+// Applies the actions that only touch the simulation and hands back the
+// ones the front end has to deal with (messages, sounds, scripts, level end).
+// Teleports off the map or into a wall are dropped.
+pub fn run(
+    actions: Vec<Action>,
+    settings: &settings::Settings,
+    game_map: &map::GameMap,
+    player: &mut player::Player,
+    movers: &mut movers::Movers,
+    entity_list: &mut entities::EntityList,
+) -> Vec<Action> {
+    let mut rest = Vec::new();
+    for action in actions {
+        match action {
+            Action::Open(i, j) => movers.open_at(i, j),
+            Action::Teleport(x, y, a) => {
+                if game_map.unplaceable(x, y).is_none() {
+                    player.place(x, y, a.unwrap_or(player.position.a), settings);
+                }
+            }
+            Action::Spawn(kind, x, y) => {
+                entity_list.spawn(kind, x, y);
+            }
+            _ => rest.push(action),
+        }
+    }
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (map::GameMap, settings::Settings, player::Player) {
+        let game_map = map::GameMap::empty();
+        let settings = settings::Settings::init();
+        let mut player = player::Player::new(&settings);
+        player.place(10.5, 10.5, 0.0, &settings);
+        (game_map, settings, player)
+    }
+
+    // Walks the player forward for `ticks` simulation ticks and collects
+    // everything the triggers fired on the way.
+    fn walk(
+        ticks: usize,
+        game_map: &map::GameMap,
+        settings: &settings::Settings,
+        player: &mut player::Player,
+        triggers: &mut Triggers,
+    ) -> Vec<Action> {
+        let mut actions = Vec::new();
+        player.movement.dir.f = true;
+        for _ in 0..ticks {
+            player.walk(game_map, settings, 0.0, 0.0, false);
+            actions.extend(triggers.update(player, false, settings.delta_time));
+        }
+        player.movement.dir.f = false;
+        actions
+    }
+
+    #[test]
+    fn enter_fires_once() {
+        let (game_map, settings, mut player) = setup();
+        let mut triggers = Triggers::new();
+        let mut trigger = Trigger::new(14, 9, 15, 11, Condition::Enter);
+        trigger.actions.push(Action::Message("Hello".to_string()));
+        triggers.triggers.push(trigger);
+
+        let actions = walk(30, &game_map, &settings, &mut player, &mut triggers);
+        assert_eq!(actions, vec![Action::Message("Hello".to_string())]);
+        assert!(player.position.x > 16.0);
+
+        player.place(10.5, 10.5, 0.0, &settings);
+        let actions = walk(30, &game_map, &settings, &mut player, &mut triggers);
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn repeat_fires_on_every_entry() {
+        let (game_map, settings, mut player) = setup();
+        let mut triggers = Triggers::new();
+        let mut trigger = Trigger::new(14, 9, 15, 11, Condition::Enter);
+        trigger.repeat = true;
+        trigger.actions.push(Action::Sound("alarm".to_string()));
+        triggers.triggers.push(trigger);
+
+        walk(30, &game_map, &settings, &mut player, &mut triggers);
+        player.place(10.5, 10.5, 0.0, &settings);
+        let actions = walk(30, &game_map, &settings, &mut player, &mut triggers);
+        assert_eq!(actions, vec![Action::Sound("alarm".to_string())]);
+    }
+
+    #[test]
+    fn use_needs_player_inside() {
+        let (_, settings, player) = setup();
+        let mut triggers = Triggers::new();
+        let mut trigger = Trigger::new(10, 10, 10, 10, Condition::Use);
+        trigger.actions.push(Action::EndLevel);
+        triggers.triggers.push(trigger);
+
+        assert!(triggers.update(&player, false, settings.delta_time).is_empty());
+        assert_eq!(triggers.update(&player, true, settings.delta_time), vec![Action::EndLevel]);
+
+        let mut triggers = Triggers::new();
+        triggers.triggers.push(Trigger::new(20, 20, 21, 21, Condition::Use));
+        triggers.triggers[0].actions.push(Action::EndLevel);
+        assert!(triggers.update(&player, true, settings.delta_time).is_empty());
+    }
+
+    #[test]
+    fn timer_counts_while_inside() {
+        let (_, settings, player) = setup();
+        let mut triggers = Triggers::new();
+        let mut trigger = Trigger::new(0, 0, 255, 255, Condition::Timer(1.0));
+        trigger.repeat = true;
+        trigger.actions.push(Action::Message("Tick".to_string()));
+        triggers.triggers.push(trigger);
+
+        let mut fired = 0;
+        for _ in 0..150 {
+            fired += triggers.update(&player, false, settings.delta_time).len();
+        }
+        assert_eq!(fired, 2);
+    }
+
+    #[test]
+    fn run_applies_simulation_actions() {
+        let (game_map, settings, mut player) = setup();
+        let mut movers = movers::Movers::new();
+        let mut entity_list = entities::EntityList::new();
+        let mut triggers = Triggers::new();
+        let mut trigger = Trigger::new(14, 9, 15, 11, Condition::Enter);
+        trigger.actions = vec![
+            Action::Spawn(entities::EntityKind::Barrel, 30.5, 30.5),
+            Action::Teleport(40.5, 40.5, Some(1.0)),
+            Action::EndLevel,
+        ];
+        triggers.triggers.push(trigger);
+
+        let actions = walk(30, &game_map, &settings, &mut player, &mut triggers);
+        let rest = run(actions, &settings, &game_map, &mut player, &mut movers, &mut entity_list);
+
+        assert_eq!(rest, vec![Action::EndLevel]);
+        assert_eq!(entity_list.count(entities::EntityKind::Barrel), 1);
+        assert_eq!((player.position.x, player.position.y), (40.5, 40.5));
+    }
+
+    #[test]
+    fn teleports_off_the_map_or_into_walls_are_dropped() {
+        let (mut game_map, settings, mut player) = setup();
+        game_map.wall_bot_array[20][20] = 0;
+        let mut movers = movers::Movers::new();
+        let mut entity_list = entities::EntityList::new();
+        let actions = vec![Action::Teleport(300.5, 300.5, None), Action::Teleport(20.5, 20.5, None)];
+        run(actions, &settings, &game_map, &mut player, &mut movers, &mut entity_list);
+        assert_eq!((player.position.x, player.position.y), (10.5, 10.5));
+    }
+
+    #[test]
+    fn walls_stop_the_player_short_of_a_trigger() {
+        let (mut game_map, settings, mut player) = setup();
+        for j in 0..settings::MAPSIZE {
+            game_map.wall_bot_array[13][j] = 0;
+        }
+        let mut triggers = Triggers::new();
+        let mut trigger = Trigger::new(14, 9, 15, 11, Condition::Enter);
+        trigger.actions.push(Action::EndLevel);
+        triggers.triggers.push(trigger);
+
+        let actions = walk(60, &game_map, &settings, &mut player, &mut triggers);
+        assert!(actions.is_empty());
+        assert!(player.position.x < 13.0);
+    }
+}
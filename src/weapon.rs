@@ -0,0 +1,206 @@
+use crate::camera;
+use crate::entities;
+use crate::map;
+use crate::player;
+use crate::settings;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feedback {
+    None,
+    Hit,
+    Miss,
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShotResult {
+    Hit(entities::EntityId, f32),
+    Miss(f32),
+}
+
+pub struct Weapon {
+    pub damage: f32,
+    pub fire_rate: f32,
+    pub range: f32,
+    pub ammo: i32,
+    pub max_ammo: i32,
+    pub cooldown: f32,
+    pub shot_time: f32,
+    pub feedback: Feedback,
+    pub feedback_time: f32,
+}
+
+impl Weapon {
+    pub fn init() -> Weapon {
+        Weapon {
+            damage: 15.0,
+            fire_rate: 4.0,
+            range: 64.0,
+            ammo: 50,
+            max_ammo: 200,
+            cooldown: 0.0,
+            shot_time: 1.0,
+            feedback: Feedback::None,
+            feedback_time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+        self.shot_time += dt;
+        self.feedback_time = (self.feedback_time - dt).max(0.0);
+        if self.feedback_time == 0.0 {
+            self.feedback = Feedback::None;
+        }
+    }
+
+    // 0 = idle, 1 = muzzle flash, 2 = recoil
+    pub fn frame(&self) -> usize {
+        if self.shot_time < 0.06 {
+            1
+        } else if self.shot_time < 0.14 {
+            2
+        } else {
+            0
+        }
+    }
+
+    pub fn trigger(
+        &mut self,
+        game_map: &map::GameMap,
+        entity_list: &mut entities::EntityList,
+        player: &player::Player,
+    ) -> Option<ShotResult> {
+        if self.cooldown > 0.0 {
+            return None;
+        }
+        self.cooldown = 1.0 / self.fire_rate;
+
+        if self.ammo <= 0 {
+            self.feedback = Feedback::Empty;
+            self.feedback_time = 0.3;
+            return None;
+        }
+        self.ammo -= 1;
+        self.shot_time = 0.0;
+
+        let result = hitscan(game_map, entity_list, player, self.range);
+
+        match result {
+            ShotResult::Hit(id, _) => {
+                if let Some(entity) = entity_list.get_mut(id) {
+                    entity.damage(self.damage, player);
+                }
+                self.feedback = Feedback::Hit;
+                self.feedback_time = 0.2;
+            }
+            ShotResult::Miss(dist) => {
+                let d = (dist - 0.05).max(0.0);
+                let z = player.position.z + d * player.position.bz / player.position.bxy;
+                let id = entity_list.spawn(
+                    entities::EntityKind::Puff,
+                    player.position.x + d * player.position.ax,
+                    player.position.y + d * player.position.ay,
+                );
+                if let Some(puff) = id.and_then(|id| entity_list.get_mut(id)) {
+                    puff.position.z = (z - 0.15).clamp(0.0, 1.7);
+                }
+                self.feedback = Feedback::Miss;
+                self.feedback_time = 0.2;
+            }
+        }
+
+        Some(result)
+    }
+}
+
+// Casts a ray along the view direction and returns the closest shootable
+// entity in front of the first wall, or the wall distance on a miss.
+pub fn hitscan(
+    game_map: &map::GameMap,
+    entity_list: &entities::EntityList,
+    player: &player::Player,
+    range: f32,
+) -> ShotResult {
+    let xp = player.position.x;
+    let yp = player.position.y;
+    let ax = player.position.ax;
+    let ay = player.position.ay;
+    let slope = player.position.bz / player.position.bxy.max(settings::TOLERANCE);
+
+    let mut wall = camera::ray_distance(game_map, xp, yp, ax, ay, range);
+    if slope < -settings::TOLERANCE {
+        wall = wall.min(-player.position.z / slope);
+    } else if slope > settings::TOLERANCE {
+        wall = wall.min((2.0 - player.position.z) / slope);
+    }
+
+    let mut best: Option<(entities::EntityId, f32)> = None;
+
+    for entity in entity_list.entities.iter() {
+        if entity.health.is_none() {
+            continue;
+        }
+        let (radius, height) = match (entity.collider, entity.sprite) {
+            (Some(c), Some(s)) => (c.radius.max(0.5 * s.width), s.height),
+            (Some(c), None) => (c.radius, 1.0),
+            _ => continue,
+        };
+        let dx = entity.position.x - xp;
+        let dy = entity.position.y - yp;
+        let t = dx * ax + dy * ay;
+        if t <= 0.0 || t > wall {
+            continue;
+        }
+        let side = (dx * ay - dy * ax).abs();
+        if side > radius {
+            continue;
+        }
+        let z = player.position.z + t * slope;
+        if z < entity.position.z || z > entity.position.z + height {
+            continue;
+        }
+        match best {
+            Some((_, d)) if d <= t => {}
+            _ => best = Some((entity.id, t)),
+        }
+    }
+
+    match best {
+        Some((id, t)) => ShotResult::Hit(id, t),
+        None => ShotResult::Miss(wall),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An enemy in line with the player, and a thin wall of `shape` in the
+    // player's own tile
+    fn shoot(shape: u8, texture: u8, (x, y, a): (f32, f32, f32), enemy: (f32, f32)) -> ShotResult {
+        let settings = settings::Settings::init();
+        let mut game_map = map::GameMap::empty();
+        game_map.thin_array[10][10] = map::ThinWall::new(10, 10, shape, texture, 255, 0.0, map::CEILING);
+        let mut entity_list = entities::EntityList::new();
+        entity_list.spawn(entities::EntityKind::Enemy, enemy.0, enemy.1);
+        let mut player = player::Player::new(&settings);
+        player.place(x, y, a, &settings);
+        hitscan(&game_map, &entity_list, &player, 20.0)
+    }
+
+    fn missed_at(result: ShotResult, expected: f32) -> bool {
+        matches!(result, ShotResult::Miss(dist) if (dist - expected).abs() < 1e-4)
+    }
+
+    #[test]
+    fn thin_walls_in_the_shooters_tile_stop_shots() {
+        let diagonal = shoot(map::EDGE_DIAGONAL, 1, (10.2, 10.8, 0.0), (13.5, 10.8));
+        assert!(missed_at(diagonal, 0.6), "{:?}", diagonal);
+        let edge = shoot(map::EDGE_WEST, 1, (10.5, 10.5, settings::PI), (7.5, 10.5));
+        assert!(missed_at(edge, 0.5), "{:?}", edge);
+        // see-through bars let the shot pass
+        let bars = shoot(map::EDGE_WEST, map::SEE_THROUGH[0], (10.5, 10.5, settings::PI), (7.5, 10.5));
+        assert!(matches!(bars, ShotResult::Hit(..)), "{:?}", bars);
+    }
+}
@@ -0,0 +1,597 @@
+use crate::{input, richtext, settings, text};
+
+// Positions along a slider track
+const SLIDER_STEPS: usize = 10;
+// Space between the widgets of a row, in spaces
+const ROW_GAP: usize = 3;
+// Labels wrap at this part of the screen width
+const WRAP: f32 = 0.6;
+
+pub type Getter<T> = Box<dyn Fn(&settings::Settings) -> T>;
+pub type Setter<T> = Box<dyn Fn(&mut settings::Settings, T)>;
+
+pub struct Button {
+    pub id: &'static str,
+    pub label: String,
+}
+
+pub struct Slider {
+    pub id: &'static str,
+    pub label: String,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub get: Getter<f32>,
+    pub set: Setter<f32>,
+}
+
+pub struct Toggle {
+    pub id: &'static str,
+    pub label: String,
+    // shown for off and on
+    pub states: [String; 2],
+    pub get: Getter<bool>,
+    pub set: Setter<bool>,
+}
+
+pub struct Dropdown {
+    pub id: &'static str,
+    pub label: String,
+    pub options: Vec<String>,
+    pub get: Getter<usize>,
+    pub set: Setter<usize>,
+    pub open: bool,
+}
+
+pub enum Widget {
+    Label(String),
+    Heading(String),
+    Separator,
+    Button(Button),
+    Slider(Slider),
+    Toggle(Toggle),
+    Dropdown(Dropdown),
+    Column(Vec<Widget>),
+    Row(Vec<Widget>),
+}
+
+impl Widget {
+    pub fn label(text: &str) -> Widget {
+        Widget::Label(text.to_string())
+    }
+
+    pub fn heading(text: &str) -> Widget {
+        Widget::Heading(text.to_string())
+    }
+
+    pub fn button(id: &'static str, label: &str) -> Widget {
+        Widget::Button(Button { id, label: label.to_string() })
+    }
+
+    pub fn slider(
+        id: &'static str,
+        label: &str,
+        (min, max, step): (f32, f32, f32),
+        get: impl Fn(&settings::Settings) -> f32 + 'static,
+        set: impl Fn(&mut settings::Settings, f32) + 'static,
+    ) -> Widget {
+        Widget::Slider(Slider {
+            id,
+            label: label.to_string(),
+            min,
+            max,
+            step,
+            get: Box::new(get),
+            set: Box::new(set),
+        })
+    }
+
+    pub fn toggle(
+        id: &'static str,
+        label: &str,
+        [off, on]: [&str; 2],
+        get: impl Fn(&settings::Settings) -> bool + 'static,
+        set: impl Fn(&mut settings::Settings, bool) + 'static,
+    ) -> Widget {
+        Widget::Toggle(Toggle {
+            id,
+            label: label.to_string(),
+            states: [off.to_string(), on.to_string()],
+            get: Box::new(get),
+            set: Box::new(set),
+        })
+    }
+
+    pub fn dropdown(
+        id: &'static str,
+        label: &str,
+        options: Vec<&str>,
+        get: impl Fn(&settings::Settings) -> usize + 'static,
+        set: impl Fn(&mut settings::Settings, usize) + 'static,
+    ) -> Widget {
+        Widget::Dropdown(Dropdown {
+            id,
+            label: label.to_string(),
+            options: options.iter().map(|o| o.to_string()).collect(),
+            get: Box::new(get),
+            set: Box::new(set),
+            open: false,
+        })
+    }
+
+    // Interactive widgets in depth-first order, the order `Target` counts in
+    fn controls<'a>(&'a mut self, out: &mut Vec<&'a mut Widget>) {
+        match self {
+            Widget::Column(children) | Widget::Row(children) => {
+                for child in children.iter_mut() {
+                    child.controls(out);
+                }
+            }
+            Widget::Label(_) | Widget::Heading(_) | Widget::Separator => {}
+            _ => out.push(self),
+        }
+    }
+
+    // One line per widget, rows put their children side by side on one
+    // line, an open dropdown lists its options under itself and labels
+    // may wrap over several
+    fn lines(&self, fonts: &text::Fonts, settings: &settings::Settings, count: &mut usize, lines: &mut Vec<Vec<Run>>) {
+        match self {
+            Widget::Column(children) => {
+                for child in children {
+                    child.lines(fonts, settings, count, lines);
+                }
+            }
+            Widget::Row(children) => {
+                let mut row = Vec::new();
+                let mut below = Vec::new();
+                for child in children {
+                    let mut child_lines = Vec::new();
+                    child.lines(fonts, settings, count, &mut child_lines);
+                    let mut child_lines = child_lines.into_iter();
+                    if let Some(first) = child_lines.next() {
+                        if !row.is_empty() {
+                            row.push(Run::new(fonts, &" ".repeat(ROW_GAP), None));
+                        }
+                        row.extend(first);
+                    }
+                    below.extend(child_lines);
+                }
+                lines.push(row);
+                lines.extend(below);
+            }
+            Widget::Label(markup) => lines.extend(Run::wrap(fonts, settings, markup, text::SANS)),
+            Widget::Heading(markup) => lines.extend(Run::wrap(fonts, settings, markup, text::SANS_LARGE)),
+            Widget::Separator => lines.push(vec![Run::new(fonts, "-", None)]),
+            Widget::Button(button) => {
+                lines.push(vec![Run::control(fonts, &button.label, Some(Target::Control(*count)))]);
+                *count += 1;
+            }
+            Widget::Toggle(toggle) => {
+                let state = &toggle.states[(toggle.get)(settings) as usize];
+                lines.push(vec![Run::control(fonts, &format!("{}: {}", toggle.label, state), Some(Target::Control(*count)))]);
+                *count += 1;
+            }
+            Widget::Slider(slider) => {
+                let value = (slider.get)(settings);
+                let t = ((value - slider.min) / (slider.max - slider.min)).clamp(0.0, 1.0);
+                let knob = (t * SLIDER_STEPS as f32).round() as usize;
+                let track: String = (0..=SLIDER_STEPS).map(|k| if k == knob { '●' } else { '-' }).collect();
+                lines.push(vec![
+                    Run::new(fonts, &format!("{} ", slider.label), None),
+                    Run::new(fonts, &track, Some(Target::Track(*count))),
+                    Run::new(fonts, &format!(" {}", value), None),
+                ]);
+                *count += 1;
+            }
+            Widget::Dropdown(dropdown) => {
+                let current = dropdown.options.get((dropdown.get)(settings)).cloned().unwrap_or_default();
+                let arrow = if dropdown.open { "<" } else { ">" };
+                let text = format!("{}: {} {}", dropdown.label, current, arrow);
+                lines.push(vec![Run::control(fonts, &text, Some(Target::Control(*count)))]);
+                if dropdown.open {
+                    for (k, option) in dropdown.options.iter().enumerate() {
+                        lines.push(vec![Run::control(fonts, option, Some(Target::Option(*count, k)))]);
+                    }
+                }
+                *count += 1;
+            }
+        }
+    }
+}
+
+// What clicking a piece of text does: the n-th control, option k of the
+// n-th control, or a point on the track of the n-th control
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Control(usize),
+    Option(usize, usize),
+    Track(usize),
+}
+
+// A piece of text placed on screen, measured when it is made
+struct Run {
+    text: String,
+    font: usize,
+    tint: Option<richtext::Color>,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    target: Option<Target>,
+}
+
+impl Run {
+    fn new(fonts: &text::Fonts, text: &str, target: Option<Target>) -> Run {
+        let (width, height) = (fonts.width(text::SANS, text), fonts.line_height(text::SANS));
+        Run { text: text.to_string(), font: text::SANS, tint: None, x: 0.0, y: 0.0, width, height, target }
+    }
+
+    // The text of a button, toggle or dropdown, sized by the box rich text
+    // lays it out in
+    fn control(fonts: &text::Fonts, text: &str, target: Option<Target>) -> Run {
+        let (width, height) = richtext::measure(fonts, &richtext::escape(text), text::SANS, None);
+        Run { width, height, ..Run::new(fonts, text, target) }
+    }
+
+    // Markup broken into lines that fit the menu
+    fn wrap(fonts: &text::Fonts, settings: &settings::Settings, markup: &str, font: usize) -> Vec<Vec<Run>> {
+        let max_width = settings.screen_width_f * WRAP;
+        let text_box = richtext::TextBox::new(fonts, markup, font, Some(max_width), richtext::Align::Center);
+        let lines = text_box.lines.into_iter().map(|line| {
+            let height = line.height;
+            let runs: Vec<Run> = line
+                .runs
+                .into_iter()
+                .map(|run| Run {
+                    font: run.style.font,
+                    tint: run.style.color,
+                    width: run.width,
+                    height,
+                    ..Run::new(fonts, &run.text, None)
+                })
+                .collect();
+            // an empty line still takes up a line
+            if runs.is_empty() { vec![Run { font, height, ..Run::new(fonts, "", None) }] } else { runs }
+        });
+        lines.collect()
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x > self.x && x < self.x + self.width && y > self.y && y < self.y + self.height
+    }
+}
+
+// A retained widget tree shown as a centred panel. The focused control is
+// the one under the mouse or the one moved to with the keys or a pad.
+pub struct Menu {
+    pub name: &'static str,
+    pub root: Widget,
+    pub focus: Option<Target>,
+    pub drag: Option<usize>,
+    pub mouse: (f32, f32),
+}
+
+impl Menu {
+    pub fn new(name: &'static str, widgets: Vec<Widget>) -> Menu {
+        Menu { name, root: Widget::Column(widgets), focus: None, drag: None, mouse: (0.0, 0.0) }
+    }
+
+    fn layout(&self, fonts: &text::Fonts, settings: &settings::Settings) -> Vec<Run> {
+        let mut lines: Vec<Vec<Run>> = Vec::new();
+        self.root.lines(fonts, settings, &mut 0, &mut lines);
+
+        // a line is as tall as its largest font
+        let heights: Vec<f32> = lines.iter().map(|line| line.iter().map(|run| run.height).fold(0.0, f32::max)).collect();
+        let mut y = (settings.screen_height_f - heights.iter().sum::<f32>()) * 0.5;
+        let mut runs = Vec::new();
+        for (line, height) in lines.into_iter().zip(heights) {
+            let width: f32 = line.iter().map(|run| run.width).sum();
+            let mut x = (settings.screen_width_f - width) * 0.5;
+            for mut run in line {
+                run.x = x;
+                run.y = y;
+                x += run.width;
+                runs.push(run);
+            }
+            y += height;
+        }
+        runs
+    }
+
+    // Hover, clicks and slider drags. Returns the id of the control that
+    // was pressed or changed.
+    pub fn update(
+        &mut self,
+        input_state: &input::InputState,
+        fonts: &text::Fonts,
+        settings: &mut settings::Settings,
+    ) -> Option<&'static str> {
+        let (x, y) = (input_state.mouse.x, input_state.mouse.y);
+        let runs = self.layout(fonts, settings);
+        let hit = runs.iter().find(|run| run.target.is_some() && run.contains(x, y));
+        // a still mouse leaves the focus where the keys put it
+        if self.mouse != (x, y) {
+            self.mouse = (x, y);
+            self.focus = hit.and_then(|run| run.target);
+        }
+
+        if !input_state.mouse.left {
+            self.drag = None;
+        }
+        let clicked = input_state.mouse.left && input_state.apply_change;
+
+        if let Some(n) = self.drag {
+            if let Some(track) = runs.iter().find(|run| run.target == Some(Target::Track(n))) {
+                return slide(self.control(n)?, settings, (x - track.x) / track.width);
+            }
+        }
+        if !clicked {
+            return None;
+        }
+
+        // a click anywhere but on an open dropdown closes it
+        let target = hit.and_then(|run| run.target);
+        let mut controls = Vec::new();
+        self.root.controls(&mut controls);
+        for (n, control) in controls.iter_mut().enumerate() {
+            if let Widget::Dropdown(dropdown) = control {
+                let on_it = matches!(target, Some(Target::Control(m)) | Some(Target::Option(m, _)) if m == n);
+                if !on_it {
+                    dropdown.open = false;
+                }
+            }
+        }
+
+        match target? {
+            Target::Track(n) => {
+                self.drag = Some(n);
+                let track = hit?;
+                slide(self.control(n)?, settings, (x - track.x) / track.width)
+            }
+            target => self.activate(target, settings),
+        }
+    }
+
+    // Keyboard and pad navigation. Up and down go through the controls,
+    // left and right adjust the focused one or else move like up and down.
+    pub fn navigate(&mut self, nav: input::Nav, fonts: &text::Fonts, settings: &mut settings::Settings) -> Option<&'static str> {
+        let targets: Vec<Target> = self.layout(fonts, settings).iter().filter_map(|run| run.target).collect();
+        if targets.is_empty() {
+            return None;
+        }
+        let len = targets.len();
+        let pos = match self.focus.and_then(|focus| targets.iter().position(|&t| t == focus)) {
+            Some(pos) => pos,
+            // the first key press only shows where the focus is, except
+            // activating which goes straight to the first control
+            None => {
+                self.focus = Some(targets[0]);
+                if nav != input::Nav::Activate {
+                    return None;
+                }
+                0
+            }
+        };
+        let step = match nav {
+            input::Nav::Up => -1,
+            input::Nav::Down => 1,
+            input::Nav::Left => -1,
+            input::Nav::Right => 1,
+            input::Nav::Activate => return self.activate(targets[pos], settings),
+            input::Nav::Back => return None,
+        };
+        if matches!(nav, input::Nav::Left | input::Nav::Right) {
+            let n = match targets[pos] {
+                Target::Control(n) | Target::Track(n) => n,
+                Target::Option(n, _) => n,
+            };
+            if let Some(id) = nudge(self.control(n)?, settings, step) {
+                return Some(id);
+            }
+        }
+        self.focus = Some(targets[(pos as i32 + step).rem_euclid(len as i32) as usize]);
+        None
+    }
+
+    // Closes an open dropdown, false if there was none
+    pub fn close_popups(&mut self) -> bool {
+        let mut controls = Vec::new();
+        self.root.controls(&mut controls);
+        let mut closed = false;
+        for control in controls {
+            if let Widget::Dropdown(dropdown) = control {
+                closed |= dropdown.open;
+                dropdown.open = false;
+            }
+        }
+        if let Some(Target::Option(n, _)) = self.focus {
+            self.focus = Some(Target::Control(n));
+        }
+        closed
+    }
+
+    fn control(&mut self, n: usize) -> Option<&mut Widget> {
+        let mut controls = Vec::new();
+        self.root.controls(&mut controls);
+        controls.into_iter().nth(n)
+    }
+
+    fn activate(&mut self, target: Target, settings: &mut settings::Settings) -> Option<&'static str> {
+        match target {
+            Target::Control(n) => match self.control(n)? {
+                Widget::Button(button) => Some(button.id),
+                Widget::Toggle(toggle) => {
+                    let on = (toggle.get)(settings);
+                    (toggle.set)(settings, !on);
+                    Some(toggle.id)
+                }
+                Widget::Dropdown(dropdown) => {
+                    dropdown.open = !dropdown.open;
+                    None
+                }
+                _ => None,
+            },
+            Target::Option(n, k) => {
+                self.focus = Some(Target::Control(n));
+                match self.control(n)? {
+                    Widget::Dropdown(dropdown) => {
+                        (dropdown.set)(settings, k);
+                        dropdown.open = false;
+                        Some(dropdown.id)
+                    }
+                    _ => None,
+                }
+            }
+            Target::Track(_) => None,
+        }
+    }
+
+    // Lays the menu out into the text lines the GUI pipeline draws
+    pub fn fill(&self, gui: &mut text::GUI, fonts: &text::Fonts, settings: &settings::Settings) {
+        let runs = self.layout(fonts, settings);
+        if runs.is_empty() {
+            return;
+        }
+        let left = runs.iter().map(|run| run.x).fold(settings.screen_width_f, f32::min);
+        let right = runs.iter().map(|run| run.x + run.width).fold(0.0, f32::max);
+
+        gui.lines = runs.iter().map(|run| run.text.clone()).collect();
+        gui.line_x = runs.iter().map(|run| run.x).collect();
+        gui.line_y = runs.iter().map(|run| run.y).collect();
+        gui.line_width = runs.iter().map(|run| run.width).collect();
+        gui.line_font = runs.iter().map(|run| run.font).collect();
+        gui.line_tint = runs.iter().map(|run| run.tint).collect();
+        // a slider stays lit while it is dragged
+        let active = |run: &Run| run.target.is_some() && (run.target == self.focus || run.target == self.drag.map(Target::Track));
+        gui.line_active = runs.iter().map(|run| if active(run) { 1 } else { 0 }).collect();
+        gui.x0 = left;
+        gui.y0 = runs.first().map(|run| run.y).unwrap_or(0.0);
+        gui.max_width = right - left;
+    }
+}
+
+// Sets a slider from a position along its track, 0 at the left end and 1
+// at the right one
+fn slide(control: &mut Widget, settings: &mut settings::Settings, t: f32) -> Option<&'static str> {
+    match control {
+        Widget::Slider(slider) => {
+            let value = slider.min + t.clamp(0.0, 1.0) * (slider.max - slider.min);
+            let value = slider.min + ((value - slider.min) / slider.step).round() * slider.step;
+            if (value - (slider.get)(settings)).abs() > settings::TOLERANCE {
+                (slider.set)(settings, value);
+                Some(slider.id)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// Left and right on a control: moves a slider by one step, turns a toggle
+// over or picks the next or previous option of a dropdown
+fn nudge(control: &mut Widget, settings: &mut settings::Settings, step: i32) -> Option<&'static str> {
+    match control {
+        Widget::Slider(slider) => {
+            let value = ((slider.get)(settings) + step as f32 * slider.step).clamp(slider.min, slider.max);
+            if (value - (slider.get)(settings)).abs() > settings::TOLERANCE {
+                (slider.set)(settings, value);
+            }
+            Some(slider.id)
+        }
+        Widget::Toggle(toggle) => {
+            let on = (toggle.get)(settings);
+            (toggle.set)(settings, !on);
+            Some(toggle.id)
+        }
+        Widget::Dropdown(dropdown) if !dropdown.open && !dropdown.options.is_empty() => {
+            let len = dropdown.options.len() as i32;
+            let k = ((dropdown.get)(settings) as i32 + step).rem_euclid(len);
+            (dropdown.set)(settings, k as usize);
+            Some(dropdown.id)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn lines_stack_down_the_middle_of_the_screen() {
+        let fonts = text::Fonts::load();
+        let settings = settings::Settings::init();
+        let menu = Menu::new("test", vec![
+            Widget::heading("Title"),
+            Widget::Separator,
+            Widget::button("first", "First"),
+            Widget::Row(vec![Widget::button("yes", "Yes"), Widget::button("no", "No")]),
+        ]);
+        let runs = menu.layout(&fonts, &settings);
+        // heading, separator, button, then yes, the gap and no on one line
+        assert_eq!(runs.len(), 6);
+        for pair in runs[..4].windows(2) {
+            assert!(close(pair[1].y, pair[0].y + pair[0].height));
+        }
+        assert!(runs[3..].iter().all(|run| run.y == runs[3].y));
+        for pair in runs[3..].windows(2) {
+            assert!(close(pair[1].x, pair[0].x + pair[0].width));
+        }
+
+        let middle = settings.screen_width_f * 0.5;
+        for run in runs[..3].iter() {
+            assert!(close(run.x + 0.5 * run.width, middle));
+        }
+        assert!(close(0.5 * (runs[3].x + runs[5].x + runs[5].width), middle));
+        let bottom = runs[5].y + runs[5].height;
+        assert!(close(runs[0].y, settings.screen_height_f - bottom));
+
+        let targets: Vec<Option<Target>> = runs.iter().map(|run| run.target).collect();
+        assert_eq!(targets, vec![
+            None,
+            None,
+            Some(Target::Control(0)),
+            Some(Target::Control(1)),
+            None,
+            Some(Target::Control(2)),
+        ]);
+    }
+
+    fn scale_slider() -> Widget {
+        Widget::slider("scale", "Scale", (0.5, 2.0, 0.25), |s| s.hud_scale, |s, v| s.hud_scale = v)
+    }
+
+    #[test]
+    fn sliding_clamps_to_the_track_and_snaps_to_steps() {
+        let mut settings = settings::Settings::init();
+        let mut slider = scale_slider();
+        assert_eq!(slide(&mut slider, &mut settings, -1.0), Some("scale"));
+        assert_eq!(settings.hud_scale, 0.5);
+        // already there, nothing changes
+        assert_eq!(slide(&mut slider, &mut settings, -0.5), None);
+        slide(&mut slider, &mut settings, 2.0);
+        assert_eq!(settings.hud_scale, 2.0);
+        slide(&mut slider, &mut settings, 0.3);
+        assert!(close(settings.hud_scale, 1.0));
+    }
+
+    #[test]
+    fn nudging_stops_at_the_ends() {
+        let mut settings = settings::Settings::init();
+        let mut slider = scale_slider();
+        settings.hud_scale = 1.75;
+        nudge(&mut slider, &mut settings, 1);
+        assert!(close(settings.hud_scale, 2.0));
+        nudge(&mut slider, &mut settings, 1);
+        assert!(close(settings.hud_scale, 2.0));
+        settings.hud_scale = 0.5;
+        assert_eq!(nudge(&mut slider, &mut settings, -1), Some("scale"));
+        assert!(close(settings.hud_scale, 0.5));
+    }
+}